@@ -98,6 +98,17 @@ export default defineConfig({
   // outputDir: 'test-results/',
 
   /* Run your local dev server before starting the tests */
+  webServer: {
+    /* Mock OpenAI server used by the translate tests, the app itself is started by cargo leptos end-to-end */
+    command: "cargo run --bin mock_openai --features ssr",
+    cwd: "..",
+    port: 3100,
+    env: {
+      TRANSLATION_FIXTURE_FILE: "end2end/fixtures/translations.json",
+    },
+    reuseExistingServer: !process.env.CI,
+    timeout: 300 * 1000,
+  },
   // webServer: {
   //   command: 'npm run start',
   //   port: 3000,
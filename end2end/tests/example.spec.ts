@@ -3,7 +3,7 @@ import { test, expect } from "@playwright/test";
 test("homepage has title and heading text", async ({ page }) => {
   await page.goto("http://localhost:3000/");
 
-  await expect(page).toHaveTitle("Welcome to Leptos");
+  await expect(page).toHaveTitle("Drama Study Tool");
 
-  await expect(page.locator("h1")).toHaveText("Welcome to Leptos!");
+  await expect(page.locator("header .fs-4")).toHaveText("Drama Study Tool");
 });
@@ -0,0 +1,294 @@
+import { test, expect } from "@playwright/test";
+import * as fs from "fs";
+
+// These tests need the server to be pointed at the mock OpenAI server started in playwright.config.ts:
+//   TRANSLATION_PROVIDER=openai OPENAI_API_URL=http://127.0.0.1:3100 OPENAI_API_DEPLOYMENT=mock
+
+test("translates text input", async ({ page }) => {
+  await page.goto("http://localhost:3000/");
+
+  await page.locator("input[name=input_text]").fill("안녕하세요");
+  await page.locator("input[type=submit]").click();
+
+  await expect(page.locator(".translation-output-box h4")).toHaveText("Hello");
+  await expect(page.getByText("Polite Ending")).toBeVisible();
+
+  await page.getByRole("button", { name: "Add word" }).first().click();
+  await expect(page.getByRole("button", { name: "Remove" })).toHaveCount(1);
+});
+
+test("translates cues from an uploaded subtitle file", async ({ page }) => {
+  await page.goto("http://localhost:3000/");
+
+  await page.getByText("Upload", { exact: true }).click();
+  await page.locator("input[type=file]").first().setInputFiles("fixtures/episode.srt");
+
+  await expect(page.locator(".subtitle-text")).toHaveText("안녕하세요");
+  await page.getByRole("button", { name: "Forward ▶" }).click();
+  await expect(page.locator(".subtitle-text")).toHaveText("사전을 못 찾아");
+
+  await page.getByRole("button", { name: "Translate", exact: true }).click();
+
+  await expect(page.locator(".translation-output-box h4")).toHaveText("I can't find the dictionary");
+  await expect(page.getByText("Object Marker")).toBeVisible();
+});
+
+test("pre-translates a whole subtitle file", async ({ page }) => {
+  await page.goto("http://localhost:3000/");
+
+  await page.getByText("Upload", { exact: true }).click();
+  await page.locator("input[type=file]").first().setInputFiles("fixtures/episode.srt");
+  await expect(page.locator(".subtitle-text")).toHaveText("안녕하세요");
+
+  await page.getByRole("button", { name: "Translate all" }).click();
+  await expect(page.locator(".batch-translation")).toContainText("Done");
+
+  // Stepping to a cue shows its translation without asking for it
+  await page.getByRole("button", { name: "Forward ▶" }).click();
+  await expect(page.locator(".translation-output-box h4")).toHaveText("I can't find the dictionary");
+});
+
+test("shows the token usage of translations", async ({ page }) => {
+  await page.goto("http://localhost:3000/");
+
+  await page.locator("input[name=input_text]").fill("안녕하세요");
+  await page.locator("input[type=submit]").click();
+  await expect(page.locator(".translation-output-box h4")).toHaveText("Hello");
+
+  await page.getByRole("link", { name: "Usage" }).click();
+  await expect(page.getByRole("heading", { name: "This browser" })).toBeVisible();
+  await expect(page.locator("table").first()).toContainText("Today");
+});
+
+test("translates cues from an uploaded WebVTT file", async ({ page }) => {
+  await page.goto("http://localhost:3000/");
+
+  await page.getByText("Upload", { exact: true }).click();
+  await page.locator("input[type=file]").first().setInputFiles("fixtures/episode.vtt");
+
+  await expect(page.locator(".subtitle-text")).toHaveText("안녕하세요");
+  await page.locator(".subtitle-timing input").fill("00:00:04");
+  await page.locator(".subtitle-timing input").press("Enter");
+  await expect(page.locator(".subtitle-text")).toHaveText("사전을 못 찾아");
+
+  await page.getByRole("button", { name: "Translate", exact: true }).click();
+  await expect(page.locator(".translation-output-box h4")).toHaveText("I can't find the dictionary");
+});
+
+test("navigates the dialogue styles of an uploaded ASS file", async ({ page }) => {
+  await page.goto("http://localhost:3000/");
+
+  await page.getByText("Upload", { exact: true }).click();
+  await page.locator("input[type=file]").first().setInputFiles("fixtures/episode.ass");
+
+  // Signs are skipped by default
+  await expect(page.locator(".subtitle-text")).toHaveText("안녕하세요");
+  await page.getByRole("button", { name: "Forward ▶" }).click();
+  await expect(page.locator(".subtitle-text")).toHaveText("사전을 못 찾아");
+
+  await page.getByLabel("Sign").check();
+  await page.getByRole("button", { name: "Forward ▶" }).click();
+  await expect(page.locator(".subtitle-text")).toHaveText("편의점");
+});
+
+test("picks the Korean track of an uploaded SAMI file", async ({ page }) => {
+  await page.goto("http://localhost:3000/");
+
+  await page.getByText("Upload", { exact: true }).click();
+  await page.locator("input[type=file]").first().setInputFiles("fixtures/episode.smi");
+
+  await expect(page.locator(".subtitle-text")).toHaveText("안녕하세요");
+  await page.locator(".subtitle-track").selectOption({ label: "English (en-US)" });
+  await expect(page.locator(".subtitle-text")).toHaveText("Hello");
+});
+
+test("decodes an uploaded CP949 subtitle file", async ({ page }) => {
+  await page.goto("http://localhost:3000/");
+
+  await page.getByText("Upload", { exact: true }).click();
+  await page.locator("input[type=file]").first().setInputFiles("fixtures/episode-cp949.srt");
+
+  await expect(page.locator(".subtitle-text")).toHaveText("안녕하세요");
+  await expect(page.locator(".subtitle-encoding select")).toHaveValue("cp949");
+
+  await page.locator(".subtitle-encoding select").selectOption("utf-8");
+  await expect(page.locator(".subtitle-text")).not.toHaveText("안녕하세요");
+});
+
+test("shows the official translation of an uploaded second track", async ({ page }) => {
+  await page.goto("http://localhost:3000/");
+
+  await page.getByText("Upload", { exact: true }).click();
+  await page.locator("input[type=file]").first().setInputFiles("fixtures/episode.srt");
+  await page.locator(".subtitle-reference-upload input[type=file]").setInputFiles("fixtures/episode.en.srt");
+
+  // Split in two in the English file
+  await expect(page.locator(".subtitle-reference")).toHaveText("Hi. How are you?");
+  // Merged with the next cue in the English file
+  await page.getByRole("button", { name: "Forward ▶" }).click();
+  await expect(page.locator(".subtitle-reference")).toHaveText("I can't find the dictionary, thanks.");
+});
+
+test("exports the pre-translated cues as bilingual subtitles", async ({ page }) => {
+  await page.goto("http://localhost:3000/");
+
+  await page.getByText("Upload", { exact: true }).click();
+  await page.locator("input[type=file]").first().setInputFiles("fixtures/episode.srt");
+  await page.getByRole("button", { name: "Translate all" }).click();
+  await expect(page.locator(".batch-translation")).toContainText("Done");
+
+  await page.locator(".subtitle-export select").selectOption("vtt");
+  const downloadPromise = page.waitForEvent("download");
+  await page.getByRole("button", { name: "Download" }).click();
+  const download = await downloadPromise;
+
+  expect(download.suggestedFilename()).toBe("episode.annotated.vtt");
+  const exported = fs.readFileSync(await download.path(), "utf-8");
+  expect(exported).toContain("00:00:04.000 --> 00:00:06.000\n사전을 못 찾아\n<c.translation><i>I can't find the dictionary</i></c>");
+});
+
+test("exports the signs that aren't navigated without a translation", async ({ page }) => {
+  await page.goto("http://localhost:3000/");
+
+  await page.getByText("Upload", { exact: true }).click();
+  await page.locator("input[type=file]").first().setInputFiles("fixtures/episode.ass");
+  await page.getByRole("button", { name: "Translate all" }).click();
+  await expect(page.locator(".batch-translation")).toContainText("Done");
+
+  const downloadPromise = page.waitForEvent("download");
+  await page.getByRole("button", { name: "Download" }).click();
+  const exported = fs.readFileSync(await (await downloadPromise).path(), "utf-8");
+
+  expect(exported).toContain("00:00:02,000 --> 00:00:04,000\n편의점\n\n");
+  expect(exported).toContain("00:00:04,000 --> 00:00:06,000\n사전을 못 찾아\n<i>I can't find the dictionary</i>");
+});
+
+test("finds cues by their text and jumps to them", async ({ page }) => {
+  await page.goto("http://localhost:3000/");
+
+  await page.getByText("Upload", { exact: true }).click();
+  await page.locator("input[type=file]").first().setInputFiles("fixtures/episode.srt");
+  await expect(page.locator(".subtitle-text")).toHaveText("안녕하세요");
+
+  await page.getByPlaceholder("Search subtitles").fill("사 전을");
+  await expect(page.locator(".search-matches li")).toHaveCount(0);
+  await page.locator(".subtitle-search select").selectOption("hangul");
+  await expect(page.locator(".search-matches li")).toHaveCount(1);
+
+  await page.locator(".search-matches li").first().click();
+  await expect(page.locator(".subtitle-text")).toHaveText("사전을 못 찾아");
+
+  await page.locator(".subtitle-search select").selectOption("regex");
+  await page.getByPlaceholder("Search subtitles").fill("^(안녕|고마)");
+  await page.getByRole("button", { name: "Next match ▶" }).click();
+  await expect(page.locator(".subtitle-text")).toHaveText("고마워");
+  await page.getByRole("button", { name: "Next match ▶" }).click();
+  await expect(page.locator(".subtitle-text")).toHaveText("안녕하세요");
+});
+
+test("lists the words of an uploaded file to study first", async ({ page }) => {
+  await page.goto("http://localhost:3000/");
+
+  await page.getByText("Upload", { exact: true }).click();
+  await page.locator("input[type=file]").first().setInputFiles("fixtures/episode.srt");
+  await page.getByRole("button", { name: "Analyze vocabulary" }).click();
+
+  // Particles are stripped, 사전을 is counted as 사전
+  await expect(page.locator(".study-list tbody tr").filter({ hasText: "사전" })).toHaveCount(1);
+  await expect(page.locator(".study-list")).toContainText("3 cues");
+});
+
+test("shows the dictionary form of conjugated words and saves each lemma once", async ({ page }) => {
+  await page.goto("http://localhost:3000/");
+
+  await page.locator("input[name=input_text]").fill("고마워");
+  await page.locator("input[type=submit]").click();
+
+  await expect(page.locator(".vocab-details").first()).toHaveText("(고맙다, adjective, informal (-어))");
+
+  await page.getByRole("button", { name: "Add word" }).first().click();
+  await expect(page.getByRole("button", { name: "Remove" })).toHaveCount(1);
+  await expect(page.getByRole("button", { name: "Saved" })).toBeDisabled();
+});
+
+test("shows the romanization, Hanja, register and an example of each word", async ({ page }) => {
+  await page.goto("http://localhost:3000/");
+
+  await page.locator("input[name=input_text]").fill("안녕하세요");
+  await page.locator("input[type=submit]").click();
+
+  await expect(page.locator(".vocab-romanization").first()).toHaveText("annyeonghaseyo");
+  await expect(page.locator(".vocab-notes").first()).toHaveText("Hanja: 安寧 · Register: polite");
+  await expect(page.locator(".vocab-example").first()).toContainText("처음 뵙겠습니다");
+});
+
+test("romanizes the current cue and vocabulary, and hides it when toggled off", async ({ page }) => {
+  await page.goto("http://localhost:3000/");
+
+  await page.getByText("Upload", { exact: true }).click();
+  await page.locator("input[type=file]").first().setInputFiles("fixtures/episode.srt");
+  await expect(page.locator(".subtitle-romanization")).toHaveText("annyeonghaseyo");
+
+  await page.getByRole("button", { name: "Forward ▶" }).click();
+  await expect(page.locator(".subtitle-romanization")).toHaveText("sajeoneul mot chaja");
+
+  await page.getByRole("button", { name: "Translate", exact: true }).click();
+  await expect(page.locator(".vocab-romanization").first()).toHaveText("sajeon");
+
+  await page.getByLabel("Show romanization").uncheck();
+  await expect(page.locator(".subtitle-romanization")).toHaveCount(0);
+  await expect(page.locator(".vocab-romanization")).toHaveCount(0);
+});
+
+test("glosses the selected words of the study list and saves only those", async ({ page }) => {
+  await page.goto("http://localhost:3000/");
+
+  await page.getByText("Upload", { exact: true }).click();
+  await page.locator("input[type=file]").first().setInputFiles("fixtures/episode.srt");
+  await page.getByRole("button", { name: "Analyze vocabulary" }).click();
+
+  const rows = page.locator(".study-list tbody tr");
+  await expect(rows.filter({ hasText: "사전" })).toHaveCount(1);
+  for (const row of await rows.filter({ hasNot: page.locator("b", { hasText: /^사전$/ }) }).all()) {
+    await row.locator("input[type=checkbox]").uncheck();
+  }
+  await page.getByRole("button", { name: "Gloss and save selected" }).click();
+
+  await expect(page.locator(".study-list")).toContainText("Saved 1 words");
+  await expect(page.getByRole("button", { name: "Remove" })).toHaveCount(1);
+});
+
+test("tells the user to slow down when rate limited", async ({ page }) => {
+  // Answered like the rate limit middleware does, without using up the shared per-IP budget of the other tests
+  await page.route("**/api/stream_translate_info*", (route) =>
+    route.fulfill({
+      status: 429,
+      headers: { "Retry-After": "42" },
+      body: 'ServerError|{"Failed":{"TooManyRequests":{"retry_after_secs":42}}}',
+    }),
+  );
+  await page.goto("http://localhost:3000/");
+
+  await page.locator("input[name=input_text]").fill("안녕하세요");
+  await page.locator("input[type=submit]").click();
+
+  // A warning rather than a failure, since trying again later works
+  await expect(page.locator(".alert-warning")).toHaveText("You're translating too quickly. Try again in 42 seconds.");
+  await expect(page.locator(".translation-output-box h4")).toHaveText("Error");
+});
+
+test.describe("without JavaScript", () => {
+  test.use({ javaScriptEnabled: false });
+
+  test("posts the text input to the translation server function", async ({ page }) => {
+    await page.goto("http://localhost:3000/");
+
+    await page.locator("input[name=input_text]").fill("안녕하세요");
+    const request = page.waitForRequest(
+      (request) => request.method() === "POST" && request.url().includes("get_translate_info"),
+    );
+    await page.locator("input[type=submit]").click();
+
+    expect((await request).postData()).toContain("language_pair=ko-en");
+  });
+});
@@ -1,96 +1,1107 @@
-use leptos::logging::*;
-use az_openai_rs::*;
-use anyhow::{anyhow, Result};
-use serde_json::json;
 use serde::{Deserialize, Serialize};
 
+pub mod error;
+pub mod parsing;
+#[cfg(feature = "ssr")]
+pub mod providers;
+#[cfg(feature = "ssr")]
+pub mod retry;
+
+pub use error::TranslateError;
+use parsing::ParsePath;
+use crate::usage::TokenUsage;
+
+#[cfg(feature = "ssr")]
+use futures::channel::mpsc::UnboundedSender;
+#[cfg(feature = "ssr")]
+use futures::stream::{Stream, StreamExt};
+#[cfg(feature = "ssr")]
+use providers::{
+    ChatMessage, ChatRequest, Completion, CompletionChunk, ProviderError, ResponseSchema, TranslationProvider,
+};
+#[cfg(feature = "ssr")]
+use retry::{CircuitBreaker, RetryPolicy};
+#[cfg(feature = "ssr")]
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(feature = "ssr")]
+use std::sync::Arc;
+#[cfg(feature = "ssr")]
+use std::time::Duration;
+#[cfg(feature = "ssr")]
+use crate::languages::{Language, LanguagePair};
+#[cfg(feature = "ssr")]
+use crate::translation_cache::{CacheKey, TranslationCache};
+
+// Bump whenever the prompt changes so that cached translations from the old prompt are not reused
+#[cfg(feature = "ssr")]
+pub const PROMPT_VERSION: u32 = 5;
+
+// A failed translation or gloss and the tokens it had already cost, e.g. for a stream that broke off or an answer
+// that couldn't be repaired. Those are billed too.
+#[cfg(feature = "ssr")]
+pub type BilledError = (TranslateError, TokenUsage);
+
+// How many times the model is asked to fix an answer that couldn't be parsed
+#[cfg(feature = "ssr")]
+const MAX_REPAIR_ATTEMPTS: usize = 1;
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "ssr", derive(schemars::JsonSchema))]
 pub struct VocabularyInfo {
     pub word: String,
     pub translation: String,
+    // Dictionary form, as in 찾다 for 찾아. The analyzer's takes precedence for the languages it knows.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lemma: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub part_of_speech: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub romanization: Option<String>,
+    // Chinese characters of Sino-Korean words
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hanja: Option<String>,
+    // Formality, as in "neutral", "formal", "polite", "casual" or "slang"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub register: Option<String>,
+    // Another sentence using the word, with its translation
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub example: Option<String>,
+    // The ending attached to the lemma, as in "polite (-아요)". Filled in by the analyzer rather than the model.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ssr", schemars(skip))]
+    pub conjugation: Option<String>,
+}
+
+impl VocabularyInfo {
+    // Saved words are told apart by this, so that 찾아 and 찾았어요 are both 찾다
+    pub fn dictionary_form(&self) -> &str {
+        self.lemma.as_deref().unwrap_or(self.word.trim())
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "ssr", derive(schemars::JsonSchema))]
 pub struct GrammarPointInfo {
     pub name: String,
+    #[serde(default)]
     pub relevant_text: String,
     pub description: String,
 }
 
+// Field order is the order the model writes them in, the translation comes first so that it can be shown while streaming
 #[derive(Clone, Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "ssr", derive(schemars::JsonSchema))]
 pub struct SubtitleTranslationInfo {
-    pub vocabulary: Vec<VocabularyInfo>,
+    #[cfg_attr(feature = "ssr", schemars(length(min = 1)))]
     pub translation: String,
+    #[serde(default)]
+    pub vocabulary: Vec<VocabularyInfo>,
+    #[serde(default)]
     pub grammar_points: Vec<GrammarPointInfo>,
 }
 
+// The answer to a gloss request, dictionary entries for a list of words
+#[cfg(feature = "ssr")]
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "ssr", derive(schemars::JsonSchema))]
+pub struct WordGlosses {
+    pub vocabulary: Vec<VocabularyInfo>,
+}
+
+// Information about how a translation was produced
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct TranslationMetadata {
+    pub parse_path: ParsePath,
+    // Tokens the translation cost, None when it came from the cache
+    #[serde(default)]
+    pub usage: Option<TokenUsage>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct TranslationResult {
+    pub info: SubtitleTranslationInfo,
+    pub metadata: TranslationMetadata,
+}
+
+// One line of the streaming translation endpoint, events are sent as JSON separated by newlines
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum TranslationStreamEvent {
+    // The next piece of the model's answer
+    Token(String),
+    // The parsed (and possibly repaired) translation, which replaces whatever was built from the tokens
+    Done(TranslationResult),
+    Failed(TranslateError),
+}
+
+#[cfg(feature = "ssr")]
+impl TranslationStreamEvent {
+    pub fn to_line(&self) -> String {
+        let json = serde_json::to_string(self).unwrap_or_else(|e| {
+            serde_json::to_string(&Self::Failed(TranslateError::Other(e.to_string()))).unwrap_or_default()
+        });
+        format!("{}\n", json)
+    }
+}
+
+// Surrounding subtitles given to the model to resolve pronouns, speakers and split sentences.
+// Only the focused line itself is analysed.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct TranslationContext {
+    #[serde(default)]
+    pub previous_lines: Vec<String>,
+    #[serde(default)]
+    pub next_lines: Vec<String>,
+    #[serde(default)]
+    pub title: Option<String>,
+    // The official translation of the line, from a second subtitle file
+    #[serde(default)]
+    pub reference_translation: Option<String>,
+}
+
+impl TranslationContext {
+    pub fn is_empty(&self) -> bool {
+        self.previous_lines.is_empty()
+            && self.next_lines.is_empty()
+            && self.title.as_deref().map(str::trim).unwrap_or_default().is_empty()
+            && self.reference_translation().is_none()
+    }
+
+    fn reference_translation(&self) -> Option<&str> {
+        self.reference_translation.as_deref().map(str::trim).filter(|reference| !reference.is_empty())
+    }
+
+    #[cfg(feature = "ssr")]
+    fn prompt(&self) -> String {
+        let mut prompt = "Context for the next subtitle. Use it to work out who is speaking, what pronouns refer to and how sentences split across subtitles continue, but only translate and analyse the next subtitle itself.".to_string();
+
+        if let Some(title) = self.title.as_deref().filter(|title| !title.trim().is_empty()) {
+            prompt.push_str(&format!("\nShow: {}", title.trim()));
+        }
+
+        if !self.previous_lines.is_empty() {
+            prompt.push_str("\nPrevious subtitles:");
+            for line in &self.previous_lines {
+                prompt.push_str(&format!("\n- {}", line));
+            }
+        }
+
+        if !self.next_lines.is_empty() {
+            prompt.push_str("\nFollowing subtitles:");
+            for line in &self.next_lines {
+                prompt.push_str(&format!("\n- {}", line));
+            }
+        }
+
+        if let Some(reference) = self.reference_translation() {
+            prompt.push_str(&format!(
+                "\nOfficial translation of the next subtitle: {}\nBase the translation and the vocabulary glosses on it, so they match what the viewer reads in the official subtitles, unless it is clearly wrong.",
+                reference
+            ));
+        }
+
+        prompt
+    }
+}
+
+#[cfg(feature = "ssr")]
 #[derive(Clone, Debug)]
-pub struct AIInterface;
+pub struct AIInterface {
+    provider: Arc<dyn TranslationProvider>,
+    cache: Option<Arc<TranslationCache>>,
+    retry_policy: RetryPolicy,
+    circuit_breaker: Arc<CircuitBreaker>,
+    // Set once the provider answers a request with a response schema with a 400, e.g. an Azure deployment of a model
+    // without structured outputs. From then on the few-shot example is used instead.
+    structured_output_rejected: Arc<AtomicBool>,
+}
 
+#[cfg(feature = "ssr")]
 impl AIInterface {
-    pub fn new() -> Self {
-        Self {}
-    }
-
-    pub async fn translate(text: String) -> Result<SubtitleTranslationInfo> {
-        let json_example_string = json!({
-            "vocabulary": [
-                {
-                    "word": "사전",
-                    "translation": "dictionary"
-                },
-                {
-                    "word": "못",
-                    "translation": "not"
-                },
-                {
-                    "word": "찾아",
-                    "translation": "find"
+    pub fn new(provider: Arc<dyn TranslationProvider>) -> Self {
+        Self {
+            provider,
+            cache: None,
+            retry_policy: RetryPolicy::default(),
+            circuit_breaker: Arc::new(CircuitBreaker::new(5, Duration::from_secs(30))),
+            structured_output_rejected: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn with_cache(mut self, cache: Arc<TranslationCache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    pub fn with_circuit_breaker(mut self, circuit_breaker: CircuitBreaker) -> Self {
+        self.circuit_breaker = Arc::new(circuit_breaker);
+        self
+    }
+
+    pub fn provider_name(&self) -> &'static str {
+        self.provider.name()
+    }
+
+    fn uses_structured_output(&self) -> bool {
+        self.provider.supports_structured_output() && !self.structured_output_rejected.load(Ordering::Relaxed)
+    }
+
+    pub async fn translate(
+        &self,
+        text: String,
+        language_pair: LanguagePair,
+        context: Option<&TranslationContext>,
+    ) -> Result<TranslationResult, BilledError> {
+        self.translate_with_tokens(text, language_pair, context, None).await
+    }
+
+    // The cached translation, if any, without calling the model. Used to keep serving cached subtitles once
+    // the spending cap is reached.
+    pub fn cached_translation(
+        &self,
+        text: &str,
+        language_pair: LanguagePair,
+        context: Option<&TranslationContext>,
+    ) -> Option<TranslationResult> {
+        let cache = self.cache.as_ref()?;
+        let mut cached_info = cache.get(&cache_key(text, language_pair, context))?;
+        annotate_vocabulary(&mut cached_info.vocabulary, language_pair);
+        Some(TranslationResult {
+            info: cached_info,
+            metadata: TranslationMetadata {
+                parse_path: ParsePath::Cached,
+                usage: None,
+            },
+        })
+    }
+
+    // Like translate, but sends the model's answer as it is written before the final result. A failure ends the
+    // stream, with what it cost.
+    pub fn translate_stream(
+        &self,
+        text: String,
+        language_pair: LanguagePair,
+        context: Option<TranslationContext>,
+    ) -> impl Stream<Item = Result<TranslationStreamEvent, BilledError>> + Send + 'static {
+        let (sender, receiver) = futures::channel::mpsc::unbounded();
+        let ai_interface = self.clone();
+
+        // The translation finishes (and is cached) even if the client goes away
+        tokio::spawn(async move {
+            let event = ai_interface
+                .translate_with_tokens(text, language_pair, context.as_ref(), Some(&sender))
+                .await
+                .map(TranslationStreamEvent::Done);
+            let _ = sender.unbounded_send(event);
+        });
+
+        receiver
+    }
+
+    async fn translate_with_tokens(
+        &self,
+        text: String,
+        language_pair: LanguagePair,
+        context: Option<&TranslationContext>,
+        tokens: Option<&UnboundedSender<Result<TranslationStreamEvent, BilledError>>>,
+    ) -> Result<TranslationResult, BilledError> {
+        if text.trim().is_empty() {
+            return Err((TranslateError::EmptyInput, TokenUsage::default()));
+        }
+
+        if let Some(cached_result) = self.cached_translation(&text, language_pair, context) {
+            return Ok(cached_result);
+        }
+        let context = context.filter(|context| !context.is_empty());
+
+        let translation_request = |structured_output: bool| {
+            let mut messages = vec![ChatMessage::new("system", &language_pair.system_prompt())];
+
+            // Structured outputs enforce the format, otherwise a few-shot example shows the model what JSON to produce
+            let response_schema = if structured_output {
+                Some(ResponseSchema {
+                    name: "subtitle_translation_info".to_string(),
+                    schema: parsing::strict_translation_json_schema(),
+                })
+            } else {
+                let (example_input, example_output) = language_pair.few_shot_example();
+                messages.push(ChatMessage::new("user", example_input));
+                messages.push(ChatMessage::new("assistant", &example_output.to_string()));
+                None
+            };
+
+            // Context goes in its own message so that the last user message is always just the subtitle
+            if let Some(context) = context {
+                messages.push(ChatMessage::new("system", &context.prompt()));
+            }
+            messages.push(ChatMessage::new("user", &text));
+
+            ChatRequest {
+                messages,
+                max_tokens: 2000,
+                response_schema,
+            }
+        };
+        let mut request = translation_request(self.uses_structured_output());
+
+        let mut usage = TokenUsage::default();
+        let mut repair_attempts = 0;
+        let (mut subtitle_translation_info, parse_path) = loop {
+            // Repairs aren't streamed, the client keeps showing the first answer until the repaired one is done
+            let completion = match tokens {
+                Some(tokens) if repair_attempts == 0 => self.stream_with_retries(&request, tokens).await,
+                _ => self
+                    .with_retries(|| self.provider.complete(&request))
+                    .await
+                    .map_err(|e| (e, TokenUsage::default())),
+            };
+            let completion = match completion {
+                // The provider rejected the response schema, ask once more with the few-shot example instead
+                Err((TranslateError::ResponseSchemaRejected, _)) if request.response_schema.is_some() => {
+                    request = translation_request(false);
+                    continue;
                 }
-            ],
-            "translation": "I can't find the dictionary",
-            "grammar_points": [
-                {
-                    "name": "Object Marker",
-                    "relevant_text": "사전을",
-                    "description": "The suffix \"을\" is the object marker, indicating that \"dictionary\" is the object of the verb."
-                },
-                {
-                    "name": "Negative Verb Form",
-                    "relevant_text": "못 찾아",
-                    "description": "\"못\" is used to indicate inability or impossibility, similar to \"cannot\" in English. \"찾아\" is the verb stem of \"찾다,\" which means \"to find.\" Together, \"못 찾아\" means \"cannot find.\""
+                Ok(completion) => completion,
+                Err((e, partial_usage)) => {
+                    usage.add(&partial_usage);
+                    return Err((e, usage));
                 }
-            ]
-        }).to_string();
-
-        let mut messages = Vec::new();
-        messages.push((
-            "system",
-            "You're an agent who helps provide information to language learners who are inputting a specific subtitle of a show they are watching. All of your answers and output will only be in JSON with no prose.",
-        ));
-        messages.push(("user", "사전을 못 찾아"));
-        messages.push(("assistant", &json_example_string));
-        messages.push(("user", &text));
-
-        let response = match completions::chat()
-            .messages(messages)
-            .max_tokens(2000)
-            .send().await {
-                Ok(response) => response,
-                Err(e) => return Err(anyhow!("Error: {:?}", e)),
             };
+            usage.add(&completion.usage.unwrap_or_else(|| estimate_usage(&request, &completion.text)));
+            let response_text = completion.text;
 
-        let response_text = match response.choices.get(0) {
-            Some(choice) => choice.message.content.clone(),
-            None => return Err(anyhow!("No response text found")),
+            // Convert response_text to SubtitleTranslationInfo
+            let problem = match parsing::parse_translation(&response_text) {
+                Ok((info, parse_path)) if repair_attempts == 0 => break (info, parse_path),
+                Ok((info, _)) => break (info, ParsePath::Repaired),
+                Err(problem) => problem,
+            };
+
+            if repair_attempts == MAX_REPAIR_ATTEMPTS {
+                leptos::logging::error!("Couldn't parse the model's answer: {}", problem);
+                return Err((TranslateError::BadOutput(problem), usage));
+            }
+            repair_attempts += 1;
+            leptos::logging::log!("Asking the model to repair its answer: {}", problem);
+
+            request.messages.push(ChatMessage::new("assistant", &response_text));
+            request.messages.push(ChatMessage::new(
+                "user",
+                &format!(
+                    "Your previous answer could not be used. {}. Reply with only the corrected JSON object, with no prose or code fences.",
+                    problem
+                ),
+            ));
         };
 
-        // Convert response_text to SubtitleTranslationInfo
-        let subtitle_translation_info: SubtitleTranslationInfo = match serde_json::from_str(&response_text) {
-            Ok(info) => info,
-            Err(e) => return Err(anyhow!("Error deserializing: {:?}", e)),
+        if let Some(cache) = &self.cache {
+            cache.insert(&cache_key(&text, language_pair, context), &subtitle_translation_info);
+        }
+        // After caching, so that improvements to the analyzer also apply to cached translations
+        annotate_vocabulary(&mut subtitle_translation_info.vocabulary, language_pair);
+
+        Ok(TranslationResult {
+            info: subtitle_translation_info,
+            metadata: TranslationMetadata {
+                parse_path,
+                usage: Some(usage),
+            },
+        })
+    }
+
+    // Dictionary entries for a list of words, e.g. the study list of an episode. Returns an entry for each of the
+    // words that the model glossed, in the order they were asked for, and leaves out anything else it answered.
+    // Glosses aren't cached.
+    pub async fn gloss(
+        &self,
+        words: &[String],
+        language_pair: LanguagePair,
+        context: Option<&TranslationContext>,
+    ) -> Result<(Vec<VocabularyInfo>, TokenUsage), BilledError> {
+        let words: Vec<&str> = words.iter().map(|word| word.trim()).filter(|word| !word.is_empty()).collect();
+        if words.is_empty() {
+            return Err((TranslateError::EmptyInput, TokenUsage::default()));
+        }
+
+        let gloss_request = |structured_output: bool| {
+            let mut messages = vec![ChatMessage::new("system", &language_pair.gloss_prompt())];
+            let response_schema = if structured_output {
+                Some(ResponseSchema {
+                    name: "word_glosses".to_string(),
+                    schema: parsing::strict_glosses_json_schema(),
+                })
+            } else {
+                let (example_input, example_output) = language_pair.gloss_example();
+                messages.push(ChatMessage::new("user", &example_input));
+                messages.push(ChatMessage::new("assistant", &example_output.to_string()));
+                None
+            };
+            let title = context.and_then(|context| context.title.as_deref()).map(str::trim);
+            if let Some(title) = title.filter(|title| !title.is_empty()) {
+                messages.push(ChatMessage::new("system", &format!("The words are from {}.", title)));
+            }
+            messages.push(ChatMessage::new("user", &words.join("\n")));
+
+            ChatRequest {
+                messages,
+                max_tokens: 4000,
+                response_schema,
+            }
+        };
+        let mut request = gloss_request(self.uses_structured_output());
+        let completion = match self.with_retries(|| self.provider.complete(&request)).await {
+            Err(TranslateError::ResponseSchemaRejected) if request.response_schema.is_some() => {
+                request = gloss_request(false);
+                self.with_retries(|| self.provider.complete(&request)).await
+            }
+            completion => completion,
         };
+        let completion = completion.map_err(|e| (e, TokenUsage::default()))?;
+        let usage = completion.usage.unwrap_or_else(|| estimate_usage(&request, &completion.text));
+
+        let mut glosses = match parsing::parse_glosses(&completion.text) {
+            Ok(glosses) => glosses.vocabulary,
+            Err(problem) => {
+                leptos::logging::error!("Couldn't parse the model's glosses: {}", problem);
+                return Err((TranslateError::BadOutput(problem), usage));
+            }
+        };
+        let mut requested: Vec<VocabularyInfo> = words
+            .iter()
+            .filter_map(|word| {
+                let idx = glosses.iter().position(|gloss| {
+                    gloss.word.trim() == *word || gloss.lemma.as_deref().map(str::trim) == Some(*word)
+                })?;
+                Some(glosses.swap_remove(idx))
+            })
+            .collect();
+        annotate_vocabulary(&mut requested, language_pair);
+
+        Ok((requested, usage))
+    }
+
+    // Calls the provider with a timeout, retrying transient failures with exponential backoff
+    async fn with_retries<T, F, Fut>(&self, call: F) -> Result<T, TranslateError>
+    where
+        F: Fn() -> Fut,
+        Fut: std::future::Future<Output = Result<T, ProviderError>>,
+    {
+        if let Err(remaining) = self.circuit_breaker.check() {
+            return Err(TranslateError::Unavailable {
+                retry_after_secs: Some(remaining.as_secs_f64().ceil() as u64),
+            });
+        }
+
+        let mut retry = 0;
+        loop {
+            let error = match tokio::time::timeout(self.retry_policy.request_timeout, call()).await {
+                Ok(Ok(value)) => {
+                    self.circuit_breaker.record_success();
+                    return Ok(value);
+                }
+                Ok(Err(e)) => e,
+                Err(_) => ProviderError::Timeout,
+            };
+
+            let backoff = self.retry_policy.backoff(retry, error.retry_after());
+            let out_of_retries = retry >= self.retry_policy.max_retries || backoff > self.retry_policy.max_backoff;
+            if !error.is_transient() || out_of_retries {
+                return Err(self.give_up(&error));
+            }
+
+            retry += 1;
+            leptos::logging::warn!(
+                "{}, retrying in {:?} ({}/{})",
+                error,
+                backoff,
+                retry,
+                self.retry_policy.max_retries
+            );
+            tokio::time::sleep(backoff).await;
+        }
+    }
+
+    // Opening the stream is retried like a normal call. Once tokens have been sent a failure ends the translation,
+    // as does waiting longer than the request timeout for the next token. The tokens sent until then are billed.
+    async fn stream_with_retries(
+        &self,
+        request: &ChatRequest,
+        tokens: &UnboundedSender<Result<TranslationStreamEvent, BilledError>>,
+    ) -> Result<Completion, BilledError> {
+        let mut completion_stream = self
+            .with_retries(|| self.provider.complete_stream(request))
+            .await
+            .map_err(|e| (e, TokenUsage::default()))?;
+
+        let mut completion = Completion {
+            text: String::new(),
+            usage: None,
+        };
+        loop {
+            match tokio::time::timeout(self.retry_policy.request_timeout, completion_stream.next()).await {
+                Ok(Some(Ok(CompletionChunk::Text(token)))) => {
+                    completion.text.push_str(&token);
+                    let _ = tokens.unbounded_send(Ok(TranslationStreamEvent::Token(token)));
+                }
+                Ok(Some(Ok(CompletionChunk::Usage(usage)))) => completion.usage = Some(usage),
+                Ok(Some(Err(e))) => return Err((self.give_up(&e), partial_usage(request, &completion))),
+                Ok(None) => return Ok(completion),
+                Err(_) => return Err((self.give_up(&ProviderError::Timeout), partial_usage(request, &completion))),
+            }
+        }
+    }
+
+    fn give_up(&self, error: &ProviderError) -> TranslateError {
+        if error.is_transient() {
+            self.circuit_breaker.record_failure();
+        } else {
+            // The provider is up, it only refused this request
+            self.circuit_breaker.record_success();
+        }
+        if rejects_response_format(error) && !self.structured_output_rejected.swap(true, Ordering::Relaxed) {
+            leptos::logging::warn!("The provider doesn't accept response schemas, using few-shot examples instead");
+        }
+        leptos::logging::error!("Translation request failed: {}", error);
+        translate_error(error)
+    }
+}
+
+// The model's dictionary forms and romanizations aren't always right, Korean words get them from the analyzer
+// and the romanization module instead. The analyzer's part of speech is only a fallback, it guesses it for X하다 words.
+#[cfg(feature = "ssr")]
+fn annotate_vocabulary(vocabulary: &mut [VocabularyInfo], language_pair: LanguagePair) {
+    if language_pair.source != Language::Korean {
+        return;
+    }
+    for vocab in vocabulary {
+        vocab.romanization = Some(crate::romanization::romanize(vocab.word.trim()));
+        if let Some(analysis) = crate::vocabulary::analyze_word(&vocab.word) {
+            vocab.lemma = Some(analysis.lemma);
+            vocab.part_of_speech.get_or_insert_with(|| analysis.part_of_speech.to_string());
+            vocab.conjugation = analysis.conjugation;
+        }
+    }
+}
+
+// Translations with and without context are cached separately
+#[cfg(feature = "ssr")]
+fn cache_key(text: &str, language_pair: LanguagePair, context: Option<&TranslationContext>) -> CacheKey {
+    let cache_key = CacheKey::new(text, &language_pair.id(), PROMPT_VERSION);
+    match context.filter(|context| !context.is_empty()) {
+        Some(context) => cache_key.with_variant(&context.prompt()),
+        None => cache_key,
+    }
+}
+
+// For providers that don't report usage, e.g. Azure when streaming
+#[cfg(feature = "ssr")]
+fn estimate_usage(request: &ChatRequest, response_text: &str) -> TokenUsage {
+    let prompt_characters = request.messages.iter().map(|message| message.content.chars().count()).sum();
+    TokenUsage::estimate(prompt_characters, response_text.chars().count())
+}
+
+// What a stream cost before it broke off, nothing if the model hadn't answered yet
+#[cfg(feature = "ssr")]
+fn partial_usage(request: &ChatRequest, completion: &Completion) -> TokenUsage {
+    match completion.usage {
+        Some(usage) => usage,
+        None if completion.text.is_empty() => TokenUsage::default(),
+        None => estimate_usage(request, &completion.text),
+    }
+}
+
+// A 400 about the response_format parameter, sent by models and API versions without structured outputs
+#[cfg(feature = "ssr")]
+fn rejects_response_format(error: &ProviderError) -> bool {
+    match error {
+        ProviderError::Status { status: 400, message, .. } => {
+            let message = message.to_lowercase();
+            message.contains("response_format") || message.contains("json_schema")
+        }
+        _ => false,
+    }
+}
+
+#[cfg(feature = "ssr")]
+fn translate_error(error: &ProviderError) -> TranslateError {
+    let retry_after_secs = error.retry_after().map(|retry_after| retry_after.as_secs_f64().ceil() as u64);
+
+    match error {
+        ProviderError::Status { status: 401 | 403, .. } => TranslateError::AuthFailure,
+        ProviderError::Status { status: 429, .. } => TranslateError::RateLimited { retry_after_secs },
+        ProviderError::Status { status: 408, .. } | ProviderError::Timeout => TranslateError::Timeout,
+        ProviderError::Status { status, .. } if *status >= 500 => TranslateError::Unavailable { retry_after_secs },
+        ProviderError::Transport(_) => TranslateError::Unavailable { retry_after_secs: None },
+        ProviderError::InvalidResponse(message) => TranslateError::BadOutput(message.clone()),
+        error if rejects_response_format(error) => TranslateError::ResponseSchemaRejected,
+        ProviderError::Status { .. } => TranslateError::Other(error.to_string()),
+    }
+}
+
+#[cfg(all(test, feature = "ssr"))]
+mod tests {
+    use super::*;
+    use providers::{CompletionStream, FixtureProvider};
+    use std::sync::atomic::AtomicUsize;
+
+    // Rejects response schemas like a model without structured outputs, otherwise answers with the few-shot example
+    #[derive(Debug, Default)]
+    struct SchemaRejectingProvider {
+        calls: AtomicUsize,
+        schema_calls: AtomicUsize,
+    }
+
+    #[async_trait::async_trait]
+    impl TranslationProvider for SchemaRejectingProvider {
+        fn name(&self) -> &'static str {
+            "schema-rejecting"
+        }
+
+        fn supports_structured_output(&self) -> bool {
+            true
+        }
+
+        async fn complete(&self, request: &ChatRequest) -> Result<Completion, ProviderError> {
+            self.calls.fetch_add(1, Ordering::Relaxed);
+            if request.response_schema.is_some() {
+                self.schema_calls.fetch_add(1, Ordering::Relaxed);
+                return Err(ProviderError::Status {
+                    status: 400,
+                    retry_after: None,
+                    message: "'response_format' of type 'json_schema' is not supported with this model".to_string(),
+                });
+            }
+            Ok(Completion {
+                text: LanguagePair::default().few_shot_example().1.to_string(),
+                usage: None,
+            })
+        }
+    }
+
+    // Answers with the scripted errors in order, then with the few-shot example. Keeps the requests it was sent.
+    #[derive(Debug, Default)]
+    struct ScriptedProvider {
+        errors: std::sync::Mutex<std::collections::VecDeque<ProviderError>>,
+        calls: AtomicUsize,
+        requests: std::sync::Mutex<Vec<ChatRequest>>,
+    }
+
+    impl ScriptedProvider {
+        fn failing_with(errors: impl IntoIterator<Item = ProviderError>) -> Arc<Self> {
+            Arc::new(Self {
+                errors: std::sync::Mutex::new(errors.into_iter().collect()),
+                ..Default::default()
+            })
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl TranslationProvider for ScriptedProvider {
+        fn name(&self) -> &'static str {
+            "scripted"
+        }
+
+        async fn complete(&self, request: &ChatRequest) -> Result<Completion, ProviderError> {
+            self.calls.fetch_add(1, Ordering::Relaxed);
+            self.requests.lock().unwrap().push(request.clone());
+            if let Some(error) = self.errors.lock().unwrap().pop_front() {
+                return Err(error);
+            }
+            Ok(Completion {
+                text: LanguagePair::default().few_shot_example().1.to_string(),
+                usage: None,
+            })
+        }
+    }
+
+    // Fails requests with a response schema after another request found out that the provider rejects schemas,
+    // like a request that was already on its way when the flag flipped
+    #[derive(Debug)]
+    struct LateFailingProvider {
+        error: fn() -> ProviderError,
+        structured_output_rejected: Arc<AtomicBool>,
+        calls: AtomicUsize,
+    }
+
+    #[async_trait::async_trait]
+    impl TranslationProvider for LateFailingProvider {
+        fn name(&self) -> &'static str {
+            "late-failing"
+        }
+
+        fn supports_structured_output(&self) -> bool {
+            true
+        }
+
+        async fn complete(&self, _request: &ChatRequest) -> Result<Completion, ProviderError> {
+            self.calls.fetch_add(1, Ordering::Relaxed);
+            self.structured_output_rejected.store(true, Ordering::Relaxed);
+            Err((self.error)())
+        }
+    }
+
+    // Answers with prose instead of a translation, reporting usage. Its streams break off after the first token.
+    #[derive(Debug)]
+    struct UnusableProvider;
+
+    #[async_trait::async_trait]
+    impl TranslationProvider for UnusableProvider {
+        fn name(&self) -> &'static str {
+            "unusable"
+        }
+
+        async fn complete(&self, _request: &ChatRequest) -> Result<Completion, ProviderError> {
+            Ok(Completion {
+                text: "Sorry, I can't help with that.".to_string(),
+                usage: Some(TokenUsage {
+                    prompt_tokens: 100,
+                    completion_tokens: 10,
+                    estimated: false,
+                }),
+            })
+        }
+
+        async fn complete_stream(&self, _request: &ChatRequest) -> Result<CompletionStream, ProviderError> {
+            let chunks = vec![
+                Ok(CompletionChunk::Text("{\"translation\": \"Hel".to_string())),
+                Err(ProviderError::Timeout),
+            ];
+            Ok(futures::stream::iter(chunks).boxed())
+        }
+    }
+
+    fn status(status: u16, retry_after_secs: Option<u64>) -> ProviderError {
+        ProviderError::Status {
+            status,
+            retry_after: retry_after_secs.map(Duration::from_secs),
+            message: "scripted".to_string(),
+        }
+    }
+
+    fn retry_policy() -> RetryPolicy {
+        RetryPolicy {
+            request_timeout: Duration::from_secs(30),
+            max_retries: 3,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(10),
+        }
+    }
+
+    async fn translate(ai_interface: &AIInterface) -> Result<TranslationResult, TranslateError> {
+        ai_interface.translate("안녕".to_string(), LanguagePair::default(), None).await.map_err(|(e, _)| e)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn transient_failures_are_retried_with_exponential_backoff() {
+        let provider = ScriptedProvider::failing_with([status(500, None), status(503, None), ProviderError::Timeout]);
+        let ai_interface = AIInterface::new(provider.clone()).with_retry_policy(retry_policy());
+
+        let started = tokio::time::Instant::now();
+        assert!(translate(&ai_interface).await.is_ok());
+        assert_eq!(provider.calls.load(Ordering::Relaxed), 4);
+        assert_eq!(started.elapsed(), Duration::from_secs(1 + 2 + 4));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retry_after_is_waited_out_up_to_the_maximum_backoff() {
+        let provider = ScriptedProvider::failing_with([status(429, Some(5))]);
+        let ai_interface = AIInterface::new(provider.clone()).with_retry_policy(retry_policy());
+        let started = tokio::time::Instant::now();
+        assert!(translate(&ai_interface).await.is_ok());
+        assert_eq!(started.elapsed(), Duration::from_secs(5));
+
+        // Longer than max_backoff, the user is told when to try again instead
+        let provider = ScriptedProvider::failing_with([status(429, Some(60))]);
+        let ai_interface = AIInterface::new(provider.clone()).with_retry_policy(retry_policy());
+        let started = tokio::time::Instant::now();
+        let error = translate(&ai_interface).await.unwrap_err();
+        assert_eq!(error, TranslateError::RateLimited { retry_after_secs: Some(60) });
+        assert_eq!(provider.calls.load(Ordering::Relaxed), 1);
+        assert_eq!(started.elapsed(), Duration::ZERO);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn other_errors_are_not_retried() {
+        let provider = ScriptedProvider::failing_with([status(401, None)]);
+        let ai_interface = AIInterface::new(provider.clone()).with_retry_policy(retry_policy());
+        assert_eq!(translate(&ai_interface).await.unwrap_err(), TranslateError::AuthFailure);
+        assert_eq!(provider.calls.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn open_circuit_fails_fast_until_a_trial_succeeds() {
+        let cooldown = Duration::from_millis(50);
+        let provider = ScriptedProvider::failing_with([status(500, None), status(500, None)]);
+        let ai_interface = AIInterface::new(provider.clone())
+            .with_retry_policy(RetryPolicy {
+                max_retries: 0,
+                ..retry_policy()
+            })
+            .with_circuit_breaker(CircuitBreaker::new(1, cooldown));
+
+        let unavailable = |retry_after_secs| TranslateError::Unavailable { retry_after_secs };
+        assert_eq!(translate(&ai_interface).await.unwrap_err(), unavailable(None));
+        assert_eq!(translate(&ai_interface).await.unwrap_err(), unavailable(Some(1)));
+        assert_eq!(provider.calls.load(Ordering::Relaxed), 1);
+
+        // The trial fails and the circuit opens again
+        tokio::time::sleep(cooldown).await;
+        assert!(translate(&ai_interface).await.is_err());
+        assert!(translate(&ai_interface).await.is_err());
+        assert_eq!(provider.calls.load(Ordering::Relaxed), 2);
+
+        tokio::time::sleep(cooldown).await;
+        assert!(translate(&ai_interface).await.is_ok());
+        assert!(translate(&ai_interface).await.is_ok());
+        assert_eq!(provider.calls.load(Ordering::Relaxed), 4);
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_the_few_shot_example_when_the_schema_is_rejected() {
+        let provider = Arc::new(SchemaRejectingProvider::default());
+        let ai_interface = AIInterface::new(provider.clone());
+
+        let result = ai_interface.translate("안녕".to_string(), LanguagePair::default(), None).await.unwrap();
+        assert!(!result.info.translation.is_empty());
+        assert_eq!(provider.calls.load(Ordering::Relaxed), 2);
+
+        // The schema isn't sent again
+        ai_interface.translate("고마워".to_string(), LanguagePair::default(), None).await.unwrap();
+        assert_eq!(provider.calls.load(Ordering::Relaxed), 3);
+        assert_eq!(provider.schema_calls.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn only_a_rejected_schema_falls_back_to_the_few_shot_example() {
+        let errors: [(fn() -> ProviderError, TranslateError); 2] = [
+            (|| status(401, None), TranslateError::AuthFailure),
+            (|| ProviderError::Timeout, TranslateError::Timeout),
+        ];
+        for (error, expected) in errors {
+            let mut ai_interface = AIInterface::new(Arc::new(FixtureProvider::new()));
+            let provider = Arc::new(LateFailingProvider {
+                error,
+                structured_output_rejected: ai_interface.structured_output_rejected.clone(),
+                calls: AtomicUsize::new(0),
+            });
+            ai_interface.provider = provider.clone();
+            let ai_interface = ai_interface.with_retry_policy(RetryPolicy {
+                max_retries: 0,
+                ..retry_policy()
+            });
+
+            assert_eq!(translate(&ai_interface).await.unwrap_err(), expected);
+            assert_eq!(provider.calls.load(Ordering::Relaxed), 1);
+        }
+    }
+
+    async fn collect_stream(ai_interface: &AIInterface, text: &str) -> (String, Vec<TranslationStreamEvent>) {
+        let events: Vec<_> =
+            ai_interface.translate_stream(text.to_string(), LanguagePair::default(), None).collect().await;
+        let mut tokens = String::new();
+        let mut others = Vec::new();
+        for event in events {
+            match event {
+                Ok(TranslationStreamEvent::Token(token)) => tokens.push_str(&token),
+                Ok(event) => others.push(event),
+                Err((e, _)) => others.push(TranslationStreamEvent::Failed(e)),
+            }
+        }
+        (tokens, others)
+    }
+
+    #[tokio::test]
+    async fn streams_the_answer_then_the_parsed_translation() {
+        let ai_interface = AIInterface::new(Arc::new(FixtureProvider::new()));
+        let (example_input, example_output) = LanguagePair::default().few_shot_example();
+
+        let (tokens, events) = collect_stream(&ai_interface, example_input).await;
+        assert_eq!(tokens, example_output.to_string());
+        match &events[..] {
+            [TranslationStreamEvent::Done(result)] => {
+                assert_eq!(result.info.translation, example_output["translation"]);
+                assert!(result.metadata.usage.is_some());
+            }
+            events => panic!("expected a single Done event, got {:?}", events),
+        }
+    }
+
+    #[tokio::test]
+    async fn cached_translations_are_streamed_without_tokens() {
+        let cache = Arc::new(TranslationCache::in_memory(10));
+        let ai_interface = AIInterface::new(Arc::new(FixtureProvider::new())).with_cache(cache);
+
+        let (tokens, _) = collect_stream(&ai_interface, "안녕").await;
+        assert!(!tokens.is_empty());
+
+        let (tokens, events) = collect_stream(&ai_interface, "안녕").await;
+        assert!(tokens.is_empty());
+        let cached = |result: &TranslationResult| result.metadata.parse_path == ParsePath::Cached;
+        assert!(matches!(&events[..], [TranslationStreamEvent::Done(result)] if cached(result)));
+    }
+
+    #[tokio::test]
+    async fn failed_streams_end_with_the_error() {
+        let ai_interface = AIInterface::new(ScriptedProvider::failing_with([status(401, None)]));
+
+        let (tokens, events) = collect_stream(&ai_interface, "안녕").await;
+        assert!(tokens.is_empty());
+        assert!(matches!(&events[..], [TranslationStreamEvent::Failed(TranslateError::AuthFailure)]));
+
+        let (_, events) = collect_stream(&ai_interface, " ").await;
+        assert!(matches!(&events[..], [TranslationStreamEvent::Failed(TranslateError::EmptyInput)]));
+    }
+
+    #[tokio::test]
+    async fn answers_that_cant_be_repaired_are_billed() {
+        let ai_interface = AIInterface::new(Arc::new(UnusableProvider));
+
+        match ai_interface.translate("안녕".to_string(), LanguagePair::default(), None).await {
+            Err((TranslateError::BadOutput(_), usage)) => {
+                let attempts = MAX_REPAIR_ATTEMPTS as u64 + 1;
+                assert_eq!(usage.prompt_tokens, 100 * attempts);
+                assert_eq!(usage.completion_tokens, 10 * attempts);
+            }
+            result => panic!("expected BadOutput, got {:?}", result.map(|result| result.info)),
+        }
+    }
+
+    #[tokio::test]
+    async fn streams_that_break_off_are_billed_for_what_was_sent() {
+        let ai_interface = AIInterface::new(Arc::new(UnusableProvider));
+
+        let events: Vec<_> =
+            ai_interface.translate_stream("안녕".to_string(), LanguagePair::default(), None).collect().await;
+        match &events[..] {
+            [Ok(TranslationStreamEvent::Token(_)), Err((_, usage))] => {
+                assert!(usage.estimated);
+                assert!(usage.prompt_tokens > 0 && usage.completion_tokens > 0);
+            }
+            events => panic!("expected a token then the error, got {:?}", events),
+        }
+
+        // Nothing was sent, so there is nothing to bill
+        let ai_interface = AIInterface::new(ScriptedProvider::failing_with([status(401, None)]));
+        let events: Vec<_> =
+            ai_interface.translate_stream("안녕".to_string(), LanguagePair::default(), None).collect().await;
+        assert!(matches!(&events[..], [Err((TranslateError::AuthFailure, usage))] if usage.total_tokens() == 0));
+    }
+
+    #[test]
+    fn context_without_lines_title_or_reference_is_empty() {
+        assert!(TranslationContext::default().is_empty());
+
+        let blank = TranslationContext {
+            title: Some("  ".to_string()),
+            reference_translation: Some(" ".to_string()),
+            ..Default::default()
+        };
+        assert!(blank.is_empty());
+        assert_eq!(blank.prompt().lines().count(), 1);
+
+        let with_reference = TranslationContext {
+            reference_translation: Some("Hi.".to_string()),
+            ..Default::default()
+        };
+        assert!(!with_reference.is_empty());
+    }
+
+    #[test]
+    fn context_prompt_lists_the_title_lines_and_reference() {
+        let context = TranslationContext {
+            previous_lines: vec!["누구야?".to_string(), "나야.".to_string()],
+            next_lines: vec!["왜 왔어?".to_string()],
+            title: Some(" 도깨비 ".to_string()),
+            reference_translation: Some(" It's me. ".to_string()),
+        };
+
+        let prompt = context.prompt();
+        let lines: Vec<&str> = prompt.lines().skip(1).collect();
+        assert_eq!(
+            lines[..7],
+            [
+                "Show: 도깨비",
+                "Previous subtitles:",
+                "- 누구야?",
+                "- 나야.",
+                "Following subtitles:",
+                "- 왜 왔어?",
+                "Official translation of the next subtitle: It's me.",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn context_goes_in_its_own_system_message_before_the_subtitle() {
+        let provider = ScriptedProvider::failing_with([]);
+        let ai_interface = AIInterface::new(provider.clone());
+        let context = TranslationContext {
+            previous_lines: vec!["누구야?".to_string()],
+            ..Default::default()
+        };
+
+        ai_interface.translate("나야.".to_string(), LanguagePair::default(), Some(&context)).await.unwrap();
+        // An empty context is left out
+        ai_interface.translate("안녕".to_string(), LanguagePair::default(), Some(&Default::default())).await.unwrap();
+
+        // After the system prompt and the few-shot example
+        let requests = provider.requests.lock().unwrap();
+        let last_two = |request: &ChatRequest| -> Vec<(String, String)> {
+            let messages = &request.messages[request.messages.len() - 2..];
+            messages.iter().map(|message| (message.role.clone(), message.content.clone())).collect()
+        };
+        assert_eq!(requests[0].messages.len(), 5);
+        assert_eq!(last_two(&requests[0])[0].0, "system");
+        assert!(last_two(&requests[0])[0].1.ends_with("Previous subtitles:\n- 누구야?"));
+        assert_eq!(last_two(&requests[0])[1], ("user".to_string(), "나야.".to_string()));
+        assert_eq!(requests[1].messages.len(), 4);
+        assert_eq!(last_two(&requests[1])[0].0, "assistant");
+        assert_eq!(last_two(&requests[1])[1], ("user".to_string(), "안녕".to_string()));
+    }
+
+    #[test]
+    fn cache_key_includes_the_prompt_version_and_language_pair() {
+        let language_pair = LanguagePair::default();
+        let key = cache_key("안녕", language_pair, None);
+        assert_eq!(key.prompt_version, PROMPT_VERSION);
+        assert_eq!(key.target_language, language_pair.id());
+        assert!(key.variant.is_empty());
+
+        let context = TranslationContext {
+            previous_lines: vec!["누구야?".to_string()],
+            ..Default::default()
+        };
+        assert!(!cache_key("안녕", language_pair, Some(&context)).variant.is_empty());
+    }
+
+    #[tokio::test]
+    async fn glosses_only_the_requested_words() {
+        let fixture_file = std::env::temp_dir().join(format!("gloss-fixture-{}.json", std::process::id()));
+        let answer = serde_json::json!({
+            "사전\n친구": {
+                "vocabulary": [
+                    { "word": "책", "translation": "book" },
+                    { "word": "친구", "translation": "friend" },
+                    { "word": "사전", "translation": "dictionary" }
+                ]
+            }
+        });
+        std::fs::write(&fixture_file, answer.to_string()).unwrap();
+        let provider = FixtureProvider::from_file(fixture_file.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&fixture_file).ok();
+
+        let ai_interface = AIInterface::new(Arc::new(provider));
+        let words = vec!["사전".to_string(), "친구".to_string()];
+        let (glosses, usage) = ai_interface.gloss(&words, LanguagePair::default(), None).await.unwrap();
 
-        Ok(subtitle_translation_info)
+        let glossed: Vec<(&str, &str)> =
+            glosses.iter().map(|gloss| (gloss.word.as_str(), gloss.translation.as_str())).collect();
+        assert_eq!(glossed, vec![("사전", "dictionary"), ("친구", "friend")]);
+        assert_eq!(glosses[0].romanization.as_deref(), Some("sajeon"));
+        assert!(usage.total_tokens() > 0);
     }
 }
@@ -0,0 +1,110 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+// Why a translation failed, sent to the client so that it can tell the user what to do about it
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub enum TranslateError {
+    EmptyInput,
+    InputTooLong { max_characters: usize },
+    // The surrounding lines, title and reference translation sent as context are over the limits
+    ContextTooLong { max_lines: usize, max_characters: usize },
+    UnsupportedLanguagePair(String),
+    // Translation is disabled because the server configuration is invalid
+    Disabled,
+    // The provider is rate limiting us, retry_after_secs is how long it asked us to wait
+    RateLimited { retry_after_secs: Option<u64> },
+    // The provider rejected the API key or deployment
+    AuthFailure,
+    // The model doesn't support structured outputs. The request is sent again with the few-shot example instead.
+    ResponseSchemaRejected,
+    Timeout,
+    // The model's answer couldn't be turned into a translation, even after asking it to repair it
+    BadOutput(String),
+    // The provider keeps failing, retry_after_secs is set while the circuit breaker is open
+    Unavailable { retry_after_secs: Option<u64> },
+    // Today's spending cap is used up, either the server's or this session's
+    SpendCapReached { session: bool },
+    // This client sent more translation requests than the server allows
+    TooManyRequests { retry_after_secs: u64 },
+    // The server only translates for allowlisted addresses
+    NotAllowed,
+    Other(String),
+}
+
+impl TranslateError {
+    pub fn message(&self) -> String {
+        match self {
+            Self::EmptyInput => "Enter some text to translate.".to_string(),
+            Self::InputTooLong { max_characters } => {
+                format!("That's too long to translate, the limit is {} characters.", max_characters)
+            }
+            Self::ContextTooLong { max_lines, max_characters } => format!(
+                "The context sent with the subtitle is too long, the limit is {} lines and {} characters.",
+                max_lines, max_characters
+            ),
+            Self::UnsupportedLanguagePair(label) => format!("{} is not a supported language pair.", label),
+            Self::Disabled => "Translation is disabled, check the server configuration.".to_string(),
+            Self::RateLimited { retry_after_secs } => match retry_after_secs {
+                Some(secs) => format!("The translation service is busy. Try again in {} seconds.", secs),
+                None => "The translation service is busy. Try again in a moment.".to_string(),
+            },
+            Self::AuthFailure => {
+                "The translation service rejected the server's credentials, check OPENAI_API_KEY and OPENAI_API_DEPLOYMENT."
+                    .to_string()
+            }
+            Self::ResponseSchemaRejected => {
+                "The translation service doesn't accept the request's response format.".to_string()
+            }
+            Self::Timeout => "The translation took too long. Try again, or try a shorter subtitle.".to_string(),
+            Self::BadOutput(_) => "The model gave an answer that couldn't be read. Try translating again.".to_string(),
+            Self::Unavailable { retry_after_secs } => match retry_after_secs {
+                Some(secs) => format!("The translation service is unavailable. Try again in {} seconds.", secs),
+                None => "The translation service is unavailable. Try again later.".to_string(),
+            },
+            Self::SpendCapReached { session: true } => {
+                "You've reached today's translation budget. Already translated subtitles still work, new ones will be available again tomorrow (UTC).".to_string()
+            }
+            Self::SpendCapReached { session: false } => {
+                "The site has reached today's translation budget. Already translated subtitles still work, new ones will be available again tomorrow (UTC).".to_string()
+            }
+            Self::TooManyRequests { retry_after_secs } => {
+                format!("You're translating too quickly. Try again in {} seconds.", retry_after_secs)
+            }
+            Self::NotAllowed => "Translation is only available to approved users of this server.".to_string(),
+            Self::Other(message) => format!("Translation failed: {}", message),
+        }
+    }
+
+    // Whether sending the same request again later can succeed
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            Self::RateLimited { .. }
+                | Self::Timeout
+                | Self::BadOutput(_)
+                | Self::Unavailable { .. }
+                | Self::TooManyRequests { .. }
+        )
+    }
+}
+
+// Server functions send custom errors as their Display text and read them back with FromStr, so both use JSON
+impl fmt::Display for TranslateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match serde_json::to_string(self) {
+            Ok(json) => write!(f, "{}", json),
+            Err(_) => write!(f, "{:?}", self),
+        }
+    }
+}
+
+impl FromStr for TranslateError {
+    type Err = serde_json::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_json::from_str(s)
+    }
+}
+
+impl std::error::Error for TranslateError {}
@@ -0,0 +1,420 @@
+use serde::{Deserialize, Serialize};
+
+use super::SubtitleTranslationInfo;
+#[cfg(feature = "ssr")]
+use super::WordGlosses;
+
+// How a translation was obtained from the model's answer
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub enum ParsePath {
+    // Served from the translation cache without calling the model
+    Cached,
+    // The answer was valid JSON as is
+    #[default]
+    Direct,
+    // The JSON had to be dug out of code fences or surrounding prose
+    Extracted,
+    // The model had to be asked to fix its answer
+    Repaired,
+}
+
+impl ParsePath {
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::Cached => "From cache",
+            Self::Direct => "Parsed directly",
+            Self::Extracted => "Extracted from surrounding text",
+            Self::Repaired => "Repaired by the model",
+        }
+    }
+}
+
+// Returns the body of the first ``` code fence, or the trimmed text when there is none
+#[cfg(feature = "ssr")]
+pub fn strip_code_fences(text: &str) -> &str {
+    let trimmed = text.trim();
+
+    let Some(fence_start) = trimmed.find("```") else {
+        return trimmed;
+    };
+
+    let after_fence = &trimmed[fence_start + 3..];
+    // Skip the info string, e.g. ```json
+    let body_start = after_fence.find('\n').map(|index| index + 1).unwrap_or(0);
+    let body = &after_fence[body_start..];
+
+    match body.find("```") {
+        Some(fence_end) => body[..fence_end].trim(),
+        None => body.trim(),
+    }
+}
+
+// Finds the first balanced {...} in the text that parses as a JSON object
+#[cfg(feature = "ssr")]
+pub fn find_json_object(text: &str) -> Option<&str> {
+    text.char_indices()
+        .filter(|(_, c)| *c == '{')
+        .filter_map(|(start, _)| balanced_object_end(&text[start..]).map(|end| &text[start..start + end]))
+        .find(|candidate| {
+            serde_json::from_str::<serde_json::Value>(candidate)
+                .map(|value| value.is_object())
+                .unwrap_or(false)
+        })
+}
+
+// Byte length of the object starting at the beginning of text, ignoring braces inside strings
+#[cfg(feature = "ssr")]
+fn balanced_object_end(text: &str) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for (index, c) in text.char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+
+        match c {
+            '"' => in_string = true,
+            '{' => depth += 1,
+            '}' => {
+                depth = depth.checked_sub(1)?;
+                if depth == 0 {
+                    return Some(index + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+// Best effort SubtitleTranslationInfo from the start of a streamed answer. The translation may be cut off mid sentence,
+// vocabulary and grammar items are only included once all of their required fields have arrived.
+pub fn parse_partial_translation(partial_text: &str) -> Option<SubtitleTranslationInfo> {
+    let object_start = partial_text.find('{')?;
+    let partial_json = &partial_text[object_start..];
+
+    let parse_closed = |close_open_string: bool| {
+        close_partial_json(partial_json, close_open_string)
+            .and_then(|closed| serde_json::from_str::<serde_json::Value>(&closed).ok())
+    };
+    let complete_values = parse_closed(false).unwrap_or_default();
+
+    let translation = parse_closed(true)
+        .unwrap_or_else(|| complete_values.clone())
+        .get("translation")
+        .and_then(|translation| translation.as_str())
+        .unwrap_or_default()
+        .to_string();
+    let complete_items = |field: &str| -> Vec<serde_json::Value> {
+        match complete_values.get(field) {
+            Some(serde_json::Value::Array(items)) => items.clone(),
+            _ => Vec::new(),
+        }
+    };
+
+    Some(SubtitleTranslationInfo {
+        vocabulary: complete_items("vocabulary")
+            .into_iter()
+            .filter_map(|item| serde_json::from_value(item).ok())
+            .collect(),
+        translation,
+        grammar_points: complete_items("grammar_points")
+            .into_iter()
+            .filter_map(|item| serde_json::from_value(item).ok())
+            .collect(),
+    })
+}
+
+// Turns the start of a JSON document into valid JSON by cutting it after the last complete value and closing
+// every open object and array. With close_open_string a string value that is still streaming is kept and closed too.
+fn close_partial_json(partial_json: &str, close_open_string: bool) -> Option<String> {
+    let mut open_containers = Vec::new();
+    let mut in_string = false;
+    let mut string_is_value = false;
+    let mut escaped = false;
+    let mut after_colon = false;
+    // Byte offset just after the last complete value, and the containers open at that point
+    let mut last_complete: Option<(usize, Vec<char>)> = None;
+
+    for (index, c) in partial_json.char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => {
+                    in_string = false;
+                    after_colon = false;
+                    if string_is_value {
+                        last_complete = Some((index + 1, open_containers.clone()));
+                    }
+                }
+                _ => {}
+            }
+            continue;
+        }
+
+        match c {
+            '"' => {
+                in_string = true;
+                string_is_value = open_containers.last() == Some(&'[') || after_colon;
+            }
+            '{' | '[' => {
+                open_containers.push(c);
+                after_colon = false;
+                last_complete = Some((index + 1, open_containers.clone()));
+            }
+            '}' | ']' => {
+                open_containers.pop();
+                last_complete = Some((index + 1, open_containers.clone()));
+            }
+            ':' => after_colon = true,
+            ',' => after_colon = false,
+            _ => {}
+        }
+    }
+
+    let closers = |containers: &[char]| -> String {
+        containers
+            .iter()
+            .rev()
+            .map(|container| if *container == '{' { '}' } else { ']' })
+            .collect()
+    };
+
+    if close_open_string && in_string && string_is_value {
+        // Drop a dangling escape, an unfinished \u escape makes the result invalid and the caller falls back
+        let text = if escaped { &partial_json[..partial_json.len() - 1] } else { partial_json };
+        return Some(format!("{}\"{}", text, closers(&open_containers)));
+    }
+
+    let (end, containers) = last_complete?;
+    Some(format!("{}{}", &partial_json[..end], closers(&containers)))
+}
+
+// Keywords schemars emits that structured outputs reject
+#[cfg(feature = "ssr")]
+const UNSUPPORTED_STRICT_KEYWORDS: [&str; 6] = ["default", "minLength", "maxLength", "minItems", "maxItems", "format"];
+
+// JSON schema of SubtitleTranslationInfo derived from the types, fields with defaults may be left out
+#[cfg(feature = "ssr")]
+pub fn translation_json_schema() -> serde_json::Value {
+    json_schema_for::<SubtitleTranslationInfo>()
+}
+
+// Variant of the schema for structured outputs, which need every property required and no others allowed
+#[cfg(feature = "ssr")]
+pub fn strict_translation_json_schema() -> serde_json::Value {
+    let mut schema = translation_json_schema();
+    make_strict(&mut schema);
+    schema
+}
+
+// Structured output schema of the answer to a gloss request
+#[cfg(feature = "ssr")]
+pub fn strict_glosses_json_schema() -> serde_json::Value {
+    let mut schema = json_schema_for::<WordGlosses>();
+    make_strict(&mut schema);
+    schema
+}
+
+#[cfg(feature = "ssr")]
+fn json_schema_for<T: schemars::JsonSchema>() -> serde_json::Value {
+    let settings = schemars::gen::SchemaSettings::draft07().with(|settings| {
+        settings.inline_subschemas = true;
+        settings.meta_schema = None;
+    });
+    let root_schema = settings.into_generator().into_root_schema_for::<T>();
+
+    let mut schema = serde_json::to_value(root_schema).unwrap_or_default();
+    if let Some(object) = schema.as_object_mut() {
+        object.remove("title");
+        object.remove("definitions");
+    }
+    schema
+}
+
+#[cfg(feature = "ssr")]
+fn make_strict(schema: &mut serde_json::Value) {
+    use serde_json::Value;
+
+    let Some(object) = schema.as_object_mut() else {
+        return;
+    };
+
+    for keyword in UNSUPPORTED_STRICT_KEYWORDS {
+        object.remove(keyword);
+    }
+
+    let property_names = match object.get_mut("properties") {
+        Some(Value::Object(properties)) => {
+            properties.values_mut().for_each(make_strict);
+            Some(properties.keys().cloned().map(Value::String).collect::<Vec<Value>>())
+        }
+        _ => None,
+    };
+    if let Some(property_names) = property_names {
+        object.insert("required".to_string(), Value::Array(property_names));
+        object.insert("additionalProperties".to_string(), Value::Bool(false));
+    }
+
+    if let Some(items) = object.get_mut("items") {
+        make_strict(items);
+    }
+    if let Some(Value::Array(variants)) = object.get_mut("anyOf") {
+        variants.iter_mut().for_each(make_strict);
+    }
+}
+
+#[cfg(feature = "ssr")]
+fn translation_schema() -> &'static jsonschema::Validator {
+    use std::sync::OnceLock;
+
+    static VALIDATOR: OnceLock<jsonschema::Validator> = OnceLock::new();
+    VALIDATOR.get_or_init(|| {
+        jsonschema::validator_for(&translation_json_schema()).expect("translation schema is valid")
+    })
+}
+
+// Checks a candidate JSON text against the SubtitleTranslationInfo schema, describing every violation on failure
+#[cfg(feature = "ssr")]
+fn parse_candidate(candidate: &str) -> Result<SubtitleTranslationInfo, String> {
+    let value: serde_json::Value = serde_json::from_str(candidate).map_err(|e| format!("Invalid JSON: {}", e))?;
+
+    let violations: Vec<String> = translation_schema()
+        .iter_errors(&value)
+        .map(|error| format!("{} at \"{}\"", error, error.instance_path))
+        .collect();
+    if !violations.is_empty() {
+        return Err(format!("Does not match the schema: {}", violations.join("; ")));
+    }
+
+    serde_json::from_value(value).map_err(|e| format!("Invalid translation: {}", e))
+}
+
+// Parses the model's answer, first as is and then by extracting the JSON from around it.
+// The error describes what was wrong with the answer, for the repair request.
+#[cfg(feature = "ssr")]
+pub fn parse_translation(response_text: &str) -> Result<(SubtitleTranslationInfo, ParsePath), String> {
+    let direct_error = match parse_candidate(response_text.trim()) {
+        Ok(info) => return Ok((info, ParsePath::Direct)),
+        Err(e) => e,
+    };
+
+    let unfenced = strip_code_fences(response_text);
+    match find_json_object(unfenced).or_else(|| find_json_object(response_text)) {
+        Some(candidate) => parse_candidate(candidate).map(|info| (info, ParsePath::Extracted)),
+        None => Err(direct_error),
+    }
+}
+
+// Parses the answer to a gloss request, extracting the JSON from around it like parse_translation
+#[cfg(feature = "ssr")]
+pub fn parse_glosses(response_text: &str) -> Result<WordGlosses, String> {
+    let parse = |candidate: &str| {
+        serde_json::from_str::<WordGlosses>(candidate).map_err(|e| format!("Invalid glosses: {}", e))
+    };
+    let direct_error = match parse(response_text.trim()) {
+        Ok(glosses) => return Ok(glosses),
+        Err(e) => e,
+    };
+
+    let unfenced = strip_code_fences(response_text);
+    match find_json_object(unfenced).or_else(|| find_json_object(response_text)) {
+        Some(candidate) => parse(candidate),
+        None => Err(direct_error),
+    }
+}
+
+#[cfg(all(test, feature = "ssr"))]
+mod tests {
+    use super::*;
+
+    const ANSWER: &str = r#"{"translation":"Hello","vocabulary":[{"word":"안녕","translation":"hi"}],"grammar_points":[]}"#;
+
+    #[test]
+    fn code_fences_are_stripped() {
+        assert_eq!(strip_code_fences("```json\n{\"a\": 1}\n```"), "{\"a\": 1}");
+        assert_eq!(strip_code_fences("Here you go:\n```\n{}\n```\nEnjoy"), "{}");
+        // An unclosed fence, as when the answer was cut off
+        assert_eq!(strip_code_fences("```json\n{\"a\": 1"), "{\"a\": 1");
+        assert_eq!(strip_code_fences("  {\"a\": 1}  "), "{\"a\": 1}");
+    }
+
+    #[test]
+    fn json_objects_are_found_in_prose() {
+        assert_eq!(find_json_object("Sure! {\"a\": \"}\"} Hope that helps."), Some("{\"a\": \"}\"}"));
+        // Braces that aren't JSON are skipped
+        assert_eq!(find_json_object("{not json} then {\"a\": {\"b\": 1}}"), Some("{\"a\": {\"b\": 1}}"));
+        assert_eq!(find_json_object("no object here"), None);
+        assert_eq!(find_json_object("{\"a\": 1"), None);
+    }
+
+    #[test]
+    fn valid_answers_are_parsed_directly() {
+        let (info, parse_path) = parse_translation(ANSWER).unwrap();
+        assert_eq!(parse_path, ParsePath::Direct);
+        assert_eq!(info.translation, "Hello");
+        assert_eq!(info.vocabulary[0].word, "안녕");
+    }
+
+    #[test]
+    fn fenced_and_prose_wrapped_answers_are_extracted() {
+        let fenced = format!("```json\n{}\n```", ANSWER);
+        let wrapped = format!("Here is the translation: {} Let me know if you need more.", ANSWER);
+        for answer in [fenced, wrapped] {
+            let (info, parse_path) = parse_translation(&answer).unwrap();
+            assert_eq!(parse_path, ParsePath::Extracted);
+            assert_eq!(info.translation, "Hello");
+        }
+    }
+
+    #[test]
+    fn missing_grammar_points_default_to_empty() {
+        let (info, parse_path) = parse_translation(r#"{"translation":"Hello","vocabulary":[]}"#).unwrap();
+        assert_eq!(parse_path, ParsePath::Direct);
+        assert!(info.grammar_points.is_empty());
+    }
+
+    #[test]
+    fn answers_that_dont_match_the_schema_are_described() {
+        let error = parse_translation(r#"{"vocabulary":[]}"#).unwrap_err();
+        assert!(error.contains("translation"), "{}", error);
+        assert!(parse_translation("I can't translate that.").is_err());
+    }
+
+    #[test]
+    fn partial_json_is_closed_after_the_last_complete_value() {
+        let partial = r#"{"translation":"Hel"#;
+        assert_eq!(close_partial_json(partial, true).as_deref(), Some(r#"{"translation":"Hel"}"#));
+        assert_eq!(close_partial_json(partial, false).as_deref(), Some("{}"));
+
+        let partial = r#"{"translation":"Hello","vocabulary":[{"word":"안녕","trans"#;
+        assert_eq!(
+            close_partial_json(partial, false).as_deref(),
+            Some(r#"{"translation":"Hello","vocabulary":[{"word":"안녕"}]}"#)
+        );
+    }
+
+    #[test]
+    fn partial_answers_show_the_translation_cut_off_mid_string() {
+        let partial = r#"Sure: {"translation":"Hello, how ar"#;
+        let info = parse_partial_translation(partial).unwrap();
+        assert_eq!(info.translation, "Hello, how ar");
+        assert!(info.vocabulary.is_empty());
+
+        // Vocabulary only shows once all of its required fields have arrived
+        let partial = r#"{"translation":"Hello","vocabulary":[{"word":"안녕","translation":"hi"},{"word":"친"#;
+        let info = parse_partial_translation(partial).unwrap();
+        assert_eq!(info.vocabulary.len(), 1);
+        assert!(parse_partial_translation("no json yet").is_none());
+    }
+}
@@ -0,0 +1,329 @@
+use async_trait::async_trait;
+use futures::stream::{BoxStream, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::fmt;
+use std::time::Duration;
+
+use crate::usage::TokenUsage;
+
+mod azure_openai;
+mod fixture;
+mod openai_compatible;
+
+pub use azure_openai::{AzureOpenAIConfig, AzureOpenAIProvider};
+pub use fixture::{stream_chunks, FixtureProvider};
+pub use openai_compatible::OpenAICompatibleProvider;
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ChatMessage {
+    pub role: String,
+    pub content: String,
+}
+
+impl ChatMessage {
+    pub fn new(role: &str, content: &str) -> Self {
+        Self {
+            role: role.to_string(),
+            content: content.to_string(),
+        }
+    }
+}
+
+// JSON schema the answer has to follow, sent to providers that support structured outputs
+#[derive(Clone, Debug)]
+pub struct ResponseSchema {
+    pub name: String,
+    pub schema: serde_json::Value,
+}
+
+impl ResponseSchema {
+    // The `response_format` field of an OpenAI or Azure OpenAI chat completion request
+    pub fn response_format(&self) -> serde_json::Value {
+        json!({
+            "type": "json_schema",
+            "json_schema": {
+                "name": self.name,
+                "strict": true,
+                "schema": self.schema,
+            }
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ChatRequest {
+    pub messages: Vec<ChatMessage>,
+    pub max_tokens: u32,
+    pub response_schema: Option<ResponseSchema>,
+}
+
+// A model host that can answer a chat completion request with the raw text of the first choice
+#[async_trait]
+pub trait TranslationProvider: Send + Sync + std::fmt::Debug {
+    fn name(&self) -> &'static str;
+
+    // Whether the provider honours `ChatRequest::response_schema`, otherwise the prompt alone asks for JSON
+    fn supports_structured_output(&self) -> bool {
+        false
+    }
+
+    async fn complete(&self, request: &ChatRequest) -> Result<Completion, ProviderError>;
+
+    // Streams the answer text as the model writes it, providers that can't stream send it as a single chunk
+    async fn complete_stream(&self, request: &ChatRequest) -> Result<CompletionStream, ProviderError> {
+        let completion = self.complete(request).await?;
+        let mut chunks = vec![Ok(CompletionChunk::Text(completion.text))];
+        if let Some(usage) = completion.usage {
+            chunks.push(Ok(CompletionChunk::Usage(usage)));
+        }
+        Ok(futures::stream::iter(chunks).boxed())
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Completion {
+    pub text: String,
+    // None when the provider doesn't report token usage
+    pub usage: Option<TokenUsage>,
+}
+
+#[derive(Clone, Debug)]
+pub enum CompletionChunk {
+    Text(String),
+    // Sent after the text by providers that report usage for streamed answers
+    Usage(TokenUsage),
+}
+
+pub type CompletionStream = BoxStream<'static, Result<CompletionChunk, ProviderError>>;
+
+#[derive(Clone, Debug)]
+pub enum ProviderError {
+    // The provider answered with an error status, retry_after is its Retry-After header if it sent one
+    Status {
+        status: u16,
+        retry_after: Option<Duration>,
+        message: String,
+    },
+    Timeout,
+    // The request couldn't be sent or the response couldn't be read, e.g. the connection was refused
+    Transport(String),
+    // The response wasn't a chat completion
+    InvalidResponse(String),
+}
+
+impl ProviderError {
+    // Whether the same request may succeed if sent again
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::Status { status, .. } => *status == 408 || *status == 429 || *status >= 500,
+            Self::Timeout | Self::Transport(_) => true,
+            Self::InvalidResponse(_) => false,
+        }
+    }
+
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::Status { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ProviderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Status { status, message, .. } => write!(f, "Provider returned status {}: {}", status, message),
+            Self::Timeout => write!(f, "Provider timed out"),
+            Self::Transport(message) => write!(f, "Error sending request: {}", message),
+            Self::InvalidResponse(message) => write!(f, "Invalid response: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for ProviderError {}
+
+// Sends a chat completion request to an OpenAI style endpoint and returns the text of the first choice
+pub(crate) async fn send_completion_request(http_request: reqwest::RequestBuilder) -> Result<Completion, ProviderError> {
+    let response = send_checked(http_request).await?;
+
+    let completion: ChatCompletionResponse = match response.json().await {
+        Ok(completion) => completion,
+        Err(e) if e.is_timeout() => return Err(ProviderError::Timeout),
+        Err(e) => return Err(ProviderError::InvalidResponse(format!("Error deserializing: {:?}", e))),
+    };
+
+    let usage = completion.usage.map(TokenUsage::from);
+    match completion.choices.into_iter().next() {
+        Some(choice) => Ok(Completion {
+            text: choice.message.content,
+            usage,
+        }),
+        None => Err(ProviderError::InvalidResponse("No response text found".to_string())),
+    }
+}
+
+// Sends a chat completion request with `"stream": true` and streams the content of the server-sent events
+pub(crate) async fn send_streaming_completion_request(
+    http_request: reqwest::RequestBuilder,
+) -> Result<CompletionStream, ProviderError> {
+    let response = send_checked(http_request).await?;
+
+    let state = ServerSentEvents {
+        bytes: response.bytes_stream().map(|chunk| chunk.map(|bytes| bytes.to_vec())).boxed(),
+        buffer: Vec::new(),
+        done: false,
+    };
+    Ok(futures::stream::unfold(state, |mut state| async move {
+        let next = state.next_content().await?;
+        Some((next, state))
+    })
+    .boxed())
+}
+
+struct ServerSentEvents {
+    bytes: BoxStream<'static, reqwest::Result<Vec<u8>>>,
+    buffer: Vec<u8>,
+    done: bool,
+}
+
+impl ServerSentEvents {
+    // The next non-empty content delta or the usage, None once the stream is over
+    async fn next_content(&mut self) -> Option<Result<CompletionChunk, ProviderError>> {
+        loop {
+            if self.done {
+                return None;
+            }
+
+            // Lines are split on bytes so that a character cut in two by the network is put back together
+            if let Some(newline) = self.buffer.iter().position(|byte| *byte == b'\n') {
+                let line: Vec<u8> = self.buffer.drain(..=newline).collect();
+                let line = String::from_utf8_lossy(&line);
+                let Some(data) = line.trim().strip_prefix("data:") else {
+                    continue;
+                };
+
+                let data = data.trim();
+                if data == "[DONE]" {
+                    self.done = true;
+                    return None;
+                }
+
+                match serde_json::from_str::<ChatCompletionChunk>(data) {
+                    // With stream_options.include_usage the last chunk has the usage and no choices
+                    Ok(ChatCompletionChunk { usage: Some(usage), .. }) => {
+                        return Some(Ok(CompletionChunk::Usage(TokenUsage::from(usage))))
+                    }
+                    // Azure sends chunks without choices, e.g. for content filter results
+                    Ok(chunk) => match chunk.choices.into_iter().next().and_then(|choice| choice.delta.content) {
+                        Some(content) if !content.is_empty() => return Some(Ok(CompletionChunk::Text(content))),
+                        _ => continue,
+                    },
+                    Err(e) => {
+                        self.done = true;
+                        return Some(Err(ProviderError::InvalidResponse(format!("Error deserializing chunk: {:?}", e))));
+                    }
+                }
+            }
+
+            match self.bytes.next().await {
+                Some(Ok(bytes)) => self.buffer.extend_from_slice(&bytes),
+                Some(Err(e)) => {
+                    self.done = true;
+                    return Some(Err(if e.is_timeout() {
+                        ProviderError::Timeout
+                    } else {
+                        ProviderError::Transport(format!("{:?}", e))
+                    }));
+                }
+                None => {
+                    self.done = true;
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+// Sends the request, turning error statuses into ProviderError::Status
+async fn send_checked(http_request: reqwest::RequestBuilder) -> Result<reqwest::Response, ProviderError> {
+    let response = match http_request.send().await {
+        Ok(response) => response,
+        Err(e) if e.is_timeout() => return Err(ProviderError::Timeout),
+        Err(e) => return Err(ProviderError::Transport(format!("{:?}", e))),
+    };
+
+    let status = response.status();
+    if !status.is_success() {
+        let retry_after = retry_after(response.headers());
+        let message = response.text().await.unwrap_or_default();
+        return Err(ProviderError::Status {
+            status: status.as_u16(),
+            retry_after,
+            message,
+        });
+    }
+
+    Ok(response)
+}
+
+// Azure sends retry-after-ms next to the standard Retry-After, which is only parsed in its seconds form
+fn retry_after(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+    let header_value = |name: &str| headers.get(name).and_then(|value| value.to_str().ok()).map(str::trim);
+
+    if let Some(millis) = header_value("retry-after-ms").and_then(|value| value.parse::<u64>().ok()) {
+        return Some(Duration::from_millis(millis));
+    }
+    header_value("retry-after")
+        .and_then(|value| value.parse::<u64>().ok())
+        .map(Duration::from_secs)
+}
+
+// Response body shared by the OpenAI and Azure OpenAI chat completion endpoints
+#[derive(Debug, Deserialize)]
+pub(crate) struct ChatCompletionResponse {
+    pub choices: Vec<ChatCompletionChoice>,
+    #[serde(default)]
+    pub usage: Option<ChatCompletionUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct ChatCompletionUsage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+impl From<ChatCompletionUsage> for TokenUsage {
+    fn from(usage: ChatCompletionUsage) -> Self {
+        Self {
+            prompt_tokens: usage.prompt_tokens,
+            completion_tokens: usage.completion_tokens,
+            estimated: false,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct ChatCompletionChoice {
+    pub message: ChatMessage,
+}
+
+// One server-sent event of a streamed chat completion
+#[derive(Debug, Deserialize)]
+pub(crate) struct ChatCompletionChunk {
+    #[serde(default)]
+    pub choices: Vec<ChatCompletionChunkChoice>,
+    #[serde(default)]
+    pub usage: Option<ChatCompletionUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct ChatCompletionChunkChoice {
+    #[serde(default)]
+    pub delta: ChatCompletionDelta,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub(crate) struct ChatCompletionDelta {
+    pub content: Option<String>,
+}
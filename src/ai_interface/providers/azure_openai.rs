@@ -0,0 +1,114 @@
+use async_trait::async_trait;
+use serde_json::json;
+
+use super::{
+    send_completion_request, send_streaming_completion_request, ChatRequest, Completion, CompletionStream,
+    ProviderError, TranslationProvider,
+};
+
+#[derive(Clone, Debug, Default)]
+pub struct AzureOpenAIConfig {
+    pub api_key: String,
+    pub endpoint: String,
+    pub deployment: String,
+    pub api_version: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct AzureOpenAIProvider {
+    client: reqwest::Client,
+    config: AzureOpenAIConfig,
+    structured_output: bool,
+}
+
+impl AzureOpenAIProvider {
+    pub fn new(config: AzureOpenAIConfig) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            config,
+            structured_output: true,
+        }
+    }
+
+    // Structured outputs need api version 2024-08-01-preview or later and a model that supports them
+    pub fn with_structured_output(mut self, structured_output: bool) -> Self {
+        self.structured_output = structured_output;
+        self
+    }
+
+    fn http_request(&self, request: &ChatRequest, stream: bool) -> reqwest::RequestBuilder {
+        let mut body = json!({
+            "messages": request.messages,
+            "max_tokens": request.max_tokens,
+            "stream": stream,
+        });
+        if let Some(response_schema) = &request.response_schema {
+            body["response_format"] = response_schema.response_format();
+        }
+
+        self.client
+            .post(self.completions_url())
+            .header("api-key", &self.config.api_key)
+            .json(&body)
+    }
+
+    fn completions_url(&self) -> String {
+        format!(
+            "{}/openai/deployments/{}/chat/completions?api-version={}",
+            self.config.endpoint.trim_end_matches('/'),
+            self.config.deployment,
+            self.config.api_version
+        )
+    }
+}
+
+#[async_trait]
+impl TranslationProvider for AzureOpenAIProvider {
+    fn name(&self) -> &'static str {
+        "azure"
+    }
+
+    fn supports_structured_output(&self) -> bool {
+        self.structured_output
+    }
+
+    async fn complete(&self, request: &ChatRequest) -> Result<Completion, ProviderError> {
+        send_completion_request(self.http_request(request, false)).await
+    }
+
+    async fn complete_stream(&self, request: &ChatRequest) -> Result<CompletionStream, ProviderError> {
+        send_streaming_completion_request(self.http_request(request, true)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai_interface::providers::ChatMessage;
+
+    #[test]
+    fn requests_go_to_the_deployment_with_the_api_key() {
+        let provider = AzureOpenAIProvider::new(AzureOpenAIConfig {
+            api_key: "key".to_string(),
+            endpoint: "https://example.openai.azure.com/".to_string(),
+            deployment: "gpt-4o".to_string(),
+            api_version: "2024-08-01-preview".to_string(),
+        });
+        let request = ChatRequest {
+            messages: vec![ChatMessage::new("user", "안녕")],
+            max_tokens: 100,
+            response_schema: None,
+        };
+        let http_request = provider.http_request(&request, false).build().unwrap();
+
+        assert_eq!(
+            http_request.url().as_str(),
+            "https://example.openai.azure.com/openai/deployments/gpt-4o/chat/completions?api-version=2024-08-01-preview"
+        );
+        assert_eq!(http_request.headers()["api-key"], "key");
+        let body: serde_json::Value =
+            serde_json::from_slice(http_request.body().and_then(|body| body.as_bytes()).unwrap()).unwrap();
+        assert_eq!(body["messages"][0]["content"], "안녕");
+        assert!(body.get("model").is_none());
+    }
+}
@@ -0,0 +1,154 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use futures::stream::StreamExt;
+use serde_json::json;
+use std::collections::HashMap;
+
+use super::{ChatRequest, Completion, CompletionChunk, CompletionStream, ProviderError, TranslationProvider};
+use crate::languages::LanguagePair;
+use crate::usage::TokenUsage;
+
+// Deterministic provider that answers from canned responses keyed by the input text.
+// Fixture files are a JSON object mapping input text to a `SubtitleTranslationInfo`.
+#[derive(Clone, Debug)]
+pub struct FixtureProvider {
+    responses: HashMap<String, serde_json::Value>,
+}
+
+impl FixtureProvider {
+    pub fn new() -> Self {
+        // Answer the few-shot examples of every supported language pair out of the box
+        let responses = LanguagePair::SUPPORTED
+            .iter()
+            .map(|language_pair| {
+                let (example_input, example_output) = language_pair.few_shot_example();
+                (example_input.to_string(), example_output)
+            })
+            .collect();
+        Self { responses }
+    }
+
+    pub fn from_file(path: &str) -> Result<Self> {
+        let file_text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) => return Err(anyhow!("Error reading fixture file {}: {:?}", path, e)),
+        };
+
+        let file_responses: HashMap<String, serde_json::Value> = match serde_json::from_str(&file_text) {
+            Ok(responses) => responses,
+            Err(e) => return Err(anyhow!("Error deserializing fixture file {}: {:?}", path, e)),
+        };
+
+        let mut provider = Self::new();
+        for (input_text, response) in file_responses {
+            provider.responses.insert(input_text.trim().to_string(), response);
+        }
+
+        Ok(provider)
+    }
+
+    pub fn response_for(&self, input_text: &str) -> serde_json::Value {
+        match self.responses.get(input_text.trim()) {
+            Some(response) => response.clone(),
+            None => json!({
+                "translation": format!("[fixture] {}", input_text.trim()),
+                "vocabulary": [],
+                "grammar_points": []
+            }),
+        }
+    }
+}
+
+// Splits an answer into pieces of a few characters, like a model streams it
+pub fn stream_chunks(response_text: &str) -> Vec<String> {
+    let characters: Vec<char> = response_text.chars().collect();
+    characters.chunks(8).map(|chunk| chunk.iter().collect()).collect()
+}
+
+impl Default for FixtureProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl TranslationProvider for FixtureProvider {
+    fn name(&self) -> &'static str {
+        "fixture"
+    }
+
+    async fn complete(&self, request: &ChatRequest) -> Result<Completion, ProviderError> {
+        let input_text = match request.messages.iter().rev().find(|message| message.role == "user") {
+            Some(message) => &message.content,
+            None => return Err(ProviderError::InvalidResponse("No user message found".to_string())),
+        };
+
+        let response_text = self.response_for(input_text).to_string();
+        let prompt_characters = request.messages.iter().map(|message| message.content.chars().count()).sum();
+        Ok(Completion {
+            usage: Some(TokenUsage::estimate(prompt_characters, response_text.chars().count())),
+            text: response_text,
+        })
+    }
+
+    async fn complete_stream(&self, request: &ChatRequest) -> Result<CompletionStream, ProviderError> {
+        let completion = self.complete(request).await?;
+        let chunks = stream_chunks(&completion.text)
+            .into_iter()
+            .map(CompletionChunk::Text)
+            .chain(completion.usage.map(CompletionChunk::Usage));
+        Ok(futures::stream::iter(chunks.map(Ok)).boxed())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai_interface::providers::ChatMessage;
+
+    fn request(text: &str) -> ChatRequest {
+        ChatRequest {
+            messages: vec![ChatMessage::new("system", "Translate"), ChatMessage::new("user", text)],
+            max_tokens: 100,
+            response_schema: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn answers_the_few_shot_examples_and_echoes_other_lines() {
+        let provider = FixtureProvider::new();
+        let (example_input, example_output) = LanguagePair::default().few_shot_example();
+        let completion = provider.complete(&request(example_input)).await.unwrap();
+        assert_eq!(completion.text, example_output.to_string());
+        assert!(completion.usage.is_some());
+
+        let completion = provider.complete(&request(" 처음 보는 줄 ")).await.unwrap();
+        let answer: serde_json::Value = serde_json::from_str(&completion.text).unwrap();
+        assert_eq!(answer["translation"], "[fixture] 처음 보는 줄");
+    }
+
+    #[tokio::test]
+    async fn streams_the_same_answer_in_pieces() {
+        let provider = FixtureProvider::new();
+        let completion = provider.complete(&request("안녕")).await.unwrap();
+
+        let chunks: Vec<CompletionChunk> =
+            provider.complete_stream(&request("안녕")).await.unwrap().map(Result::unwrap).collect().await;
+        let text: String = chunks
+            .iter()
+            .filter_map(|chunk| match chunk {
+                CompletionChunk::Text(text) => Some(text.as_str()),
+                CompletionChunk::Usage(_) => None,
+            })
+            .collect();
+        assert_eq!(text, completion.text);
+        assert!(chunks.len() > 2);
+        assert!(matches!(chunks.last(), Some(CompletionChunk::Usage(_))));
+    }
+
+    #[test]
+    fn missing_fixture_files_are_reported() {
+        let error = FixtureProvider::from_file("/nonexistent/translations.json").unwrap_err();
+        assert!(error.to_string().contains("/nonexistent/translations.json"));
+    }
+}
@@ -0,0 +1,142 @@
+use async_trait::async_trait;
+use serde_json::json;
+
+use super::{
+    send_completion_request, send_streaming_completion_request, ChatRequest, Completion, CompletionStream,
+    ProviderError, TranslationProvider,
+};
+
+// Any server exposing the OpenAI `/chat/completions` API, e.g. OpenAI itself, a local llama.cpp or Ollama server
+#[derive(Clone, Debug)]
+pub struct OpenAICompatibleProvider {
+    client: reqwest::Client,
+    base_url: String,
+    api_key: String,
+    model: String,
+    structured_output: bool,
+}
+
+impl OpenAICompatibleProvider {
+    pub fn new(base_url: String, api_key: String, model: String) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key,
+            model,
+            structured_output: true,
+        }
+    }
+
+    // Turn off for servers that reject `response_format`
+    pub fn with_structured_output(mut self, structured_output: bool) -> Self {
+        self.structured_output = structured_output;
+        self
+    }
+
+    fn http_request(&self, request: &ChatRequest, stream: bool) -> reqwest::RequestBuilder {
+        let mut body = json!({
+            "model": self.model,
+            "messages": request.messages,
+            "max_tokens": request.max_tokens,
+            "stream": stream,
+        });
+        if let Some(response_schema) = &request.response_schema {
+            body["response_format"] = response_schema.response_format();
+        }
+        // Streamed answers only report token usage when asked to
+        if stream {
+            body["stream_options"] = json!({ "include_usage": true });
+        }
+
+        let http_request = self
+            .client
+            .post(format!("{}/chat/completions", self.base_url))
+            .json(&body);
+
+        if self.api_key.is_empty() {
+            http_request
+        } else {
+            http_request.bearer_auth(&self.api_key)
+        }
+    }
+}
+
+#[async_trait]
+impl TranslationProvider for OpenAICompatibleProvider {
+    fn name(&self) -> &'static str {
+        "openai"
+    }
+
+    fn supports_structured_output(&self) -> bool {
+        self.structured_output
+    }
+
+    async fn complete(&self, request: &ChatRequest) -> Result<Completion, ProviderError> {
+        send_completion_request(self.http_request(request, false)).await
+    }
+
+    async fn complete_stream(&self, request: &ChatRequest) -> Result<CompletionStream, ProviderError> {
+        send_streaming_completion_request(self.http_request(request, true)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai_interface::providers::{ChatMessage, ResponseSchema};
+
+    fn request(response_schema: Option<ResponseSchema>) -> ChatRequest {
+        ChatRequest {
+            messages: vec![ChatMessage::new("user", "안녕")],
+            max_tokens: 100,
+            response_schema,
+        }
+    }
+
+    fn provider(base_url: &str, api_key: &str) -> OpenAICompatibleProvider {
+        OpenAICompatibleProvider::new(base_url.to_string(), api_key.to_string(), "llama".to_string())
+    }
+
+    fn body(http_request: reqwest::Request) -> serde_json::Value {
+        serde_json::from_slice(http_request.body().and_then(|body| body.as_bytes()).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn requests_go_to_the_chat_completions_endpoint() {
+        let provider = provider("http://localhost:8080/v1/", "key");
+        let http_request = provider.http_request(&request(None), false).build().unwrap();
+
+        assert_eq!(http_request.url().as_str(), "http://localhost:8080/v1/chat/completions");
+        assert_eq!(http_request.headers()["authorization"], "Bearer key");
+        let body = body(http_request);
+        assert_eq!(body["model"], "llama");
+        assert_eq!(body["messages"][0]["content"], "안녕");
+        assert_eq!(body["stream"], false);
+        assert!(body.get("response_format").is_none());
+    }
+
+    #[test]
+    fn local_servers_dont_need_a_key() {
+        let provider = provider("http://localhost:11434/v1", "");
+        let http_request = provider.http_request(&request(None), true).build().unwrap();
+
+        assert!(http_request.headers().get("authorization").is_none());
+        let body = body(http_request);
+        assert_eq!(body["stream"], true);
+        assert_eq!(body["stream_options"]["include_usage"], true);
+    }
+
+    #[test]
+    fn response_schema_is_sent_as_response_format() {
+        let provider = provider("http://localhost:8080/v1", "");
+        let schema = ResponseSchema {
+            name: "answer".to_string(),
+            schema: json!({ "type": "object" }),
+        };
+        let body = body(provider.http_request(&request(Some(schema)), false).build().unwrap());
+
+        assert_eq!(body["response_format"]["type"], "json_schema");
+        assert_eq!(body["response_format"]["json_schema"]["name"], "answer");
+        assert_eq!(body["response_format"]["json_schema"]["strict"], true);
+    }
+}
@@ -0,0 +1,209 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// How model calls are timed out and retried
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    // Time limit for a single call to the provider
+    pub request_timeout: Duration,
+    // Retries after the first attempt for transient failures (timeouts, 429 and 5xx)
+    pub max_retries: u32,
+    pub initial_backoff: Duration,
+    // Longest wait between attempts. A Retry-After longer than this is returned to the user instead of waited out.
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            request_timeout: Duration::from_secs(30),
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RetryPolicy {
+    // Exponential backoff for the given retry (starting at 0), or the provider's Retry-After if it sent one
+    pub fn backoff(&self, retry: u32, retry_after: Option<Duration>) -> Duration {
+        match retry_after {
+            Some(retry_after) => retry_after,
+            None => self
+                .initial_backoff
+                .saturating_mul(2u32.saturating_pow(retry))
+                .min(self.max_backoff),
+        }
+    }
+}
+
+// Stops calling a provider that keeps failing, so requests fail fast instead of each waiting out the retries.
+// After `failure_threshold` consecutive failed translations the circuit opens for `cooldown`. Then a single call is
+// let through as a trial while the others keep failing fast: success closes the circuit, failure opens it again.
+// A trial that doesn't report back within the cooldown, e.g. because it was cancelled, is replaced by the next call.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    cooldown: Duration,
+    state: Mutex<CircuitState>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum CircuitState {
+    Closed { consecutive_failures: u32 },
+    Open { until: Instant },
+    HalfOpen { trial_started: Instant },
+}
+
+impl CircuitBreaker {
+    // A threshold of 0 disables the breaker
+    pub fn new(failure_threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            failure_threshold,
+            cooldown,
+            state: Mutex::new(CircuitState::Closed { consecutive_failures: 0 }),
+        }
+    }
+
+    // Err with the time until the next call may be let through while the circuit is open or a trial is running
+    pub fn check(&self) -> Result<(), Duration> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        let wait = match *state {
+            CircuitState::Closed { .. } => return Ok(()),
+            CircuitState::Open { until } => until.saturating_duration_since(now),
+            CircuitState::HalfOpen { trial_started } => (trial_started + self.cooldown).saturating_duration_since(now),
+        };
+        if !wait.is_zero() {
+            return Err(wait);
+        }
+        *state = CircuitState::HalfOpen { trial_started: now };
+        Ok(())
+    }
+
+    pub fn record_success(&self) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        *state = CircuitState::Closed { consecutive_failures: 0 };
+    }
+
+    pub fn record_failure(&self) {
+        if self.failure_threshold == 0 {
+            return;
+        }
+
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let consecutive_failures = match *state {
+            CircuitState::Closed { consecutive_failures } => consecutive_failures.saturating_add(1),
+            // Calls that were let through before the circuit opened
+            CircuitState::Open { .. } => return,
+            CircuitState::HalfOpen { .. } => {
+                leptos::logging::warn!("Translation provider trial call failed, pausing calls for {:?}", self.cooldown);
+                *state = CircuitState::Open { until: Instant::now() + self.cooldown };
+                return;
+            }
+        };
+        if consecutive_failures >= self.failure_threshold {
+            leptos::logging::warn!(
+                "Translation provider failed {} times in a row, pausing calls for {:?}",
+                consecutive_failures,
+                self.cooldown
+            );
+            *state = CircuitState::Open { until: Instant::now() + self.cooldown };
+        } else {
+            *state = CircuitState::Closed { consecutive_failures };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_up_to_the_maximum() {
+        let policy = RetryPolicy {
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(3),
+            ..RetryPolicy::default()
+        };
+        let backoffs: Vec<Duration> = (0..5).map(|retry| policy.backoff(retry, None)).collect();
+        assert_eq!(backoffs, [500, 1000, 2000, 3000, 3000].map(Duration::from_millis));
+    }
+
+    #[test]
+    fn retry_after_replaces_the_backoff() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.backoff(0, Some(Duration::from_secs(7))), Duration::from_secs(7));
+        // Even past max_backoff, the caller decides whether to wait that long
+        assert_eq!(policy.backoff(2, Some(Duration::from_secs(60))), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn circuit_opens_after_consecutive_failures() {
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(30));
+        breaker.record_failure();
+        breaker.record_success();
+        breaker.record_failure();
+        assert!(breaker.check().is_ok());
+
+        breaker.record_failure();
+        let remaining = breaker.check().unwrap_err();
+        assert!(remaining > Duration::from_secs(29) && remaining <= Duration::from_secs(30));
+    }
+
+    #[test]
+    fn half_open_circuit_lets_a_single_trial_through() {
+        let cooldown = Duration::from_millis(50);
+        let breaker = CircuitBreaker::new(1, cooldown);
+        breaker.record_failure();
+        assert!(breaker.check().is_err());
+
+        std::thread::sleep(cooldown);
+        assert!(breaker.check().is_ok());
+        // Other calls wait for the trial
+        assert!(breaker.check().is_err());
+
+        breaker.record_success();
+        assert!(breaker.check().is_ok());
+        assert!(breaker.check().is_ok());
+    }
+
+    #[test]
+    fn failed_trial_opens_the_circuit_again() {
+        let cooldown = Duration::from_millis(50);
+        let breaker = CircuitBreaker::new(3, cooldown);
+        for _ in 0..3 {
+            breaker.record_failure();
+        }
+
+        std::thread::sleep(cooldown);
+        assert!(breaker.check().is_ok());
+        breaker.record_failure();
+        assert!(breaker.check().is_err());
+
+        std::thread::sleep(cooldown);
+        assert!(breaker.check().is_ok());
+    }
+
+    #[test]
+    fn abandoned_trial_is_replaced_after_the_cooldown() {
+        let cooldown = Duration::from_millis(50);
+        let breaker = CircuitBreaker::new(1, cooldown);
+        breaker.record_failure();
+
+        std::thread::sleep(cooldown);
+        assert!(breaker.check().is_ok());
+        std::thread::sleep(cooldown);
+        assert!(breaker.check().is_ok());
+        assert!(breaker.check().is_err());
+    }
+
+    #[test]
+    fn threshold_of_zero_never_opens() {
+        let breaker = CircuitBreaker::new(0, Duration::from_secs(30));
+        for _ in 0..10 {
+            breaker.record_failure();
+        }
+        assert!(breaker.check().is_ok());
+    }
+}
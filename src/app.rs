@@ -9,6 +9,26 @@ mod views;
 
 use views::*;
 
+// URLs of the server functions that call the model or analyze whole files, rate limited by crate::rate_limit
+#[cfg(feature = "ssr")]
+pub fn translation_server_fn_paths() -> Vec<&'static str> {
+    use leptos::server_fn::ServerFn;
+    vec![
+        home::GetTranslateInfo::PATH,
+        home::StreamTranslateInfo::PATH,
+        home::StartBatchTranslation::PATH,
+        home::GlossWords::PATH,
+        home::AnalyzeEpisodeVocabulary::PATH,
+    ]
+}
+
+// The ones among them that stream their answer and report errors in the stream
+#[cfg(feature = "ssr")]
+pub fn streaming_server_fn_paths() -> Vec<&'static str> {
+    use leptos::server_fn::ServerFn;
+    vec![home::StreamTranslateInfo::PATH]
+}
+
 pub fn shell(options: LeptosOptions) -> impl IntoView {
     view! {
         <!DOCTYPE html>
@@ -66,6 +86,7 @@ pub fn App() -> impl IntoView {
                                 <span class="fs-4">Drama Study Tool</span>
                             </a>
                             <NavLink to=format!("/")>"Home"</NavLink>
+                            <NavLink to=format!("/usage")>"Usage"</NavLink>
                             <NavLink to=format!("/about")>"About"</NavLink>
                         </header>
                     </div>
@@ -80,6 +101,8 @@ pub fn App() -> impl IntoView {
                         }>
                             <Route path=path!("/") view=home::Home />
                             <Route path=path!("/about") view=about::About />
+                            <Route path=path!("/usage") view=usage::Usage />
+                            <Route path=path!("/admin") view=admin::Admin />
                         </Routes>
                     </div>
                 </main>
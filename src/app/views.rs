@@ -1,2 +1,4 @@
 pub mod home;
-pub mod about;
\ No newline at end of file
+pub mod about;
+pub mod admin;
+pub mod usage;
\ No newline at end of file
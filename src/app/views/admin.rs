@@ -0,0 +1,154 @@
+use leptos::prelude::*;
+
+use crate::translation_cache::CacheStats;
+
+#[cfg(feature = "ssr")]
+fn admin_context(admin_token: &str) -> Result<crate::context::DramaStudyToolAppContext, ServerFnError> {
+    use crate::context::DramaStudyToolAppContext;
+    let app_context = match use_context::<DramaStudyToolAppContext>() {
+        Some(context) => context,
+        None => return Err(ServerFnError::ServerError("Couldn't get app context".to_string())),
+    };
+
+    if !app_context.is_admin(admin_token) {
+        return Err(ServerFnError::new("Invalid admin token"));
+    }
+
+    Ok(app_context)
+}
+
+#[server]
+pub async fn get_cache_stats(admin_token: String) -> Result<CacheStats, ServerFnError> {
+    let app_context = admin_context(&admin_token)?;
+
+    match &app_context.translation_cache {
+        Some(cache) => Ok(cache.stats()),
+        None => Err(ServerFnError::new("The translation cache is disabled")),
+    }
+}
+
+// Removes the cached translations of input_text, or every cached translation when it is empty
+#[server]
+pub async fn invalidate_translation_cache(admin_token: String, input_text: String) -> Result<usize, ServerFnError> {
+    let app_context = admin_context(&admin_token)?;
+
+    let cache = match &app_context.translation_cache {
+        Some(cache) => cache,
+        None => return Err(ServerFnError::new("The translation cache is disabled")),
+    };
+
+    if input_text.trim().is_empty() {
+        Ok(cache.clear())
+    } else {
+        Ok(cache.invalidate_text(&input_text))
+    }
+}
+
+#[component]
+pub fn Admin() -> impl IntoView {
+    let cache_stats_action = ServerAction::<GetCacheStats>::new();
+    let invalidate_action = ServerAction::<InvalidateTranslationCache>::new();
+
+    let (admin_token, set_admin_token) = signal(String::new());
+
+    view! {
+        <h1>Admin</h1>
+        <div class="mb-3">
+            <label for="admin_token" class="form-label">
+                Admin token
+            </label>
+            <input
+                class="form-control"
+                type="password"
+                id="admin_token"
+                on:input=move |ev| set_admin_token.set(event_target_value(&ev))
+                prop:value=admin_token
+            />
+        </div>
+
+        <h3>Translation cache</h3>
+        <ActionForm action=cache_stats_action>
+            <input type="hidden" name="admin_token" prop:value=admin_token />
+            <input type="submit" class="btn btn-primary mb-3" value="Show stats" />
+        </ActionForm>
+        {move || {
+            cache_stats_action
+                .value()
+                .get()
+                .map(|result| match result {
+                    Ok(stats) => view! { <CacheStatsTable stats=stats /> }.into_any(),
+                    Err(e) => view! { <div class="alert alert-danger">{e.to_string()}</div> }.into_any(),
+                })
+        }}
+
+        <ActionForm action=invalidate_action>
+            <input type="hidden" name="admin_token" prop:value=admin_token />
+            <div class="mb-3">
+                <label for="invalidate_input_text" class="form-label">
+                    Subtitle text to invalidate, leave empty to clear the whole cache
+                </label>
+                <input class="form-control" type="text" id="invalidate_input_text" name="input_text" />
+            </div>
+            <input type="submit" class="btn btn-danger" value="Invalidate" />
+        </ActionForm>
+        {move || {
+            invalidate_action
+                .value()
+                .get()
+                .map(|result| match result {
+                    Ok(removed) => {
+                        view! { <div class="alert alert-success mt-3">"Removed " {removed} " entries"</div> }
+                            .into_any()
+                    }
+                    Err(e) => view! { <div class="alert alert-danger mt-3">{e.to_string()}</div> }.into_any(),
+                })
+        }}
+    }
+}
+
+#[component]
+fn CacheStatsTable(stats: CacheStats) -> impl IntoView {
+    let lookups = stats.memory_hits + stats.disk_hits + stats.misses;
+    let hit_rate = if lookups == 0 {
+        0.0
+    } else {
+        (stats.memory_hits + stats.disk_hits) as f64 / lookups as f64 * 100.0
+    };
+
+    view! {
+        <table class="table">
+            <tbody>
+                <tr>
+                    <th>Memory hits</th>
+                    <td>{stats.memory_hits}</td>
+                </tr>
+                <tr>
+                    <th>Disk hits</th>
+                    <td>{stats.disk_hits}</td>
+                </tr>
+                <tr>
+                    <th>Misses</th>
+                    <td>{stats.misses}</td>
+                </tr>
+                <tr>
+                    <th>Hit rate</th>
+                    <td>{format!("{:.1}%", hit_rate)}</td>
+                </tr>
+                <tr>
+                    <th>Entries in memory</th>
+                    <td>{stats.memory_entries}</td>
+                </tr>
+                <tr>
+                    <th>Entries on disk</th>
+                    <td>
+                        {if stats.persistent {
+                            stats.disk_entries.to_string()
+                        } else {
+                            "Not persisted".to_string()
+                        }}
+                    </td>
+                </tr>
+            </tbody>
+        </table>
+    }
+}
@@ -1,54 +1,317 @@
 use leptos::{html::Input, logging::log, prelude::*, task::spawn_local};
-use leptos::ev::Event;
 
-use crate::ai_interface::{SubtitleTranslationInfo, VocabularyInfo};
+use leptos::server_fn::codec::{Json, StreamingText, TextStream};
+use leptos::server_fn::ServerFn;
+
+use crate::ai_interface::{
+    SubtitleTranslationInfo, TranslateError, TranslationContext, TranslationResult, VocabularyInfo,
+};
+use crate::batch::{BatchCue, BatchProgress};
+use crate::config::TranslationStatus;
+use crate::languages::LanguagePair;
+use crate::romanization::{has_hangul, romanize};
+
+mod study_list;
+#[cfg(feature = "ssr")]
+pub use study_list::{AnalyzeEpisodeVocabulary, GlossWords};
+mod subtitle_export;
+mod subtitle_file_input;
+use subtitle_file_input::SubtitleFileInput;
+mod subtitle_search;
+mod translator;
+use translator::{TranslationState, Translator};
+
+use std::collections::HashMap;
+use std::iter::Iterator;
+use std::time::Duration;
 
-use web_sys::HtmlInputElement;
+const BATCH_POLL_INTERVAL: Duration = Duration::from_secs(1);
 
-use srtlib::Subtitles;
+#[cfg(feature = "ssr")]
+fn app_context() -> Result<crate::context::DramaStudyToolAppContext, TranslateError> {
+    use crate::context::DramaStudyToolAppContext;
+    match use_context::<DramaStudyToolAppContext>() {
+        Some(context) => Ok(context),
+        None => Err(TranslateError::Other("Couldn't get app context".to_string())),
+    }
+}
 
-use std::iter::Iterator;
+// The AI interface to translate with, or why nothing can be translated into the language pair
+#[cfg(feature = "ssr")]
+fn checked_ai_interface(language_pair: &LanguagePair) -> Result<crate::ai_interface::AIInterface, TranslateError> {
+    let ai_interface = match app_context()?.ai_interface {
+        Some(ai_interface) => ai_interface,
+        None => return Err(TranslateError::Disabled),
+    };
 
-fn format_timestamp_without_ms(timestamp: &srtlib::Timestamp) -> String {
-    let timestamp_vals = timestamp.get();
-    format!("{:02}:{:02}:{:02}", timestamp_vals.0, timestamp_vals.1, timestamp_vals.2)
+    if !language_pair.is_supported() {
+        return Err(TranslateError::UnsupportedLanguagePair(language_pair.label()));
+    }
+
+    Ok(ai_interface)
 }
 
-#[server]
-pub async fn get_translate_info(input_text: String) -> Result<SubtitleTranslationInfo, ServerFnError> {
-    use crate::ai_interface::*;
-    use leptos::logging::*;
-    // use crate::context::DramaStudyToolAppContext;
-    // let app_context_option = use_context::<DramaStudyToolAppContext>();
-    // let app_context = match app_context_option {
-    //     Some(context) => context,
-    //     None => return Err(ServerFnError::ServerError(format!("Couldn't get app context"))),
-    // };
-    // let ai_interface = &app_context.ai_interface;
+// Rejects empty text and text over the configured length cap
+#[cfg(feature = "ssr")]
+fn check_input(input_text: &str, context: Option<&TranslationContext>) -> Result<(), TranslateError> {
+    let request_limits = app_context()?.request_limits;
+    request_limits.check_input(input_text)?;
+    request_limits.check_context(context)
+}
 
-    if input_text == "".to_string() {
-        return Err(ServerFnError::new(format!("Input text is empty")));
-    }
+// Who the translation is billed to, the file is the title of the uploaded subtitles
+#[cfg(feature = "ssr")]
+async fn usage_scope(context: Option<&TranslationContext>) -> crate::usage::UsageScope {
+    let session_id = crate::session::current_session_id().await;
+    crate::usage::UsageScope::new(&session_id, context)
+}
 
-    let translated_text_info = match AIInterface::translate(input_text.clone()).await {
+#[server]
+pub async fn get_translate_info(
+    input_text: String,
+    #[server(default)] language_pair: LanguagePair,
+    #[server(default)] context: Option<TranslationContext>,
+) -> Result<TranslationResult, ServerFnError<TranslateError>> {
+    check_input(&input_text, context.as_ref())?;
+    let ai_interface = checked_ai_interface(&language_pair)?;
+    let usage = app_context()?.usage;
+    let usage_scope = usage_scope(context.as_ref()).await;
+
+    let translated_text_info = match usage
+        .translate(&ai_interface, &usage_scope, input_text.clone(), language_pair, context.as_ref())
+        .await
+    {
         Ok(translated_text) => translated_text,
-        Err(e) => return Err(ServerFnError::new(format!("Error: {:?}", e))),
+        Err(e) => return Err(e.into()),
     };
 
     Ok(translated_text_info)
 }
 
+// Streams TranslationStreamEvents, one JSON object per line. The streaming codec can't carry a TranslateError,
+// so errors are sent as a Failed event too.
+#[server(output = StreamingText)]
+pub async fn stream_translate_info(
+    input_text: String,
+    #[server(default)] language_pair: LanguagePair,
+    #[server(default)] context: Option<TranslationContext>,
+) -> Result<TextStream, ServerFnError> {
+    use crate::ai_interface::TranslationStreamEvent;
+    use futures::StreamExt;
+
+    let events = match translation_events(input_text, language_pair, context).await {
+        Ok(events) => events.boxed(),
+        Err(e) => futures::stream::once(async move { TranslationStreamEvent::Failed(e) }).boxed(),
+    };
+
+    Ok(TextStream::new(events.map(|event| Ok(event.to_line()))))
+}
+
+#[cfg(feature = "ssr")]
+async fn translation_events(
+    input_text: String,
+    language_pair: LanguagePair,
+    context: Option<TranslationContext>,
+) -> Result<impl futures::Stream<Item = crate::ai_interface::TranslationStreamEvent>, TranslateError> {
+    check_input(&input_text, context.as_ref())?;
+    let ai_interface = checked_ai_interface(&language_pair)?;
+    let usage = app_context()?.usage;
+    let usage_scope = usage_scope(context.as_ref()).await;
+
+    Ok(usage.translate_stream(&ai_interface, usage_scope, input_text, language_pair, context))
+}
+
+// Starts pre-translating the cues of a subtitle file in the background and returns the job id
+#[server(input = Json)]
+pub async fn start_batch_translation(
+    cues: Vec<BatchCue>,
+    language_pair: LanguagePair,
+) -> Result<String, ServerFnError<TranslateError>> {
+    let ai_interface = checked_ai_interface(&language_pair)?;
+
+    let app_context = app_context()?;
+    let session_id = crate::session::current_session_id().await;
+
+    let cues: Vec<BatchCue> = cues.into_iter().filter(|cue| !cue.text.trim().is_empty()).collect();
+    for cue in &cues {
+        app_context.request_limits.check_input(&cue.text)?;
+        app_context.request_limits.check_context(cue.context.as_ref())?;
+    }
+    let client_ip = crate::rate_limit::current_client_ip().await;
+    app_context.request_limits.check_batch(client_ip, &session_id, cues.len())?;
+
+    Ok(app_context
+        .batch_jobs
+        .start(ai_interface, app_context.usage, session_id, cues, language_pair)?)
+}
+
+// Progress of a batch job with the cues finished since `since`
+#[server]
+pub async fn get_batch_progress(job_id: String, since: usize) -> Result<BatchProgress, ServerFnError> {
+    let app_context = app_context().map_err(|e| ServerFnError::new(e.message()))?;
+    let session_id = crate::session::current_session_id().await;
+    match app_context.batch_jobs.progress(&job_id, &session_id, since) {
+        Some(progress) => Ok(progress),
+        None => Err(ServerFnError::new(format!("Unknown batch job {}", job_id))),
+    }
+}
+
+#[server]
+pub async fn cancel_batch_translation(job_id: String) -> Result<bool, ServerFnError> {
+    let app_context = app_context().map_err(|e| ServerFnError::new(e.message()))?;
+    let session_id = crate::session::current_session_id().await;
+    Ok(app_context.batch_jobs.cancel(&job_id, &session_id))
+}
+
+#[server]
+pub async fn get_translation_status() -> Result<TranslationStatus, ServerFnError> {
+    use crate::context::DramaStudyToolAppContext;
+    let app_context = match use_context::<DramaStudyToolAppContext>() {
+        Some(context) => context,
+        None => return Err(ServerFnError::ServerError("Couldn't get app context".to_string())),
+    };
+
+    Ok(app_context.translation_status)
+}
+
+// Pre-translation of every cue of the uploaded file, polled from the server
+#[derive(Clone, Copy)]
+struct BatchTranslation {
+    job_id: RwSignal<Option<String>>,
+    progress: RwSignal<Option<BatchProgress>>,
+    // Successful translations by cue index
+    results: RwSignal<HashMap<usize, TranslationResult>>,
+    error: RwSignal<Option<TranslateError>>,
+}
+
+impl BatchTranslation {
+    fn new() -> Self {
+        Self {
+            job_id: RwSignal::new(None),
+            progress: RwSignal::new(None),
+            results: RwSignal::new(HashMap::new()),
+            error: RwSignal::new(None),
+        }
+    }
+
+    fn is_running(&self) -> bool {
+        self.job_id.with(Option::is_some)
+            && !self.progress.with(|progress| progress.as_ref().map(|progress| progress.finished).unwrap_or(false))
+    }
+
+    fn start(&self, cues: Vec<BatchCue>, language_pair: LanguagePair) {
+        let batch = *self;
+        batch.reset();
+
+        spawn_local(async move {
+            match start_batch_translation(cues, language_pair).await {
+                Ok(job_id) => {
+                    batch.job_id.set(Some(job_id.clone()));
+                    batch.poll(job_id, 0);
+                }
+                Err(ServerFnError::WrappedServerError(e)) => batch.error.set(Some(e)),
+                Err(e) => batch.error.set(Some(TranslateError::Other(e.to_string()))),
+            }
+        });
+    }
+
+    fn poll(&self, job_id: String, since: usize) {
+        let batch = *self;
+
+        spawn_local(async move {
+            let progress_result = get_batch_progress(job_id.clone(), since).await;
+
+            // Stop polling once the job has been replaced, e.g. by uploading another file
+            if batch.job_id.with_untracked(|current_job_id| current_job_id.as_deref() != Some(job_id.as_str())) {
+                return;
+            }
+
+            let mut progress = match progress_result {
+                Ok(progress) => progress,
+                Err(e) => return batch.error.set(Some(TranslateError::Other(e.to_string()))),
+            };
+
+            let new_results = std::mem::take(&mut progress.results);
+            batch.results.update(|results| {
+                for cue_result in new_results {
+                    if let Ok(result) = cue_result.result {
+                        results.insert(cue_result.index, result);
+                    }
+                }
+            });
+
+            let next = progress.next;
+            let finished = progress.finished;
+            if progress.error.is_some() {
+                batch.error.set(progress.error.clone());
+            }
+            batch.progress.set(Some(progress));
+
+            if !finished {
+                set_timeout(move || batch.poll(job_id, next), BATCH_POLL_INTERVAL);
+            }
+        });
+    }
+
+    fn cancel(&self) {
+        if let Some(job_id) = self.job_id.get_untracked() {
+            spawn_local(async move {
+                if let Err(e) = cancel_batch_translation(job_id).await {
+                    log!("Error cancelling batch translation: {:?}", e);
+                }
+            });
+        }
+    }
+
+    fn reset(&self) {
+        self.job_id.set(None);
+        self.progress.set(None);
+        self.results.set(HashMap::new());
+        self.error.set(None);
+    }
+}
+
 #[component]
 pub fn Home() -> impl IntoView {
-    let get_translate_info_action = ServerAction::<GetTranslateInfo>::new();
+    let translator = Translator::new();
+
+    let text_input: NodeRef<Input> = NodeRef::new();
+
+    let translation_status = Resource::new(|| (), |_| get_translation_status());
 
     let (saved_word_list, set_saved_word_list) = signal(Vec::<(usize, VocabularyInfo)>::new());
 
     let (direct_input, direct_input_set) = signal(true);
 
+    let (language_pair, set_language_pair) = signal(LanguagePair::default());
+
+    let (show_romanization, set_show_romanization) = signal(true);
+
     view! {
+        <Suspense fallback=|| ()>
+            {move || {
+                translation_status
+                    .get()
+                    .and_then(|status| status.ok())
+                    .filter(|status| !status.enabled)
+                    .map(|status| view! { <TranslationDisabledBanner status=status /> })
+            }}
+        </Suspense>
         <div class="row">
             <div class="col col-md-6">
+                <LanguagePairSelect language_pair=language_pair set_language_pair=set_language_pair />
+                <div class="form-check mb-3">
+                    <input
+                        class="form-check-input"
+                        type="checkbox"
+                        id="show_romanization"
+                        prop:checked=move || show_romanization.get()
+                        on:change=move |ev| set_show_romanization.set(event_target_checked(&ev))
+                    />
+                    <label class="form-check-label" for="show_romanization">
+                        Show romanization
+                    </label>
+                </div>
                 <ul class="nav nav-tabs">
                     <li class="nav-item">
                         <a
@@ -74,10 +337,30 @@ pub fn Home() -> impl IntoView {
                 <Show
                     when=move || direct_input.get()
                     fallback=move || {
-                        view! { <SubtitleFileInput translate_action=get_translate_info_action /> }
+                        view! {
+                            <SubtitleFileInput
+                                translator=translator
+                                language_pair=language_pair
+                                saved_word_list=saved_word_list
+                                set_saved_word_list=set_saved_word_list
+                                show_romanization=show_romanization
+                            />
+                        }
                     }
                 >
-                    <ActionForm action=get_translate_info_action>
+                    // Without JavaScript, or before hydration, the form posts to get_translate_info like an
+                    // ActionForm. Once hydrated the translation is streamed instead.
+                    <form
+                        method="post"
+                        action=GetTranslateInfo::url()
+                        on:submit=move |ev| {
+                            ev.prevent_default();
+                            if let Some(input) = text_input.get() {
+                                translator.translate(input.value(), language_pair.get_untracked(), None);
+                            }
+                        }
+                    >
+                        <input type="hidden" name="language_pair" prop:value=move || language_pair.get().id() />
                         <div class="mb-3">
                             <label for="translation_input" class="form-label">
                                 Input language text
@@ -85,29 +368,90 @@ pub fn Home() -> impl IntoView {
                             <input
                                 class="form-control"
                                 type="text"
-                                placeholder="e.g: 사전을 못 찾아"
+                                placeholder=move || language_pair.get().placeholder()
                                 name="input_text"
+                                node_ref=text_input
                             />
                         </div>
                         <input type="submit" class="btn btn-primary" value="Translate" />
-                    </ActionForm>
+                    </form>
                 </Show>
             </div>
             <div class="col col-md-6">
                 <TranslationBox
-                    translate_action=get_translate_info_action
+                    translation_state=translator.state.read_only()
                     saved_word_list=saved_word_list
                     set_saved_word_list=set_saved_word_list
+                    show_romanization=show_romanization
                 />
             </div>
         </div>
         <div class="row">
             <hr />
-            <SavedWordBox saved_word_list=saved_word_list set_saved_word_list=set_saved_word_list />
+            <SavedWordBox
+                saved_word_list=saved_word_list
+                set_saved_word_list=set_saved_word_list
+                show_romanization=show_romanization
+            />
         </div>
     }
 }
 
+#[component]
+fn LanguagePairSelect(language_pair: ReadSignal<LanguagePair>, set_language_pair: WriteSignal<LanguagePair>) -> impl IntoView {
+    view! {
+        <div class="mb-3">
+            <label for="language_pair" class="form-label">
+                Language
+            </label>
+            <select
+                class="form-select"
+                id="language_pair"
+                on:change=move |ev| {
+                    if let Some(selected_pair) = LanguagePair::from_id(&event_target_value(&ev)) {
+                        set_language_pair.set(selected_pair);
+                    }
+                }
+                prop:value=move || language_pair.get().id()
+            >
+                {LanguagePair::SUPPORTED
+                    .into_iter()
+                    .map(|supported_pair| {
+                        view! { <option value=supported_pair.id()>{supported_pair.label()}</option> }
+                    })
+                    .collect_view()}
+            </select>
+        </div>
+    }
+}
+
+#[component]
+fn TranslationDisabledBanner(status: TranslationStatus) -> impl IntoView {
+    view! {
+        <div class="alert alert-warning">
+            <b>Translation is disabled.</b>
+            " The server's "
+            {status.provider}
+            " translation provider is not configured correctly. Check these settings:"
+            <ul class="mb-0">
+                {status
+                    .problems
+                    .into_iter()
+                    .map(|setting| view! { <li><code>{setting}</code></li> })
+                    .collect_view()}
+            </ul>
+        </div>
+    }
+}
+
+// Typed translation errors carry a message telling the user what to do, anything else is shown as is
+fn translate_error_message(error: &ServerFnError<TranslateError>) -> String {
+    match error {
+        ServerFnError::WrappedServerError(translate_error) => translate_error.message(),
+        other_error => other_error.to_string(),
+    }
+}
+
 #[component]
 fn TranslationOutputBox(message: String) -> impl IntoView {
     view! {
@@ -118,14 +462,23 @@ fn TranslationOutputBox(message: String) -> impl IntoView {
 }
 
 #[component]
-fn TranslationBox(translate_action: ServerAction<GetTranslateInfo>, saved_word_list: ReadSignal<Vec<(usize, VocabularyInfo)>>, set_saved_word_list: WriteSignal<Vec<(usize,VocabularyInfo)>>) -> impl IntoView {
-
-    let translation_pending = translate_action.pending();
-
-    let translation_result_option = translate_action.value();
+fn TranslationBox(
+    translation_state: ReadSignal<TranslationState>,
+    saved_word_list: ReadSignal<Vec<(usize, VocabularyInfo)>>,
+    set_saved_word_list: WriteSignal<Vec<(usize, VocabularyInfo)>>,
+    show_romanization: ReadSignal<bool>,
+) -> impl IntoView {
+
+     // Another form of a saved word isn't saved again
+     let is_saved = move |vocab: &VocabularyInfo| {
+        saved_word_list.with(|saved| saved.iter().any(|(_, saved)| saved.dictionary_form() == vocab.dictionary_form()))
+     };
 
      let add_saved_word_fn = move |new_element: VocabularyInfo| { 
         let mut saved_word_list_value = saved_word_list.get();
+        if is_saved(&new_element) {
+            return;
+        }
         let max_index = match saved_word_list_value.last() {
             Some((index, _)) => index + 1,
             None => 0,
@@ -153,82 +506,121 @@ fn TranslationBox(translate_action: ServerAction<GetTranslateInfo>, saved_word_l
     // let translation_result_option = RwSignal::new(Some(Ok::<SubtitleTranslationInfo,ServerFnError>(fake_data)));
     // let translation_result_option = RwSignal::new(Some(Err::<SubtitleTranslationInfo,ServerFnError>(ServerFnError::new("Broken"))));
 
-    let get_translation_object = move || translation_result_option.get()
-                                                                                .unwrap()
-                                                                                .unwrap();
+    // Shared by finished and still streaming translations
+    let translation_info_view = move |translation_info: SubtitleTranslationInfo| {
+        view! {
+            <TranslationOutputBox message=translation_info.translation />
+            <h4>Vocabulary</h4>
+            <ul>
+                {translation_info
+                    .vocabulary
+                    .into_iter()
+                    .map(|vocab| {
+                        let vocab_clone = vocab.clone();
+                        let saved_check = vocab.clone();
+                        let saved_label = vocab.clone();
+                        let details = vocab_details(&vocab);
+                        let notes = vocab_notes(&vocab);
+                        let romanization = vocab_romanization(&vocab);
+                        view! {
+                            <li>
+                                <b>{vocab.word}</b>
+                                {move || romanization_view(show_romanization, romanization.clone())}
+                                {details}
+                                -
+                                {vocab.translation}
+                                <button
+                                    on:click=move |_| add_saved_word_fn(vocab_clone.clone())
+                                    prop:disabled=move || is_saved(&saved_check)
+                                >
+                                    {move || if is_saved(&saved_label) { "Saved" } else { "Add word" }}
+                                </button>
+                                {notes}
+                                {vocab
+                                    .example
+                                    .map(|example| {
+                                        view! { <div class="vocab-example small"><i>{example}</i></div> }
+                                    })}
+                            </li>
+                        }
+                    })
+                    .collect_view()}
+            </ul>
+            <h4>Grammar</h4>
+            <ul>
+                {translation_info
+                    .grammar_points
+                    .into_iter()
+                    .map(|grammar| {
+                        view! {
+                            <li>
+                                <b>{grammar.name}</b>
+                                -
+                                {grammar.description}
+                                <br />
+                                {grammar.relevant_text}
+                            </li>
+                        }
+                    })
+                    .collect_view()}
+            </ul>
+        }
+    };
 
     view! {
         <p class="translation-output-label">Translation Output:</p>
-        <Show
-            when=move || !translation_pending.get()
-            fallback=|| view! { <TranslationOutputBox message="Translating...".to_string() /> }
-        >
-            <Show
-                when=move || translation_result_option.get().is_some()
-                fallback=|| {
-                    view! {
-                        <TranslationOutputBox message="Translation output goes here".to_string() />
-                    }
+        {move || match translation_state.get() {
+            TranslationState::Idle => {
+                view! { <TranslationOutputBox message="Translation output goes here".to_string() /> }
+                    .into_any()
+            }
+            TranslationState::Waiting => {
+                view! { <TranslationOutputBox message="Translating...".to_string() /> }.into_any()
+            }
+            TranslationState::Streaming(partial_info) => {
+                view! {
+                    {translation_info_view(partial_info)}
+                    <p class="text-body-secondary small mt-1">Translating...</p>
                 }
-            >
-                <Show
-                    when=move || translation_result_option.get().unwrap().is_ok()
-                    fallback=move || {
-                        view! {
-                            <div class="alert alert-danger">
-                                {translation_result_option.get().unwrap().unwrap_err().to_string()}
-                            </div>
-                            <TranslationOutputBox message="Error".to_string() />
-                        }
-                    }
-                >
-                    <TranslationOutputBox message=get_translation_object().translation />
-                    <h4>Vocabulary</h4>
-                    <ul>
-                        {get_translation_object()
-                            .vocabulary
-                            .into_iter()
-                            .map(|vocab| {
-                                let vocab_clone = vocab.clone();
-                                view! {
-                                    <li>
-                                        <b>{vocab.word}</b>
-                                        -
-                                        {vocab.translation}
-                                        <button on:click=move |_| add_saved_word_fn(
-                                            vocab_clone.clone(),
-                                        )>Add word</button>
-                                    </li>
-                                }
-                            })
-                            .collect_view()}
-                    </ul>
-                    <h4>Grammar</h4>
-                    <ul>
-                        {get_translation_object()
-                            .grammar_points
-                            .into_iter()
-                            .map(|grammar| {
-                                view! {
-                                    <li>
-                                        <b>{grammar.name}</b>
-                                        -
-                                        {grammar.description}
-                                        <br />
-                                        {grammar.relevant_text}
-                                    </li>
-                                }
-                            })
-                            .collect_view()}
-                    </ul>
-                </Show>
-            </Show>
-        </Show>
+                    .into_any()
+            }
+            TranslationState::Done(result) => {
+                let usage_note = result.metadata.usage.map(|usage| {
+                    format!(
+                        " · {}{} tokens",
+                        if usage.estimated { "~" } else { "" },
+                        usage.total_tokens()
+                    )
+                });
+                view! {
+                    {translation_info_view(result.info)}
+                    <p class="text-body-secondary small mt-1">
+                        {result.metadata.parse_path.description()} {usage_note}
+                    </p>
+                }
+                    .into_any()
+            }
+            TranslationState::Failed(error) => {
+                // Problems that go away by trying again later are warnings rather than failures
+                let retryable = error.is_retryable();
+                view! {
+                    <div class="alert" class:alert-warning=retryable class:alert-danger=!retryable>
+                        {error.message()}
+                    </div>
+                    <TranslationOutputBox message="Error".to_string() />
+                }
+                    .into_any()
+            }
+        }}
     }
 }
 
 #[component]
-fn SavedWordBox(saved_word_list: ReadSignal<Vec<(usize, VocabularyInfo)>>, set_saved_word_list: WriteSignal<Vec<(usize,VocabularyInfo)>>) -> impl IntoView { 
+fn SavedWordBox(
+    saved_word_list: ReadSignal<Vec<(usize, VocabularyInfo)>>,
+    set_saved_word_list: WriteSignal<Vec<(usize, VocabularyInfo)>>,
+    show_romanization: ReadSignal<bool>,
+) -> impl IntoView {
  let remove_saved_word_fn = move |index: usize| { 
         let mut saved_word_list_value = saved_word_list.get();
         saved_word_list_value.retain(|(id,_)| { id != &index });
@@ -241,9 +633,13 @@ fn SavedWordBox(saved_word_list: ReadSignal<Vec<(usize, VocabularyInfo)>>, set_s
             each=move || saved_word_list.get()
             key=|vocab_entry| vocab_entry.0
             children=move |(id, vocab)| {
+                let details = vocab_details(&vocab);
+                let romanization = vocab_romanization(&vocab);
                 view! {
                     <li>
                         <b>{vocab.word}</b>
+                        {move || romanization_view(show_romanization, romanization.clone())}
+                        {details}
                         -
                         {vocab.translation}
                         <button on:click=move |_| remove_saved_word_fn(id)>Remove</button>
@@ -254,190 +650,39 @@ fn SavedWordBox(saved_word_list: ReadSignal<Vec<(usize, VocabularyInfo)>>, set_s
     }
 }
 
-async fn print_file_content(input: Option<HtmlInputElement>) -> Result<Subtitles, String> {
-    let files = input.ok_or("No input element found")?.files()
-        .ok_or("No files selected")?;
-    let file = files.get(0).ok_or("No file found")?;
-    let text_promise = file.text();
-    
-    let subtitle_text = wasm_bindgen_futures::JsFuture::from(text_promise).await
-        .map_err(|e| e.as_string().unwrap_or("Unknown error reading file".to_string()))
-        .and_then(|text| text.as_string().ok_or("Could not convert file content to string".to_string()))?;
-
-    Subtitles::parse_from_str(subtitle_text)
-        .map_err(|e| e.to_string())
+// Dictionary form, part of speech and conjugation, for words the analyzer recognised
+fn vocab_details(vocab: &VocabularyInfo) -> Option<impl IntoView> {
+    let lemma = vocab.lemma.as_ref().filter(|lemma| **lemma != vocab.word);
+    let details: Vec<&str> = lemma
+        .into_iter()
+        .chain(vocab.part_of_speech.as_ref())
+        .chain(vocab.conjugation.as_ref())
+        .map(String::as_str)
+        .collect();
+    (!details.is_empty()).then(|| {
+        view! { <span class="vocab-details text-body-secondary small ms-1">{format!("({})", details.join(", "))}</span> }
+    })
 }
 
-#[component]
-fn SubtitleFileInput(translate_action: ServerAction<GetTranslateInfo>) -> impl IntoView {
-    let file_input: NodeRef<Input> = NodeRef::new();
-    let (subtitle_content, set_subtitle_content) = signal(Subtitles::new());
-    
-    // Add current subtitle index signal
-    let (current_subtitle_idx, set_current_subtitle_idx) = signal(0usize);
-    
-    // Create computed signal for current subtitle
-    let current_subtitle = move || {
-        subtitle_content.with(|subs| {
-            subs.clone().to_vec()
-                .get(current_subtitle_idx.get())
-                .cloned()
-        })
-    };
-
-    // Navigation functions
-    let move_forward = move |step: usize| {
-        let max_idx = subtitle_content.with(|subs| subs.clone().to_vec().len().saturating_sub(1));
-        let new_idx = (current_subtitle_idx.get() + step).min(max_idx);
-        set_current_subtitle_idx.set(new_idx);
-    };
-
-    let move_backward = move |step: usize| {
-        let new_idx = current_subtitle_idx.get().saturating_sub(step);
-        set_current_subtitle_idx.set(new_idx);
-    };
-
-    // Add a new signal for the error message
-    let (timestamp_error, set_timestamp_error) = signal(Option::<String>::None);
-
-    // Update the jump_to_time function
-    let jump_to_time = move |time_str: String| {
-        // Add milliseconds to make it compatible with srtlib::Timestamp
-        let time_str_with_ms = format!("{},000", time_str);
-        
-        match srtlib::Timestamp::parse(&time_str_with_ms) {
-            Ok(parsed_time) => {
-                set_timestamp_error.set(None);
-                subtitle_content.with(|subs| {
-                    let subtitles = subs.clone().to_vec();
-                    let closest_idx = subtitles.iter()
-                        .enumerate()
-                        .min_by(|(_, a), (_, b)| {
-                            let a_after = a.start_time >= parsed_time;
-                            let b_after = b.start_time >= parsed_time;
-                            
-                            match (a_after, b_after) {
-                                (true, true) | (false, false) => a.start_time.cmp(&b.start_time),
-                                (true, false) => std::cmp::Ordering::Less,
-                                (false, true) => std::cmp::Ordering::Greater,
-                            }
-                        })
-                        .map(|(idx, _)| idx)
-                        .unwrap_or(0);
-                    
-                    set_current_subtitle_idx.set(closest_idx);
-                });
-            },
-            Err(_) => {
-                set_timestamp_error.set(Some("Invalid timestamp format. Use HH:MM:SS".to_string()));
-            }
-        }
-    };
-
-    view! {
-        <h3>File Upload</h3>
-        <input
-            type="file"
-            accept=".srt"
-            node_ref=file_input
-            on:change=move |_| {
-                let file_input_value = file_input.get();
-                spawn_local(async move {
-                    match print_file_content(file_input_value).await {
-                        Ok(subtitle_output) => {
-                            log!("File content: {:?}", subtitle_output);
-                            set_subtitle_content.set(subtitle_output);
-                            // Reset index when new file is loaded
-                            set_current_subtitle_idx.set(0);
-                        },
-                        Err(e) => {
-                            log!("Error reading file: {}", e);
-                        }
-                    }
-                })
-            }
-        />
+// Korean words are romanized locally, words saved before the server did it included
+fn vocab_romanization(vocab: &VocabularyInfo) -> Option<String> {
+    match has_hangul(&vocab.word) {
+        true => Some(romanize(vocab.word.trim())),
+        false => vocab.romanization.clone().filter(|romanization| !romanization.trim().is_empty()),
+    }
+}
 
-        <div class="subtitle-navigation mt-3">
-            <div class="subtitle-text mb-3">
-                // Show placeholder text when no subtitles
-                {move || current_subtitle()
-                    .map(|sub| sub.text)
-                    .unwrap_or_else(|| "Upload a subtitle file to begin".to_string())}
-            </div>
+fn romanization_view(show_romanization: ReadSignal<bool>, romanization: Option<String>) -> Option<impl IntoView> {
+    romanization
+        .filter(|_| show_romanization.get())
+        .map(|romanization| view! { <i class="vocab-romanization ms-1">{romanization}</i> })
+}
 
-            // Add translate button here
-            <button 
-                class="btn btn-success mb-3"
-                on:click=move |_| {
-                    if let Some(subtitle) = current_subtitle() {
-                        translate_action.dispatch(GetTranslateInfo {
-                            input_text: subtitle.text
-                        });
-                    }
-                }
-                // Disable if no subtitles
-                prop:disabled=move || subtitle_content.with(|subs| subs.clone().to_vec().is_empty())
-            >
-                "Translate"
-            </button>
-
-            <div class="subtitle-timing mb-2">
-                <input
-                    type="text"
-                    class="form-control"
-                    class:is-invalid=move || timestamp_error.get().is_some()
-                    style="width: 200px"
-                    prop:value=move || current_subtitle()
-                        .map(|sub| format_timestamp_without_ms(&sub.start_time))
-                        .unwrap_or_default()
-                    on:change=move |ev| {
-                        let time_str = event_target_value(&ev);
-                        jump_to_time(time_str);
-                    }
-                    // Disable if no subtitles
-                    prop:disabled=move || subtitle_content.with(|subs| subs.clone().to_vec().is_empty())
-                />
-                <Show
-                    when=move || timestamp_error.get().is_some()
-                    fallback=|| view! {}
-                >
-                    <div class="invalid-feedback">
-                        {move || timestamp_error.get()}
-                    </div>
-                </Show>
-            </div>
-            <div class="navigation-buttons">
-                <button 
-                    class="btn btn-secondary me-2"
-                    on:click=move |_| move_backward(10)
-                    // Disable if no subtitles
-                    prop:disabled=move || subtitle_content.with(|subs| subs.clone().to_vec().is_empty())
-                >
-                    "⏪ Skip 10"
-                </button>
-                <button 
-                    class="btn btn-primary me-2"
-                    on:click=move |_| move_backward(1)
-                    prop:disabled=move || subtitle_content.with(|subs| subs.clone().to_vec().is_empty())
-                >
-                    "◀ Back"
-                </button>
-                <button 
-                    class="btn btn-primary me-2"
-                    on:click=move |_| move_forward(1)
-                    prop:disabled=move || subtitle_content.with(|subs| subs.clone().to_vec().is_empty())
-                >
-                    "Forward ▶"
-                </button>
-                <button 
-                    class="btn btn-secondary"
-                    on:click=move |_| move_forward(10)
-                    prop:disabled=move || subtitle_content.with(|subs| subs.clone().to_vec().is_empty())
-                >
-                    "Skip 10 ⏩"
-                </button>
-            </div>
-        </div>
-    }
-}
\ No newline at end of file
+// Hanja and register, on a line of their own under the word
+fn vocab_notes(vocab: &VocabularyInfo) -> Option<impl IntoView> {
+    let notes: Vec<String> = [("Hanja", &vocab.hanja), ("Register", &vocab.register)]
+        .into_iter()
+        .filter_map(|(label, value)| value.as_ref().map(|value| format!("{}: {}", label, value)))
+        .collect();
+    (!notes.is_empty()).then(|| view! { <div class="vocab-notes text-body-secondary small">{notes.join(" · ")}</div> })
+}
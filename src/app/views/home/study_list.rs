@@ -0,0 +1,283 @@
+use leptos::{ev::MouseEvent, logging::log, prelude::*, task::spawn_local};
+
+use leptos::server_fn::codec::Json;
+
+use crate::ai_interface::{TranslateError, TranslationContext, VocabularyInfo};
+use crate::languages::LanguagePair;
+use crate::subtitles::{format_time_without_ms, Cue};
+use crate::vocabulary::{VocabularyAnalysis, WordFrequency};
+
+use super::translate_error_message;
+
+use std::collections::HashSet;
+
+// Words in the study list
+#[cfg(feature = "ssr")]
+const STUDY_LIST_LENGTH: usize = 50;
+// Words checked for glossing when the list arrives
+const PRESELECTED_WORDS: usize = 20;
+// Words glossed per model call, well below the input length limit
+const GLOSS_CHUNK_SIZE: usize = 15;
+// Most saved words sent along to be left out of the list
+#[cfg(feature = "ssr")]
+const MAX_SAVED_WORDS: usize = 10_000;
+
+// Ranks the words of the cues by frequency and dispersion, leaving out the saved ones
+#[server(input = Json)]
+pub async fn analyze_episode_vocabulary(
+    cues: Vec<String>,
+    language_pair: LanguagePair,
+    saved_words: Vec<String>,
+) -> Result<VocabularyAnalysis, ServerFnError<TranslateError>> {
+    let app_context = super::app_context()?;
+    // The same limits as pre-translating the file, empty cues are kept so that cue indexes still match
+    let max_cues = app_context.batch_jobs.max_cues();
+    if cues.len() > max_cues {
+        let message = format!("a subtitle file can have at most {} cues, this one has {}", max_cues, cues.len());
+        return Err(TranslateError::Other(message).into());
+    }
+    for cue in cues.iter().filter(|cue| !cue.trim().is_empty()) {
+        app_context.request_limits.check_input(cue)?;
+    }
+    if saved_words.len() > MAX_SAVED_WORDS {
+        let message = format!("at most {} saved words can be left out of the study list", MAX_SAVED_WORDS);
+        return Err(TranslateError::Other(message).into());
+    }
+    for word in &saved_words {
+        app_context.request_limits.check_input(word)?;
+    }
+
+    Ok(crate::vocabulary::analyze_vocabulary(
+        &cues,
+        language_pair.source,
+        &saved_words,
+        STUDY_LIST_LENGTH,
+    ))
+}
+
+// Dictionary entries for the words, from the model. Only the requested words are returned.
+#[server(input = Json)]
+pub async fn gloss_words(
+    words: Vec<String>,
+    language_pair: LanguagePair,
+    context: Option<TranslationContext>,
+) -> Result<Vec<VocabularyInfo>, ServerFnError<TranslateError>> {
+    if words.len() > GLOSS_CHUNK_SIZE {
+        let message = format!("at most {} words can be glossed at once", GLOSS_CHUNK_SIZE);
+        return Err(TranslateError::Other(message).into());
+    }
+    super::check_input(&words.join("\n"), context.as_ref())?;
+    let ai_interface = super::checked_ai_interface(&language_pair)?;
+    let usage = super::app_context()?.usage;
+    let usage_scope = super::usage_scope(context.as_ref()).await;
+
+    Ok(usage
+        .gloss(&ai_interface, &usage_scope, &words, language_pair, context.as_ref())
+        .await?)
+}
+
+// Words to learn before watching the episode, glossed by the model and saved in one go
+#[component]
+pub fn StudyList(
+    cues: Memo<Vec<Cue>>,
+    language_pair: ReadSignal<LanguagePair>,
+    title: ReadSignal<String>,
+    saved_word_list: ReadSignal<Vec<(usize, VocabularyInfo)>>,
+    set_saved_word_list: WriteSignal<Vec<(usize, VocabularyInfo)>>,
+    set_current_idx: WriteSignal<usize>,
+) -> impl IntoView {
+    let (analysis, set_analysis) = signal(Option::<VocabularyAnalysis>::None);
+    let (selected, set_selected) = signal(HashSet::<String>::new());
+    let (status, set_status) = signal(Option::<String>::None);
+    let (busy, set_busy) = signal(false);
+
+    // Saved words drop out of the list as they're added
+    let saved_words = Memo::new(move |_| {
+        saved_word_list.with(|saved| {
+            saved.iter().map(|(_, vocab)| vocab.dictionary_form().to_string()).collect::<HashSet<String>>()
+        })
+    });
+    let study_list = move || {
+        analysis.with(|analysis| {
+            analysis
+                .iter()
+                .flat_map(|analysis| analysis.study_list.iter())
+                .filter(|word| saved_words.with(|saved| !saved.contains(&word.word)))
+                .cloned()
+                .collect::<Vec<WordFrequency>>()
+        })
+    };
+
+    let analyze = move |_: MouseEvent| {
+        let cue_texts = cues.with_untracked(|cues| cues.iter().map(|cue| cue.text.clone()).collect::<Vec<String>>());
+        let saved = saved_words.get_untracked().into_iter().collect::<Vec<String>>();
+        let language_pair = language_pair.get_untracked();
+        set_busy.set(true);
+        set_status.set(None);
+        spawn_local(async move {
+            match analyze_episode_vocabulary(cue_texts, language_pair, saved).await {
+                Ok(result) => {
+                    set_selected.set(
+                        result.study_list.iter().take(PRESELECTED_WORDS).map(|word| word.word.clone()).collect(),
+                    );
+                    set_analysis.set(Some(result));
+                }
+                Err(e) => set_status.set(Some(translate_error_message(&e))),
+            }
+            set_busy.set(false);
+        });
+    };
+
+    let gloss_selected = move |_: MouseEvent| {
+        let words: Vec<String> = study_list()
+            .into_iter()
+            .map(|word| word.word)
+            .filter(|word| selected.with_untracked(|selected| selected.contains(word)))
+            .collect();
+        let language_pair = language_pair.get_untracked();
+        let context = TranslationContext {
+            title: Some(title.get_untracked()).filter(|title| !title.trim().is_empty()),
+            ..Default::default()
+        };
+        set_busy.set(true);
+        spawn_local(async move {
+            let chunks: Vec<Vec<String>> = words.chunks(GLOSS_CHUNK_SIZE).map(<[String]>::to_vec).collect();
+            let mut saved = 0;
+            for (chunk_idx, chunk) in chunks.iter().enumerate() {
+                set_status.set(Some(format!("Glossing {} / {}...", chunk_idx + 1, chunks.len())));
+                match gloss_words(chunk.clone(), language_pair, Some(context.clone())).await {
+                    Ok(glosses) => saved += save_words(set_saved_word_list, saved_words, glosses),
+                    Err(e) => {
+                        let message = translate_error_message(&e);
+                        log!("Error glossing words: {}", message);
+                        set_status.set(Some(message));
+                        set_busy.set(false);
+                        return;
+                    }
+                }
+            }
+            set_status.set(Some(format!("Saved {} words", saved)));
+            set_busy.set(false);
+        });
+    };
+
+    view! {
+        <div class="study-list mt-3">
+            <h5>Study list</h5>
+            <small class="text-body-secondary">
+                "The words used most throughout the episode, without the ones already saved."
+            </small>
+            <div class="mt-2">
+                <button
+                    class="btn btn-outline-primary btn-sm me-2"
+                    on:click=analyze
+                    prop:disabled=move || busy.get() || cues.with(|cues| cues.is_empty())
+                >
+                    "Analyze vocabulary"
+                </button>
+                <Show when=move || analysis.with(Option::is_some)>
+                    <button
+                        class="btn btn-outline-success btn-sm"
+                        on:click=gloss_selected
+                        prop:disabled=move || busy.get() || selected.with(HashSet::is_empty)
+                    >
+                        "Gloss and save selected"
+                    </button>
+                </Show>
+            </div>
+            {move || status.get().map(|status| view! { <div class="small mt-1">{status}</div> })}
+            {move || {
+                analysis
+                    .get()
+                    .map(|analysis| {
+                        view! {
+                            <small class="text-body-secondary">
+                                {format!(
+                                    "{} cues, {} words, {} different",
+                                    analysis.cue_count,
+                                    analysis.token_count,
+                                    analysis.distinct_words,
+                                )}
+                            </small>
+                        }
+                    })
+            }}
+            <Show when=move || analysis.with(Option::is_some)>
+                <table class="table table-sm mt-1">
+                    <thead>
+                        <tr>
+                            <th></th>
+                            <th>Word</th>
+                            <th>Uses</th>
+                            <th>Spread</th>
+                            <th>First use</th>
+                        </tr>
+                    </thead>
+                    <tbody>
+                        <For
+                            each=study_list
+                            key=|word| word.word.clone()
+                            children=move |word| {
+                                let checkbox_word = word.word.clone();
+                                let toggled_word = word.word.clone();
+                                let example_cue = word.example_cue;
+                                let start_ms = cues
+                                    .with_untracked(|cues| cues.get(example_cue).map(|cue| cue.start_ms))
+                                    .unwrap_or_default();
+                                view! {
+                                    <tr>
+                                        <td>
+                                            <input
+                                                class="form-check-input"
+                                                type="checkbox"
+                                                prop:checked=move || selected.with(|selected| selected.contains(&checkbox_word))
+                                                on:change=move |ev| {
+                                                    let checked = event_target_checked(&ev);
+                                                    set_selected.update(|selected| {
+                                                        if checked {
+                                                            selected.insert(toggled_word.clone());
+                                                        } else {
+                                                            selected.remove(&toggled_word);
+                                                        }
+                                                    });
+                                                }
+                                            />
+                                        </td>
+                                        <td><b>{word.word}</b></td>
+                                        <td>{word.count}</td>
+                                        <td>{format!("{:.0}%", word.dispersion * 100.0)}</td>
+                                        <td class="study-list-example" on:click=move |_| set_current_idx.set(example_cue)>
+                                            <span class="text-body-secondary me-2">{format_time_without_ms(start_ms)}</span>
+                                            {word.example}
+                                        </td>
+                                    </tr>
+                                }
+                            }
+                        />
+                    </tbody>
+                </table>
+            </Show>
+        </div>
+    }
+}
+
+// Adds the words that aren't saved yet in any form, returns how many were added
+fn save_words(
+    set_saved_word_list: WriteSignal<Vec<(usize, VocabularyInfo)>>,
+    saved_words: Memo<HashSet<String>>,
+    words: Vec<VocabularyInfo>,
+) -> usize {
+    let mut known = saved_words.get_untracked();
+    let new_words: Vec<VocabularyInfo> = words
+        .into_iter()
+        .filter(|vocab| known.insert(vocab.dictionary_form().to_string()))
+        .collect();
+    let added = new_words.len();
+    set_saved_word_list.update(|saved_word_list| {
+        for vocab in new_words {
+            let next_index = saved_word_list.last().map(|(index, _)| index + 1).unwrap_or(0);
+            saved_word_list.push((next_index, vocab));
+        }
+    });
+    added
+}
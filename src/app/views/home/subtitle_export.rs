@@ -0,0 +1,134 @@
+use leptos::{ev::MouseEvent, prelude::*};
+
+use crate::ai_interface::TranslationResult;
+use crate::subtitles::{AnnotatedCue, Cue, ExportFormat};
+
+use std::collections::HashMap;
+
+// Writes every cue of the file back out, the navigated ones with their pre-translations, to rewatch the episode
+// with them. Signs and songs of the styles that aren't navigated are kept as they are.
+#[component]
+pub fn SubtitleExport(
+    cues: Memo<Vec<Cue>>,
+    // The navigated cues, in file order, which the translations are indexed by
+    translated_cues: Memo<Vec<Cue>>,
+    translations: RwSignal<HashMap<usize, TranslationResult>>,
+    title: ReadSignal<String>,
+) -> impl IntoView {
+    let (format, set_format) = signal(ExportFormat::Srt);
+    let (include_glosses, set_include_glosses) = signal(false);
+    let (export_error, set_export_error) = signal(Option::<String>::None);
+
+    let export = move |_: MouseEvent| {
+        let include_glosses = include_glosses.get_untracked();
+        let annotated_cues = cues.with_untracked(|cues| {
+            translated_cues.with_untracked(|translated_cues| {
+                translations.with_untracked(|translations| {
+                    // Both lists are in file order, so the navigated cues are found by walking them together
+                    let mut next_translated = 0;
+                    cues.iter()
+                        .map(|cue| {
+                            let translated_idx = if translated_cues.get(next_translated) == Some(cue) {
+                                next_translated += 1;
+                                Some(next_translated - 1)
+                            } else {
+                                None
+                            };
+                            let info = translated_idx
+                                .and_then(|idx| translations.get(&idx))
+                                .map(|result| &result.info);
+                            AnnotatedCue {
+                                start_ms: cue.start_ms,
+                                end_ms: cue.end_ms,
+                                text: cue.text.clone(),
+                                translation: info.map(|info| info.translation.clone()),
+                                glosses: info
+                                    .filter(|_| include_glosses)
+                                    .map(|info| {
+                                        info.vocabulary
+                                            .iter()
+                                            .map(|vocab| format!("{}: {}", vocab.word, vocab.translation))
+                                            .collect()
+                                    })
+                                    .unwrap_or_default(),
+                            }
+                        })
+                        .collect::<Vec<AnnotatedCue>>()
+                })
+            })
+        });
+
+        let format = format.get_untracked();
+        let title = title.get_untracked();
+        let stem = if title.trim().is_empty() { "subtitles" } else { title.trim() };
+        let file_name = format!("{}.annotated.{}", stem, format.extension());
+        set_export_error.set(download_file(&file_name, &format.write(&annotated_cues)).err());
+    };
+
+    view! {
+        <div class="subtitle-export mt-3">
+            <h5>Export</h5>
+            <small class="text-body-secondary">
+                "Each line with its translation from \"Translate all\". Cues that aren't translated yet, and those of the other styles, are written without one."
+            </small>
+            <div class="input-group input-group-sm mt-2">
+                <select
+                    class="form-select"
+                    on:change=move |ev| {
+                        if let Some(new_format) = ExportFormat::from_id(&event_target_value(&ev)) {
+                            set_format.set(new_format);
+                        }
+                    }
+                    prop:value=move || format.get().id()
+                >
+                    {ExportFormat::ALL
+                        .into_iter()
+                        .map(|option| view! { <option value=option.id()>{option.label()}</option> })
+                        .collect_view()}
+                </select>
+                <div class="input-group-text">
+                    <label class="form-check-label">
+                        <input
+                            class="form-check-input me-1"
+                            type="checkbox"
+                            prop:checked=include_glosses
+                            on:change=move |ev| set_include_glosses.set(event_target_checked(&ev))
+                        />
+                        "Vocabulary"
+                    </label>
+                </div>
+                <button
+                    class="btn btn-outline-primary"
+                    on:click=export
+                    prop:disabled=move || cues.with(|cues| cues.is_empty())
+                >
+                    "Download"
+                </button>
+            </div>
+            {move || export_error.get().map(|e| view! { <div class="alert alert-danger mt-2">{e}</div> })}
+        </div>
+    }
+}
+
+// Saves the text as a file through a temporary object URL
+fn download_file(file_name: &str, contents: &str) -> Result<(), String> {
+    use wasm_bindgen::JsCast;
+
+    let js_error = |e: wasm_bindgen::JsValue| e.as_string().unwrap_or_else(|| format!("{:?}", e));
+    let parts = js_sys::Array::of1(&wasm_bindgen::JsValue::from_str(contents));
+    let options = web_sys::BlobPropertyBag::new();
+    options.set_type("text/plain;charset=utf-8");
+    let blob = web_sys::Blob::new_with_str_sequence_and_options(&parts, &options).map_err(js_error)?;
+    let url = web_sys::Url::create_object_url_with_blob(&blob).map_err(js_error)?;
+
+    let anchor = document()
+        .create_element("a")
+        .map_err(js_error)?
+        .dyn_into::<web_sys::HtmlAnchorElement>()
+        .map_err(|_| "Could not create a download link".to_string())?;
+    anchor.set_href(&url);
+    anchor.set_download(file_name);
+    anchor.click();
+
+    web_sys::Url::revoke_object_url(&url).map_err(js_error)
+}
@@ -0,0 +1,566 @@
+use leptos::{html::Input, logging::log, prelude::*, task::spawn_local};
+
+use crate::ai_interface::{TranslationContext, VocabularyInfo};
+use crate::batch::BatchCue;
+use crate::languages::{Language, LanguagePair};
+use crate::romanization::romanize;
+use crate::subtitles::{
+    align_cues, closest_cue, cue_styles, default_dialogue_styles, default_track, detect_encoding,
+    format_time_without_ms, parse_subtitle_bytes, parse_time, Cue, SubtitleFormat, SubtitleTrack, TextEncoding,
+};
+
+use web_sys::HtmlInputElement;
+
+use super::study_list::StudyList;
+use super::subtitle_export::SubtitleExport;
+use super::subtitle_search::SubtitleSearch;
+use super::translator::Translator;
+use super::BatchTranslation;
+
+use std::collections::HashSet;
+
+// Number of cues before and after the current one sent along as context
+const CONTEXT_CUES: usize = 2;
+
+// The file's raw bytes, decoded later since older subtitle files are rarely UTF-8
+async fn read_file_bytes(input: Option<HtmlInputElement>) -> Result<(String, Vec<u8>), String> {
+    let files = input.ok_or("No input element found")?.files()
+        .ok_or("No files selected")?;
+    let file = files.get(0).ok_or("No file found")?;
+    let buffer_promise = file.array_buffer();
+
+    let buffer = wasm_bindgen_futures::JsFuture::from(buffer_promise).await
+        .map_err(|e| e.as_string().unwrap_or("Unknown error reading file".to_string()))?;
+
+    Ok((file.name(), js_sys::Uint8Array::new(&buffer).to_vec()))
+}
+
+// The cues of a subtitle file in the given language, in whatever encoding it was detected to be in
+async fn read_reference_cues(input: Option<HtmlInputElement>, language: Language) -> Result<Vec<Cue>, String> {
+    let (file_name, bytes) = read_file_bytes(input).await?;
+    let tracks = parse_subtitle_bytes(&file_name, &bytes, detect_encoding(&bytes)).map_err(|e| e.to_string())?;
+    // SAMI files with both languages have the translation as a track of their own
+    let track_idx = default_track(&tracks, language);
+    Ok(tracks.into_iter().nth(track_idx).map(|track| track.cues).unwrap_or_default())
+}
+
+// The cues around the one at idx, the show title and the official translation, as context for translating it
+fn cue_context(subtitles: &[Cue], references: &[Option<String>], idx: usize, title: &str) -> TranslationContext {
+    let idx = idx.min(subtitles.len());
+    TranslationContext {
+        previous_lines: subtitles[idx.saturating_sub(CONTEXT_CUES)..idx]
+            .iter()
+            .map(|sub| sub.text.clone())
+            .collect(),
+        next_lines: subtitles
+            .iter()
+            .skip(idx + 1)
+            .take(CONTEXT_CUES)
+            .map(|sub| sub.text.clone())
+            .collect(),
+        title: Some(title.to_string()).filter(|title| !title.trim().is_empty()),
+        reference_translation: references.get(idx).cloned().flatten(),
+    }
+}
+
+#[component]
+pub fn SubtitleFileInput(
+    translator: Translator,
+    language_pair: ReadSignal<LanguagePair>,
+    saved_word_list: ReadSignal<Vec<(usize, VocabularyInfo)>>,
+    set_saved_word_list: WriteSignal<Vec<(usize, VocabularyInfo)>>,
+    show_romanization: ReadSignal<bool>,
+) -> impl IntoView {
+    let file_input: NodeRef<Input> = NodeRef::new();
+    let (tracks, set_tracks) = signal(Vec::<SubtitleTrack>::new());
+    // SAMI files have a track per language
+    let (selected_track, set_selected_track) = signal(0usize);
+    let file_cues = Memo::new(move |_| {
+        tracks.with(|tracks| tracks.get(selected_track.get()).map(|track| track.cues.clone()).unwrap_or_default())
+    });
+
+    // ASS/SSA styles whose events are navigated, the rest are signs, songs and the like
+    let (dialogue_styles, set_dialogue_styles) = signal(HashSet::<String>::new());
+    let styles = Memo::new(move |_| file_cues.with(|cues| cue_styles(cues)));
+
+    // The cues that are navigated and translated
+    let subtitle_content = Memo::new(move |_| {
+        dialogue_styles.with(|dialogue_styles| {
+            file_cues.with(|cues| {
+                cues.iter()
+                    .filter(|cue| cue.style.as_ref().map(|style| dialogue_styles.contains(style)).unwrap_or(true))
+                    .cloned()
+                    .collect::<Vec<Cue>>()
+            })
+        })
+    });
+    
+    // Cues of a second subtitle file with the official translation, lined up with the navigated cues by time
+    let (reference_cues, set_reference_cues) = signal(Vec::<Cue>::new());
+    let references = Memo::new(move |_| {
+        reference_cues.with(|reference_cues| subtitle_content.with(|cues| align_cues(cues, reference_cues)))
+    });
+    let reference_input: NodeRef<Input> = NodeRef::new();
+
+    // Add current subtitle index signal
+    let (current_subtitle_idx, set_current_subtitle_idx) = signal(0usize);
+
+    // Show or episode title given to the model as context, defaults to the file name
+    let (episode_title, set_episode_title) = signal(String::new());
+    
+    // Create computed signal for current subtitle
+    let current_subtitle = move || {
+        subtitle_content.with(|subs| subs.get(current_subtitle_idx.get()).cloned())
+    };
+
+    // Surrounding cues of the current subtitle
+    let current_context = move || {
+        subtitle_content.with(|subs| {
+            references.with(|references| {
+                cue_context(subs, references, current_subtitle_idx.get(), &episode_title.get())
+            })
+        })
+    };
+
+    let batch = BatchTranslation::new();
+
+    // Every cue with the same context a single translation would get, so the two share cache entries
+    let batch_cues = move || {
+        subtitle_content.with(|subs| {
+            references.with(|references| {
+                let title = episode_title.get_untracked();
+                subs.iter()
+                    .enumerate()
+                    .map(|(index, sub)| BatchCue {
+                        index,
+                        text: sub.text.clone(),
+                        context: Some(cue_context(subs, references, index, &title)),
+                    })
+                    .collect::<Vec<BatchCue>>()
+            })
+        })
+    };
+
+    // Show pre-translated cues as soon as they're stepped to, or as soon as the current cue's translation arrives
+    let shown_batch_idx = StoredValue::new(Option::<usize>::None);
+    Effect::new(move |_| {
+        let idx = current_subtitle_idx.get();
+        match batch.results.with(|results| results.get(&idx).cloned()) {
+            Some(result) if shown_batch_idx.get_value() != Some(idx) => {
+                shown_batch_idx.set_value(Some(idx));
+                translator.show(result);
+            }
+            Some(_) => {}
+            None => shown_batch_idx.set_value(None),
+        }
+    });
+
+    // Navigation functions
+    let move_forward = move |step: usize| {
+        let max_idx = subtitle_content.with(|subs| subs.len().saturating_sub(1));
+        let new_idx = (current_subtitle_idx.get() + step).min(max_idx);
+        set_current_subtitle_idx.set(new_idx);
+    };
+
+    let move_backward = move |step: usize| {
+        let new_idx = current_subtitle_idx.get().saturating_sub(step);
+        set_current_subtitle_idx.set(new_idx);
+    };
+
+    // Add a new signal for the error message
+    let (timestamp_error, set_timestamp_error) = signal(Option::<String>::None);
+
+    // Update the jump_to_time function
+    let jump_to_time = move |time_str: String| {
+        match parse_time(&time_str) {
+            Some(time_ms) => {
+                set_timestamp_error.set(None);
+                let closest_idx = subtitle_content.with(|subs| closest_cue(subs, time_ms));
+                set_current_subtitle_idx.set(closest_idx);
+            },
+            None => {
+                set_timestamp_error.set(Some("Invalid timestamp format. Use HH:MM:SS".to_string()));
+            }
+        }
+    };
+
+    // The uploaded file is kept so it can be decoded again when the detected encoding is wrong
+    let file_bytes = StoredValue::new(Option::<(String, Vec<u8>)>::None);
+    let (detected_encoding, set_detected_encoding) = signal(Option::<TextEncoding>::None);
+    let (selected_encoding, set_selected_encoding) = signal(TextEncoding::Utf8);
+
+    let load_file = move |encoding: TextEncoding| {
+        let result = file_bytes.with_value(|file| {
+            file.as_ref().map(|(file_name, bytes)| parse_subtitle_bytes(file_name, bytes, encoding))
+        });
+        match result {
+            Some(Ok(subtitle_output)) => {
+                log!("File content: {:?}", subtitle_output);
+                let track_idx = default_track(&subtitle_output, language_pair.get_untracked().source);
+                let styles = subtitle_output
+                    .get(track_idx)
+                    .map(|track| cue_styles(&track.cues))
+                    .unwrap_or_default();
+                set_dialogue_styles.set(default_dialogue_styles(&styles));
+                set_selected_track.set(track_idx);
+                set_tracks.set(subtitle_output);
+                // Reset index when new file is loaded
+                set_current_subtitle_idx.set(0);
+                // Pre-translations belong to the previous file
+                batch.cancel();
+                batch.reset();
+            }
+            Some(Err(e)) => {
+                log!("Error reading file: {}", e);
+            }
+            None => {}
+        }
+    };
+
+    view! {
+        <h3>File Upload</h3>
+        <input
+            type="file"
+            accept=SubtitleFormat::ACCEPT
+            node_ref=file_input
+            on:change=move |_| {
+                let file_input_value = file_input.get();
+                let file_name = file_input_value
+                    .as_ref()
+                    .and_then(|input| input.files())
+                    .and_then(|files| files.get(0))
+                    .map(|file| file.name());
+                if let Some(file_name) = file_name {
+                    let title = file_name
+                        .rsplit_once('.')
+                        .map(|(stem, _)| stem.to_string())
+                        .unwrap_or(file_name);
+                    set_episode_title.set(title);
+                }
+                spawn_local(async move {
+                    match read_file_bytes(file_input_value).await {
+                        Ok((file_name, bytes)) => {
+                            let encoding = detect_encoding(&bytes);
+                            set_detected_encoding.set(Some(encoding));
+                            set_selected_encoding.set(encoding);
+                            file_bytes.set_value(Some((file_name, bytes)));
+                            load_file(encoding);
+                        },
+                        Err(e) => {
+                            log!("Error reading file: {}", e);
+                        }
+                    }
+                })
+            }
+        />
+        <Show when=move || detected_encoding.get().is_some()>
+            <div class="input-group input-group-sm mt-2 subtitle-encoding">
+                <span class="input-group-text">"Encoding"</span>
+                <select
+                    class="form-select"
+                    on:change=move |ev| {
+                        if let Some(new_encoding) = TextEncoding::from_id(&event_target_value(&ev)) {
+                            set_selected_encoding.set(new_encoding);
+                            load_file(new_encoding);
+                        }
+                    }
+                    prop:value=move || selected_encoding.get().id()
+                >
+                    {TextEncoding::ALL
+                        .into_iter()
+                        .map(|option| {
+                            let label = move || match detected_encoding.get() {
+                                Some(detected) if detected == option => format!("{} (detected)", option.label()),
+                                _ => option.label().to_string(),
+                            };
+                            view! { <option value=option.id()>{label}</option> }
+                        })
+                        .collect_view()}
+                </select>
+            </div>
+        </Show>
+        <input
+            type="text"
+            class="form-control mt-2"
+            placeholder="Show / episode title"
+            prop:value=episode_title
+            on:input=move |ev| set_episode_title.set(event_target_value(&ev))
+        />
+        <div class="subtitle-reference-upload mt-2">
+            <small class="text-body-secondary">"Official translation (optional)"</small>
+            <div class="input-group input-group-sm">
+                <input
+                    type="file"
+                    class="form-control"
+                    accept=SubtitleFormat::ACCEPT
+                    node_ref=reference_input
+                    on:change=move |_| {
+                        let reference_input_value = reference_input.get();
+                        let language = language_pair.get_untracked().explanation;
+                        spawn_local(async move {
+                            match read_reference_cues(reference_input_value, language).await {
+                                Ok(cues) => {
+                                    set_reference_cues.set(cues);
+                                    // Pre-translations were made without the reference
+                                    batch.cancel();
+                                    batch.reset();
+                                }
+                                Err(e) => {
+                                    log!("Error reading file: {}", e);
+                                }
+                            }
+                        })
+                    }
+                />
+                <Show when=move || !reference_cues.with(Vec::is_empty)>
+                    <button
+                        class="btn btn-outline-secondary"
+                        on:click=move |_| {
+                            set_reference_cues.set(Vec::new());
+                            if let Some(input) = reference_input.get() {
+                                input.set_value("");
+                            }
+                            batch.cancel();
+                            batch.reset();
+                        }
+                    >
+                        "Remove"
+                    </button>
+                </Show>
+            </div>
+        </div>
+        <Show when=move || tracks.with(|tracks| tracks.len() > 1)>
+            <select
+                class="form-select mt-2 subtitle-track"
+                on:change=move |ev| {
+                    if let Ok(track_idx) = event_target_value(&ev).parse::<usize>() {
+                        set_selected_track.set(track_idx);
+                        set_current_subtitle_idx.set(0);
+                        batch.cancel();
+                        batch.reset();
+                    }
+                }
+                prop:value=move || selected_track.get().to_string()
+            >
+                {move || {
+                    tracks
+                        .get()
+                        .into_iter()
+                        .enumerate()
+                        .map(|(idx, track)| {
+                            let label = match &track.language {
+                                Some(language) => format!("{} ({})", track.name, language),
+                                None => track.name.clone(),
+                            };
+                            view! { <option value=idx.to_string()>{label}</option> }
+                        })
+                        .collect_view()
+                }}
+            </select>
+        </Show>
+        <Show when=move || !styles.with(Vec::is_empty)>
+            <div class="subtitle-styles mt-2">
+                <small class="text-body-secondary">"Dialogue styles"</small>
+                <For
+                    each=move || styles.get()
+                    key=|style| style.clone()
+                    children=move |style| {
+                        let checkbox_style = style.clone();
+                        let label = style.clone();
+                        view! {
+                            <div class="form-check form-check-inline">
+                                <label class="form-check-label">
+                                    <input
+                                        class="form-check-input"
+                                        type="checkbox"
+                                        prop:checked=move || dialogue_styles.with(|styles| styles.contains(&checkbox_style))
+                                        on:change=move |ev| {
+                                            let checked = event_target_checked(&ev);
+                                            set_dialogue_styles.update(|styles| {
+                                                if checked {
+                                                    styles.insert(style.clone());
+                                                } else {
+                                                    styles.remove(&style);
+                                                }
+                                            });
+                                            // Indexes of the old cue list no longer apply
+                                            set_current_subtitle_idx.set(0);
+                                            batch.cancel();
+                                            batch.reset();
+                                        }
+                                    />
+                                    {label}
+                                </label>
+                            </div>
+                        }
+                    }
+                />
+            </div>
+        </Show>
+
+        <div class="subtitle-navigation mt-3">
+            <div class="subtitle-text mb-3">
+                // Show placeholder text when no subtitles
+                {move || current_subtitle()
+                    .map(|sub| sub.text)
+                    .unwrap_or_else(|| "Upload a subtitle file to begin".to_string())}
+            </div>
+            {move || {
+                current_subtitle()
+                    .filter(|_| show_romanization.get() && language_pair.get().source == Language::Korean)
+                    .map(|sub| {
+                        view! { <div class="subtitle-romanization text-body-secondary mb-3">{romanize(&sub.text)}</div> }
+                    })
+            }}
+            {move || {
+                references
+                    .with(|references| references.get(current_subtitle_idx.get()).cloned().flatten())
+                    .map(|reference| {
+                        view! { <div class="subtitle-reference text-body-secondary mb-3">{reference}</div> }
+                    })
+            }}
+
+            // Add translate button here
+            <button 
+                class="btn btn-success mb-3"
+                on:click=move |_| {
+                    let pretranslated = batch.results.with(|results| results.get(&current_subtitle_idx.get()).cloned());
+                    match (pretranslated, current_subtitle()) {
+                        (Some(result), _) => translator.show(result),
+                        (None, Some(subtitle)) => {
+                            translator.translate(subtitle.text, language_pair.get(), Some(current_context()))
+                        }
+                        (None, None) => {}
+                    }
+                }
+                // Disable if no subtitles
+                prop:disabled=move || subtitle_content.with(|subs| subs.is_empty())
+            >
+                "Translate"
+            </button>
+
+            <div class="batch-translation mb-3">
+                <button
+                    class="btn btn-outline-success me-2"
+                    on:click=move |_| batch.start(batch_cues(), language_pair.get())
+                    prop:disabled=move || {
+                        batch.is_running() || subtitle_content.with(|subs| subs.is_empty())
+                    }
+                >
+                    "Translate all"
+                </button>
+                <Show when=move || batch.is_running()>
+                    <button class="btn btn-outline-secondary" on:click=move |_| batch.cancel()>
+                        "Cancel"
+                    </button>
+                </Show>
+                {move || {
+                    batch
+                        .progress
+                        .get()
+                        .map(|progress| {
+                            let percent = (progress.completed * 100).checked_div(progress.total).unwrap_or(100);
+                            view! {
+                                <div class="progress mt-2" role="progressbar">
+                                    <div class="progress-bar" style:width=format!("{}%", percent)>
+                                        {format!("{} / {}", progress.completed, progress.total)}
+                                    </div>
+                                </div>
+                                <small class="text-body-secondary">
+                                    {match (progress.finished, progress.cancelled) {
+                                        (true, true) => "Cancelled".to_string(),
+                                        (true, false) => "Done".to_string(),
+                                        (false, true) => "Cancelling...".to_string(),
+                                        (false, false) => "Pre-translating...".to_string(),
+                                    }}
+                                    {(progress.failed > 0)
+                                        .then(|| format!(", {} cues failed", progress.failed))}
+                                </small>
+                            }
+                        })
+                }}
+                {move || {
+                    batch
+                        .error
+                        .get()
+                        .map(|error| view! { <div class="alert alert-warning mt-2">{error.message()}</div> })
+                }}
+            </div>
+
+            <SubtitleSearch
+                cues=subtitle_content
+                current_idx=current_subtitle_idx
+                set_current_idx=set_current_subtitle_idx
+            />
+
+            <div class="subtitle-timing mb-2">
+                <input
+                    type="text"
+                    class="form-control"
+                    class:is-invalid=move || timestamp_error.get().is_some()
+                    style="width: 200px"
+                    prop:value=move || current_subtitle()
+                        .map(|sub| format_time_without_ms(sub.start_ms))
+                        .unwrap_or_default()
+                    on:change=move |ev| {
+                        let time_str = event_target_value(&ev);
+                        jump_to_time(time_str);
+                    }
+                    // Disable if no subtitles
+                    prop:disabled=move || subtitle_content.with(|subs| subs.is_empty())
+                />
+                <Show when=move || timestamp_error.get().is_some()>
+                    <div class="invalid-feedback">
+                        {move || timestamp_error.get()}
+                    </div>
+                </Show>
+            </div>
+            <div class="navigation-buttons">
+                <button 
+                    class="btn btn-secondary me-2"
+                    on:click=move |_| move_backward(10)
+                    // Disable if no subtitles
+                    prop:disabled=move || subtitle_content.with(|subs| subs.is_empty())
+                >
+                    "⏪ Skip 10"
+                </button>
+                <button 
+                    class="btn btn-primary me-2"
+                    on:click=move |_| move_backward(1)
+                    prop:disabled=move || subtitle_content.with(|subs| subs.is_empty())
+                >
+                    "◀ Back"
+                </button>
+                <button 
+                    class="btn btn-primary me-2"
+                    on:click=move |_| move_forward(1)
+                    prop:disabled=move || subtitle_content.with(|subs| subs.is_empty())
+                >
+                    "Forward ▶"
+                </button>
+                <button 
+                    class="btn btn-secondary"
+                    on:click=move |_| move_forward(10)
+                    prop:disabled=move || subtitle_content.with(|subs| subs.is_empty())
+                >
+                    "Skip 10 ⏩"
+                </button>
+            </div>
+        </div>
+
+        <SubtitleExport
+            cues=file_cues
+            translated_cues=subtitle_content
+            translations=batch.results
+            title=episode_title
+        />
+        <StudyList
+            cues=subtitle_content
+            language_pair=language_pair
+            title=episode_title
+            saved_word_list=saved_word_list
+            set_saved_word_list=set_saved_word_list
+            set_current_idx=set_current_subtitle_idx
+        />
+    }
+}
@@ -0,0 +1,135 @@
+use leptos::prelude::*;
+
+use crate::subtitles::{format_time_without_ms, search_cues, Cue, SearchMode};
+
+// Finds cues by their text and steps through the matches
+#[component]
+pub fn SubtitleSearch(
+    cues: Memo<Vec<Cue>>,
+    current_idx: ReadSignal<usize>,
+    set_current_idx: WriteSignal<usize>,
+) -> impl IntoView {
+    // Matches listed below the search box, the navigation buttons go through all of them
+    const LISTED_MATCHES: usize = 50;
+
+    let (query, set_query) = signal(String::new());
+    let (mode, set_mode) = signal(SearchMode::Text);
+
+    let search_result = Memo::new(move |_| {
+        cues.with(|cues| query.with(|query| search_cues(cues, query, mode.get()).map_err(|e| e.to_string())))
+    });
+    let matches = move || search_result.with(|result| result.as_ref().cloned().unwrap_or_default());
+
+    // The first match after the current cue, or before it, wrapping around at the end of the file
+    let next_match = move || {
+        let current = current_idx.get();
+        let matches = matches();
+        matches.iter().find(|idx| **idx > current).or(matches.first()).copied()
+    };
+    let previous_match = move || {
+        let current = current_idx.get();
+        let matches = matches();
+        matches.iter().rev().find(|idx| **idx < current).or(matches.last()).copied()
+    };
+
+    view! {
+        <div class="subtitle-search mb-3">
+            <div class="input-group">
+                <input
+                    type="search"
+                    class="form-control"
+                    placeholder="Search subtitles"
+                    prop:value=query
+                    on:input=move |ev| set_query.set(event_target_value(&ev))
+                    on:keydown=move |ev| {
+                        if ev.key() == "Enter" {
+                            if let Some(idx) = next_match() {
+                                set_current_idx.set(idx);
+                            }
+                        }
+                    }
+                    prop:disabled=move || cues.with(|cues| cues.is_empty())
+                />
+                <select
+                    class="form-select flex-grow-0 w-auto"
+                    on:change=move |ev| {
+                        if let Some(new_mode) = SearchMode::from_id(&event_target_value(&ev)) {
+                            set_mode.set(new_mode);
+                        }
+                    }
+                    prop:value=move || mode.get().id()
+                >
+                    {SearchMode::ALL
+                        .into_iter()
+                        .map(|option| view! { <option value=option.id()>{option.label()}</option> })
+                        .collect_view()}
+                </select>
+                <button
+                    class="btn btn-outline-secondary"
+                    on:click=move |_| {
+                        if let Some(idx) = previous_match() {
+                            set_current_idx.set(idx);
+                        }
+                    }
+                    prop:disabled=move || matches().is_empty()
+                >
+                    "◀ Previous match"
+                </button>
+                <button
+                    class="btn btn-outline-secondary"
+                    on:click=move |_| {
+                        if let Some(idx) = next_match() {
+                            set_current_idx.set(idx);
+                        }
+                    }
+                    prop:disabled=move || matches().is_empty()
+                >
+                    "Next match ▶"
+                </button>
+            </div>
+            {move || {
+                if query.with(|query| query.trim().is_empty()) {
+                    return None;
+                }
+                Some(match search_result.get() {
+                    Err(e) => view! { <div class="text-danger small mt-1">{e}</div> }.into_any(),
+                    Ok(matches) => {
+                        let total = matches.len();
+                        view! {
+                            <small class="text-body-secondary">
+                                {match total {
+                                    0 => "No matches".to_string(),
+                                    1 => "1 match".to_string(),
+                                    total if total > LISTED_MATCHES => {
+                                        format!("{} matches, showing the first {}", total, LISTED_MATCHES)
+                                    }
+                                    total => format!("{} matches", total),
+                                }}
+                            </small>
+                            <ul class="list-group search-matches mt-1">
+                                {matches
+                                    .into_iter()
+                                    .take(LISTED_MATCHES)
+                                    .map(|idx| {
+                                        let cue = cues.with_untracked(|cues| cues.get(idx).cloned()).unwrap_or_default();
+                                        view! {
+                                            <li
+                                                class="list-group-item list-group-item-action"
+                                                class:active=move || current_idx.get() == idx
+                                                on:click=move |_| set_current_idx.set(idx)
+                                            >
+                                                <span class="text-body-secondary me-2">{format_time_without_ms(cue.start_ms)}</span>
+                                                {cue.text}
+                                            </li>
+                                        }
+                                    })
+                                    .collect_view()}
+                            </ul>
+                        }
+                            .into_any()
+                    }
+                })
+            }}
+        </div>
+    }
+}
@@ -0,0 +1,113 @@
+use leptos::{logging::log, prelude::*, task::spawn_local};
+
+use crate::ai_interface::parsing::parse_partial_translation;
+use crate::ai_interface::{
+    SubtitleTranslationInfo, TranslateError, TranslationContext, TranslationResult, TranslationStreamEvent,
+};
+use crate::languages::LanguagePair;
+
+// What the translation box shows
+#[derive(Clone, Debug, Default)]
+pub enum TranslationState {
+    #[default]
+    Idle,
+    // Requested, nothing to show yet
+    Waiting,
+    // Built from the part of the answer received so far
+    Streaming(SubtitleTranslationInfo),
+    Done(TranslationResult),
+    Failed(TranslateError),
+}
+
+// Streams translations into a shared TranslationState, used by both the text input and the subtitle file input
+#[derive(Clone, Copy)]
+pub struct Translator {
+    pub state: RwSignal<TranslationState>,
+    // Incremented for each translation so that a translation still streaming can't overwrite a newer one
+    generation: StoredValue<u64>,
+}
+
+impl Translator {
+    pub fn new() -> Self {
+        Self {
+            state: RwSignal::new(TranslationState::Idle),
+            generation: StoredValue::new(0),
+        }
+    }
+
+    // Shows a translation that is already known, e.g. a pre-translated cue
+    pub fn show(&self, result: TranslationResult) {
+        self.generation.update_value(|generation| *generation += 1);
+        self.state.set(TranslationState::Done(result));
+    }
+
+    pub fn translate(&self, input_text: String, language_pair: LanguagePair, context: Option<TranslationContext>) {
+        use futures::StreamExt;
+
+        let generation = self.generation.get_value() + 1;
+        self.generation.set_value(generation);
+
+        let translator = *self;
+        let set_state = move |state: TranslationState| {
+            if translator.generation.get_value() == generation {
+                translator.state.set(state);
+            }
+        };
+        set_state(TranslationState::Waiting);
+
+        spawn_local(async move {
+            let mut events = match super::stream_translate_info(input_text, language_pair, context).await {
+                Ok(text_stream) => text_stream.into_inner(),
+                // The rate limiter's errors arrive as a Failed event
+                Err(ServerFnError::ServerError(message)) => {
+                    let error = match serde_json::from_str::<TranslationStreamEvent>(message.trim()) {
+                        Ok(TranslationStreamEvent::Failed(e)) => e,
+                        _ => TranslateError::Other(message),
+                    };
+                    return set_state(TranslationState::Failed(error));
+                }
+                Err(e) => return set_state(TranslationState::Failed(TranslateError::Other(e.to_string()))),
+            };
+
+            // Chunks don't line up with events, so lines are buffered until their newline arrives
+            let mut buffer = String::new();
+            let mut response_text = String::new();
+            let mut finished = false;
+            while let Some(chunk) = events.next().await {
+                match chunk {
+                    Ok(chunk) => buffer.push_str(&chunk),
+                    Err(e) => return set_state(TranslationState::Failed(TranslateError::Other(e.to_string()))),
+                }
+
+                while let Some(newline) = buffer.find('\n') {
+                    let line: String = buffer.drain(..=newline).collect();
+                    match serde_json::from_str::<TranslationStreamEvent>(line.trim()) {
+                        Ok(TranslationStreamEvent::Token(token)) => {
+                            response_text.push_str(&token);
+                            if let Some(partial) = parse_partial_translation(&response_text)
+                                .filter(|partial| !partial.translation.is_empty())
+                            {
+                                set_state(TranslationState::Streaming(partial));
+                            }
+                        }
+                        Ok(TranslationStreamEvent::Done(result)) => {
+                            finished = true;
+                            set_state(TranslationState::Done(result));
+                        }
+                        Ok(TranslationStreamEvent::Failed(e)) => {
+                            finished = true;
+                            set_state(TranslationState::Failed(e));
+                        }
+                        Err(e) => log!("Error reading translation event: {:?}", e),
+                    }
+                }
+            }
+
+            if !finished {
+                set_state(TranslationState::Failed(TranslateError::Other(
+                    "the connection closed before the translation finished".to_string(),
+                )));
+            }
+        });
+    }
+}
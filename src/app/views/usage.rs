@@ -0,0 +1,113 @@
+use leptos::prelude::*;
+
+use crate::usage::{UsageReport, UsageTotals};
+
+#[server]
+pub async fn get_usage_report() -> Result<UsageReport, ServerFnError> {
+    use crate::context::DramaStudyToolAppContext;
+    let app_context = match use_context::<DramaStudyToolAppContext>() {
+        Some(context) => context,
+        None => return Err(ServerFnError::ServerError("Couldn't get app context".to_string())),
+    };
+
+    let session_id = crate::session::current_session_id().await;
+    Ok(app_context.usage.report(&session_id))
+}
+
+#[component]
+pub fn Usage() -> impl IntoView {
+    let report = Resource::new(|| (), |_| get_usage_report());
+
+    view! {
+        <h1>Usage</h1>
+        <p>
+            "Tokens used by translations and what they cost. Cached translations are free and not counted. Usage is kept in memory and starts over when the server restarts."
+        </p>
+        <Suspense fallback=|| view! { <p>"Loading..."</p> }>
+            {move || {
+                report
+                    .get()
+                    .map(|result| match result {
+                        Ok(report) => view! { <UsageReportView report=report /> }.into_any(),
+                        Err(e) => view! { <div class="alert alert-danger">{e.to_string()}</div> }.into_any(),
+                    })
+            }}
+        </Suspense>
+    }
+}
+
+#[component]
+fn UsageReportView(report: UsageReport) -> impl IntoView {
+    let session_rows = vec![
+        (format!("Today ({})", report.today), report.session_today.clone()),
+        ("All time".to_string(), report.session_total.clone()),
+    ];
+    let spent_today = report
+        .days
+        .iter()
+        .find(|(day, _)| *day == report.today)
+        .map(|(_, totals)| totals.cost_usd)
+        .unwrap_or_default();
+
+    view! {
+        <h3>This browser</h3>
+        <SpendCap cap=report.session_daily_spend_cap_usd spent=report.session_today.cost_usd />
+        <UsageTable label="Period" rows=session_rows />
+
+        <h3>By subtitle file</h3>
+        {if report.session_files.is_empty() {
+            view! { <p>"No subtitle files translated yet."</p> }.into_any()
+        } else {
+            view! { <UsageTable label="File" rows=report.session_files /> }.into_any()
+        }}
+
+        <h3>Everyone, by day</h3>
+        <SpendCap cap=report.daily_spend_cap_usd spent=spent_today />
+        <UsageTable label="Day (UTC)" rows=report.days />
+    }
+}
+
+#[component]
+fn SpendCap(cap: Option<f64>, spent: f64) -> impl IntoView {
+    cap.map(|cap| {
+        let alert_class = if spent >= cap { "alert alert-warning" } else { "alert alert-secondary" };
+        view! {
+            <div class=alert_class>
+                {format!("Daily spend cap: ${:.4} of ${:.2} used", spent, cap)}
+            </div>
+        }
+    })
+}
+
+#[component]
+fn UsageTable(label: &'static str, rows: Vec<(String, UsageTotals)>) -> impl IntoView {
+    view! {
+        <table class="table">
+            <thead>
+                <tr>
+                    <th>{label}</th>
+                    <th>Translations</th>
+                    <th>Prompt tokens</th>
+                    <th>Completion tokens</th>
+                    <th>Cost</th>
+                </tr>
+            </thead>
+            <tbody>
+                {rows
+                    .into_iter()
+                    .map(|(name, totals)| {
+                        view! {
+                            <tr>
+                                <td>{name}</td>
+                                <td>{totals.translations}</td>
+                                <td>{totals.prompt_tokens}</td>
+                                <td>{totals.completion_tokens}</td>
+                                <td>{format!("${:.4}", totals.cost_usd)}</td>
+                            </tr>
+                        }
+                    })
+                    .collect_view()}
+            </tbody>
+        </table>
+    }
+}
@@ -0,0 +1,306 @@
+use serde::{Deserialize, Serialize};
+
+use crate::ai_interface::{TranslateError, TranslationContext, TranslationResult};
+
+#[cfg(feature = "ssr")]
+use crate::ai_interface::AIInterface;
+#[cfg(feature = "ssr")]
+use crate::languages::LanguagePair;
+#[cfg(feature = "ssr")]
+use crate::usage::{UsageScope, UsageTracker};
+#[cfg(feature = "ssr")]
+use std::collections::HashMap;
+#[cfg(feature = "ssr")]
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(feature = "ssr")]
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "ssr")]
+use std::time::{Duration, Instant};
+
+// A subtitle cue to pre-translate, index is its position in the file
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct BatchCue {
+    pub index: usize,
+    pub text: String,
+    #[serde(default)]
+    pub context: Option<TranslationContext>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct BatchCueResult {
+    pub index: usize,
+    pub result: Result<TranslationResult, TranslateError>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct BatchProgress {
+    pub total: usize,
+    pub completed: usize,
+    pub failed: usize,
+    pub finished: bool,
+    pub cancelled: bool,
+    // Set when the job was stopped because no cue could be translated, e.g. on an auth failure
+    pub error: Option<TranslateError>,
+    // Cues finished since the `since` the progress was asked for, in the order they finished
+    pub results: Vec<BatchCueResult>,
+    // Pass as `since` next time to only get newer results
+    pub next: usize,
+}
+
+// Finished jobs are kept this long for the page to collect their results
+#[cfg(feature = "ssr")]
+const FINISHED_JOB_RETENTION: Duration = Duration::from_secs(60 * 60);
+
+// How often a cue waits out a rate limit or an open circuit breaker before it is given up on
+#[cfg(feature = "ssr")]
+const MAX_RATE_LIMIT_WAITS: usize = 3;
+
+#[cfg(feature = "ssr")]
+#[derive(Debug)]
+pub struct BatchJobs {
+    jobs: Mutex<HashMap<String, Arc<BatchJob>>>,
+    concurrency: usize,
+    max_cues: usize,
+}
+
+#[cfg(feature = "ssr")]
+#[derive(Debug)]
+struct BatchJob {
+    // Only the session that started the job can see and cancel it
+    session_id: String,
+    total: usize,
+    cancelled: AtomicBool,
+    state: Mutex<BatchJobState>,
+}
+
+#[cfg(feature = "ssr")]
+#[derive(Debug, Default)]
+struct BatchJobState {
+    results: Vec<BatchCueResult>,
+    failed: usize,
+    error: Option<TranslateError>,
+    finished_at: Option<Instant>,
+}
+
+#[cfg(feature = "ssr")]
+impl BatchJobs {
+    pub fn new(concurrency: usize, max_cues: usize) -> Self {
+        Self {
+            jobs: Mutex::new(HashMap::new()),
+            concurrency: concurrency.max(1),
+            max_cues,
+        }
+    }
+
+    // Largest subtitle file that can be pre-translated
+    pub fn max_cues(&self) -> usize {
+        self.max_cues
+    }
+
+    // Starts translating the cues in the background and returns the job id to poll.
+    // Usage is billed to the session that started the job.
+    pub fn start(
+        &self,
+        ai_interface: AIInterface,
+        usage: Arc<UsageTracker>,
+        session_id: String,
+        cues: Vec<BatchCue>,
+        language_pair: LanguagePair,
+    ) -> Result<String, TranslateError> {
+        if cues.len() > self.max_cues {
+            return Err(TranslateError::Other(format!(
+                "a subtitle file can have at most {} cues pre-translated, this one has {}",
+                self.max_cues,
+                cues.len()
+            )));
+        }
+
+        let job_id = new_job_id();
+        let job = Arc::new(BatchJob {
+            session_id: session_id.clone(),
+            total: cues.len(),
+            cancelled: AtomicBool::new(false),
+            state: Mutex::new(BatchJobState::default()),
+        });
+
+        {
+            let mut jobs = self.jobs.lock().unwrap_or_else(|e| e.into_inner());
+            jobs.retain(|_, job| !job.is_expired());
+            jobs.insert(job_id.clone(), job.clone());
+        }
+
+        let concurrency = self.concurrency;
+        tokio::spawn(async move {
+            use futures::StreamExt;
+
+            futures::stream::iter(cues)
+                .for_each_concurrent(concurrency, |cue| {
+                    let ai_interface = &ai_interface;
+                    let usage = &usage;
+                    let job = &job;
+                    let scope = UsageScope::new(&session_id, cue.context.as_ref());
+                    async move {
+                        if job.is_stopped() {
+                            return;
+                        }
+                        let result = translate_cue(ai_interface, usage, &scope, job, &cue, language_pair).await;
+                        job.record(cue.index, result);
+                    }
+                })
+                .await;
+
+            job.finish();
+        });
+
+        Ok(job_id)
+    }
+
+    pub fn progress(&self, job_id: &str, session_id: &str, since: usize) -> Option<BatchProgress> {
+        self.job(job_id, session_id).map(|job| job.progress(since))
+    }
+
+    // Cues already being translated still finish, the rest are skipped
+    pub fn cancel(&self, job_id: &str, session_id: &str) -> bool {
+        match self.job(job_id, session_id) {
+            Some(job) => {
+                job.cancelled.store(true, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Jobs of other sessions are treated as unknown
+    fn job(&self, job_id: &str, session_id: &str) -> Option<Arc<BatchJob>> {
+        let jobs = self.jobs.lock().unwrap_or_else(|e| e.into_inner());
+        jobs.get(job_id).filter(|job| job.session_id == session_id).cloned()
+    }
+}
+
+// 128 random bits, so job ids can't be guessed
+#[cfg(feature = "ssr")]
+fn new_job_id() -> String {
+    crate::session::to_hex(&crate::session::random_bytes::<16>())
+}
+
+#[cfg(feature = "ssr")]
+impl BatchJob {
+    fn is_stopped(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed) || self.lock_state().error.is_some()
+    }
+
+    fn is_expired(&self) -> bool {
+        self.lock_state()
+            .finished_at
+            .map(|finished_at| finished_at.elapsed() > FINISHED_JOB_RETENTION)
+            .unwrap_or(false)
+    }
+
+    fn lock_state(&self) -> std::sync::MutexGuard<'_, BatchJobState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn record(&self, index: usize, result: Result<TranslationResult, TranslateError>) {
+        let mut state = self.lock_state();
+        if let Err(e) = &result {
+            state.failed += 1;
+            // No other cue will get through either, so stop instead of failing every one of them
+            if matches!(e, TranslateError::AuthFailure | TranslateError::Disabled) {
+                state.error = Some(e.clone());
+            }
+        }
+        state.results.push(BatchCueResult { index, result });
+    }
+
+    fn finish(&self) {
+        self.lock_state().finished_at = Some(Instant::now());
+    }
+
+    fn progress(&self, since: usize) -> BatchProgress {
+        let state = self.lock_state();
+        BatchProgress {
+            total: self.total,
+            completed: state.results.len(),
+            failed: state.failed,
+            finished: state.finished_at.is_some(),
+            cancelled: self.cancelled.load(Ordering::Relaxed),
+            error: state.error.clone(),
+            results: state.results.iter().skip(since).cloned().collect(),
+            next: state.results.len(),
+        }
+    }
+}
+
+// Translates a cue, waiting out rate limits the provider asked us to respect instead of failing the cue
+#[cfg(feature = "ssr")]
+async fn translate_cue(
+    ai_interface: &AIInterface,
+    usage: &UsageTracker,
+    scope: &UsageScope,
+    job: &BatchJob,
+    cue: &BatchCue,
+    language_pair: LanguagePair,
+) -> Result<TranslationResult, TranslateError> {
+    let mut waits = 0;
+    loop {
+        let error = match usage
+            .translate(ai_interface, scope, cue.text.clone(), language_pair, cue.context.as_ref())
+            .await
+        {
+            Ok(result) => return Ok(result),
+            Err(e) => e,
+        };
+
+        let retry_after_secs = match &error {
+            TranslateError::RateLimited { retry_after_secs } | TranslateError::Unavailable { retry_after_secs } => {
+                retry_after_secs.unwrap_or(5)
+            }
+            _ => return Err(error),
+        };
+        if waits == MAX_RATE_LIMIT_WAITS || job.is_stopped() {
+            return Err(error);
+        }
+
+        waits += 1;
+        leptos::logging::warn!("Batch translation is waiting {}s for the provider: {}", retry_after_secs, error.message());
+        tokio::time::sleep(Duration::from_secs(retry_after_secs)).await;
+    }
+}
+
+#[cfg(all(test, feature = "ssr"))]
+mod tests {
+    use super::*;
+    use crate::ai_interface::providers::FixtureProvider;
+    use crate::usage::Pricing;
+
+    fn cue(index: usize, text: &str) -> BatchCue {
+        BatchCue {
+            index,
+            text: text.to_string(),
+            context: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn jobs_are_only_visible_to_the_session_that_started_them() {
+        let jobs = BatchJobs::new(1, 10);
+        let ai_interface = AIInterface::new(Arc::new(FixtureProvider::new()));
+        let pricing = Pricing {
+            prompt_per_million: 0.0,
+            completion_per_million: 0.0,
+        };
+        let usage = Arc::new(UsageTracker::new(pricing, None, None));
+
+        let cues = vec![cue(0, "안녕"), cue(1, "네")];
+        let job_id = jobs
+            .start(ai_interface, usage, "owner".to_string(), cues, LanguagePair::default())
+            .unwrap();
+
+        assert_eq!(job_id.len(), 32);
+        assert!(jobs.progress(&job_id, "someone else", 0).is_none());
+        assert!(!jobs.cancel(&job_id, "someone else"));
+        assert!(!jobs.progress(&job_id, "owner", 0).unwrap().cancelled);
+        assert!(jobs.cancel(&job_id, "owner"));
+        assert!(jobs.progress(&job_id, "owner", 0).unwrap().cancelled);
+    }
+}
@@ -0,0 +1,22 @@
+use dramastudytool::mock_openai;
+use leptos::logging::log;
+use std::env;
+
+// Serves canned translations on an OpenAI compatible API, see src/mock_openai.rs
+//   MOCK_OPENAI_ADDR          address to listen on, default 127.0.0.1:3100
+//   TRANSLATION_FIXTURE_FILE  JSON object mapping input text to a SubtitleTranslationInfo
+#[tokio::main]
+async fn main() {
+    dotenv::dotenv().ok();
+
+    let addr = env::var("MOCK_OPENAI_ADDR").unwrap_or_else(|_| "127.0.0.1:3100".to_string());
+    let fixture_file = env::var("TRANSLATION_FIXTURE_FILE").ok().filter(|path| !path.is_empty());
+
+    let app = mock_openai::router(mock_openai::load_fixtures(fixture_file.as_deref()));
+
+    log!("mock OpenAI server listening on http://{}", &addr);
+    let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
+    axum::serve(listener, app.into_make_service())
+        .await
+        .unwrap();
+}
@@ -0,0 +1,812 @@
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "ssr")]
+use std::env;
+
+#[cfg(feature = "ssr")]
+pub const DEFAULT_CONFIG_FILE: &str = "dramastudytool.toml";
+
+// Whether the server is able to translate, shown on the home page when it isn't
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct TranslationStatus {
+    pub enabled: bool,
+    pub provider: String,
+    // Names of the settings that keep translation from working. The details stay in the server log.
+    pub problems: Vec<String>,
+}
+
+// Settings whose problems disable translation. Any other invalid setting falls back to its default.
+// Bad allowlist entries are dropped, which could leave the translation endpoints open to everyone.
+#[cfg(feature = "ssr")]
+const TRANSLATION_SETTINGS: [&str; 7] = [
+    "TRANSLATION_PROVIDER",
+    "TRANSLATION_FIXTURE_FILE",
+    "OPENAI_API_KEY",
+    "OPENAI_API_URL",
+    "OPENAI_API_DEPLOYMENT",
+    "OPENAI_API_VERSION",
+    "TRANSLATION_ALLOWLIST",
+];
+
+// Something missing or malformed in the configuration. The message can quote configured values and config file
+// lines, so it is only logged.
+#[cfg(feature = "ssr")]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConfigProblem {
+    pub setting: String,
+    pub message: String,
+}
+
+#[cfg(feature = "ssr")]
+impl ConfigProblem {
+    pub fn new(setting: &str, message: impl Into<String>) -> Self {
+        Self { setting: setting.to_string(), message: message.into() }
+    }
+
+    pub fn disables_translation(&self) -> bool {
+        TRANSLATION_SETTINGS.contains(&self.setting.as_str())
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl std::fmt::Display for ConfigProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+#[cfg(feature = "ssr")]
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ProviderKind {
+    #[default]
+    Azure,
+    OpenAI,
+    Fixture,
+}
+
+#[cfg(feature = "ssr")]
+impl ProviderKind {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "azure" => Some(Self::Azure),
+            "openai" => Some(Self::OpenAI),
+            "fixture" => Some(Self::Fixture),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Azure => "azure",
+            Self::OpenAI => "openai",
+            Self::Fixture => "fixture",
+        }
+    }
+}
+
+#[cfg(feature = "ssr")]
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct TranslationConfig {
+    pub provider: ProviderKind,
+    pub fixture_file: Option<String>,
+    // Ask the model for JSON matching the translation schema, turn off for models or servers without structured outputs
+    pub structured_output: bool,
+}
+
+#[cfg(feature = "ssr")]
+impl Default for TranslationConfig {
+    fn default() -> Self {
+        Self {
+            provider: ProviderKind::default(),
+            fixture_file: None,
+            structured_output: true,
+        }
+    }
+}
+
+#[cfg(feature = "ssr")]
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct OpenAIConfig {
+    pub api_key: Option<String>,
+    pub url: Option<String>,
+    pub deployment: Option<String>,
+    pub api_version: Option<String>,
+}
+
+#[cfg(feature = "ssr")]
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct CacheConfig {
+    pub enabled: bool,
+    pub memory_entries: usize,
+    // sled database directory, translations are only kept in memory when empty
+    pub path: Option<String>,
+}
+
+#[cfg(feature = "ssr")]
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            memory_entries: 1000,
+            path: Some("translation_cache".to_string()),
+        }
+    }
+}
+
+#[cfg(feature = "ssr")]
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct RetryConfig {
+    pub timeout_secs: u64,
+    pub max_retries: u32,
+    pub initial_backoff_ms: u64,
+    pub max_backoff_ms: u64,
+    // Consecutive failed translations before calls are paused, 0 disables the circuit breaker
+    pub circuit_breaker_threshold: u32,
+    pub circuit_breaker_cooldown_secs: u64,
+}
+
+#[cfg(feature = "ssr")]
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            timeout_secs: 30,
+            max_retries: 3,
+            initial_backoff_ms: 500,
+            max_backoff_ms: 10_000,
+            circuit_breaker_threshold: 5,
+            circuit_breaker_cooldown_secs: 30,
+        }
+    }
+}
+
+#[cfg(feature = "ssr")]
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct BatchConfig {
+    // Cues of a pre-translated subtitle file translated at the same time
+    pub concurrency: usize,
+    pub max_cues: usize,
+}
+
+#[cfg(feature = "ssr")]
+impl Default for BatchConfig {
+    fn default() -> Self {
+        Self {
+            concurrency: 4,
+            max_cues: 5000,
+        }
+    }
+}
+
+#[cfg(feature = "ssr")]
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct UsageConfig {
+    // USD per million tokens, used to turn token counts into spend
+    pub prompt_price_per_million: f64,
+    pub completion_price_per_million: f64,
+    // Once reached, new translations are refused until the next day (UTC). Cached ones are still served.
+    pub daily_spend_cap_usd: Option<f64>,
+    pub session_daily_spend_cap_usd: Option<f64>,
+}
+
+#[cfg(feature = "ssr")]
+impl Default for UsageConfig {
+    fn default() -> Self {
+        Self {
+            prompt_price_per_million: 2.5,
+            completion_price_per_million: 10.0,
+            daily_spend_cap_usd: None,
+            session_daily_spend_cap_usd: None,
+        }
+    }
+}
+
+#[cfg(feature = "ssr")]
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct LimitsConfig {
+    // Longest subtitle text that is translated, in characters
+    pub max_input_characters: usize,
+    // Limits on the context sent with each subtitle: the number of surrounding lines, and the characters of
+    // those lines, the title and the reference translation together
+    pub max_context_lines: usize,
+    pub max_context_characters: usize,
+    // Token buckets for the translation endpoints, a rate of 0 disables the limit
+    pub ip_requests_per_minute: u32,
+    pub ip_burst: u32,
+    pub session_requests_per_minute: u32,
+    pub session_burst: u32,
+    // Cues "Translate all" can send to the model per hour, per IP address and per session. 0 disables the limit
+    pub batch_cues_per_hour: u32,
+    // When not empty only these IP addresses or CIDR ranges can translate
+    pub allowlist: Vec<String>,
+    // Take the client IP from X-Forwarded-For, only safe behind a proxy that sets it (e.g. Azure App Service)
+    pub trust_forwarded_for: bool,
+}
+
+#[cfg(feature = "ssr")]
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            max_input_characters: 500,
+            max_context_lines: 10,
+            max_context_characters: 2500,
+            ip_requests_per_minute: 30,
+            ip_burst: 10,
+            session_requests_per_minute: 20,
+            session_burst: 10,
+            batch_cues_per_hour: 5000,
+            allowlist: Vec::new(),
+            trust_forwarded_for: false,
+        }
+    }
+}
+
+#[cfg(feature = "ssr")]
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct AdminConfig {
+    // Token required by the /admin page, admin functions are disabled without one
+    pub token: Option<String>,
+}
+
+#[cfg(feature = "ssr")]
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct SessionConfig {
+    // Key that signs the session cookies, a random one is used (and sessions reset on restart) when unset
+    pub secret: Option<String>,
+}
+
+// Server configuration, read from an optional TOML file and then overridden by the environment (and .env).
+#[cfg(feature = "ssr")]
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct AppConfig {
+    // Exit at startup instead of running with translation disabled when the configuration is invalid
+    pub require_translation: bool,
+    pub translation: TranslationConfig,
+    pub openai: OpenAIConfig,
+    pub cache: CacheConfig,
+    pub retry: RetryConfig,
+    pub batch: BatchConfig,
+    pub usage: UsageConfig,
+    pub limits: LimitsConfig,
+    pub session: SessionConfig,
+    pub admin: AdminConfig,
+}
+
+#[cfg(feature = "ssr")]
+impl AppConfig {
+    // Returns the configuration together with everything that is missing or malformed in it
+    pub fn load() -> (Self, Vec<ConfigProblem>) {
+        dotenv::dotenv().ok();
+        let mut problems = Vec::new();
+
+        let mut config = match env::var("DRAMASTUDYTOOL_CONFIG") {
+            Ok(path) => Self::from_toml_file(&path).unwrap_or_else(|e| {
+                problems.push(ConfigProblem::new("DRAMASTUDYTOOL_CONFIG", e));
+                Self::default()
+            }),
+            Err(_) if std::path::Path::new(DEFAULT_CONFIG_FILE).exists() => {
+                Self::from_toml_file(DEFAULT_CONFIG_FILE).unwrap_or_else(|e| {
+                    problems.push(ConfigProblem::new("DRAMASTUDYTOOL_CONFIG", e));
+                    Self::default()
+                })
+            }
+            Err(_) => Self::default(),
+        };
+
+        config.apply_env(&mut problems);
+        problems.extend(config.validate());
+        config.reset_invalid_settings();
+
+        (config, problems)
+    }
+
+    pub fn from_toml_file(path: &str) -> Result<Self, String> {
+        let file_text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Could not read config file {}: {}", path, e))?;
+
+        toml::from_str(&file_text).map_err(|e| format!("Config file {} is malformed: {}", path, e))
+    }
+
+    fn apply_env(&mut self, problems: &mut Vec<ConfigProblem>) {
+        parse_env(problems, &mut self.require_translation, "REQUIRE_TRANSLATION", "true or false");
+
+        if let Some(value) = env_value("TRANSLATION_PROVIDER") {
+            match ProviderKind::parse(&value) {
+                Some(provider) => self.translation.provider = provider,
+                None => problems.push(ConfigProblem::new(
+                    "TRANSLATION_PROVIDER",
+                    format!("TRANSLATION_PROVIDER must be one of azure, openai or fixture, got \"{}\"", value),
+                )),
+            }
+        }
+
+        override_with_env(&mut self.translation.fixture_file, "TRANSLATION_FIXTURE_FILE");
+        parse_env(
+            problems,
+            &mut self.translation.structured_output,
+            "TRANSLATION_STRUCTURED_OUTPUT",
+            "true or false",
+        );
+        override_with_env(&mut self.openai.api_key, "OPENAI_API_KEY");
+        override_with_env(&mut self.openai.url, "OPENAI_API_URL");
+        override_with_env(&mut self.openai.deployment, "OPENAI_API_DEPLOYMENT");
+        override_with_env(&mut self.openai.api_version, "OPENAI_API_VERSION");
+
+        parse_env(problems, &mut self.cache.enabled, "TRANSLATION_CACHE_ENABLED", "true or false");
+        parse_env(problems, &mut self.cache.memory_entries, "TRANSLATION_CACHE_SIZE", "a number");
+        override_with_env(&mut self.cache.path, "TRANSLATION_CACHE_PATH");
+
+        parse_env(problems, &mut self.retry.timeout_secs, "TRANSLATION_TIMEOUT_SECS", "a number");
+        parse_env(problems, &mut self.retry.max_retries, "TRANSLATION_MAX_RETRIES", "a number");
+        parse_env(problems, &mut self.retry.initial_backoff_ms, "TRANSLATION_BACKOFF_MS", "a number");
+        parse_env(problems, &mut self.retry.max_backoff_ms, "TRANSLATION_MAX_BACKOFF_MS", "a number");
+        parse_env(
+            problems,
+            &mut self.retry.circuit_breaker_threshold,
+            "TRANSLATION_CIRCUIT_BREAKER_THRESHOLD",
+            "a number",
+        );
+        parse_env(
+            problems,
+            &mut self.retry.circuit_breaker_cooldown_secs,
+            "TRANSLATION_CIRCUIT_BREAKER_COOLDOWN_SECS",
+            "a number",
+        );
+
+        parse_env(problems, &mut self.batch.concurrency, "BATCH_CONCURRENCY", "a number");
+        parse_env(problems, &mut self.batch.max_cues, "BATCH_MAX_CUES", "a number");
+
+        parse_env(
+            problems,
+            &mut self.usage.prompt_price_per_million,
+            "USAGE_PROMPT_PRICE_PER_MILLION",
+            "a number",
+        );
+        parse_env(
+            problems,
+            &mut self.usage.completion_price_per_million,
+            "USAGE_COMPLETION_PRICE_PER_MILLION",
+            "a number",
+        );
+        parse_optional_env(problems, &mut self.usage.daily_spend_cap_usd, "USAGE_DAILY_SPEND_CAP", "a number");
+        parse_optional_env(
+            problems,
+            &mut self.usage.session_daily_spend_cap_usd,
+            "USAGE_SESSION_DAILY_SPEND_CAP",
+            "a number",
+        );
+
+        parse_env(problems, &mut self.limits.max_input_characters, "TRANSLATION_MAX_INPUT_CHARS", "a number");
+        parse_env(problems, &mut self.limits.max_context_lines, "TRANSLATION_MAX_CONTEXT_LINES", "a number");
+        parse_env(
+            problems,
+            &mut self.limits.max_context_characters,
+            "TRANSLATION_MAX_CONTEXT_CHARS",
+            "a number",
+        );
+        parse_env(problems, &mut self.limits.ip_requests_per_minute, "RATE_LIMIT_IP_PER_MINUTE", "a number");
+        parse_env(problems, &mut self.limits.ip_burst, "RATE_LIMIT_IP_BURST", "a number");
+        parse_env(
+            problems,
+            &mut self.limits.session_requests_per_minute,
+            "RATE_LIMIT_SESSION_PER_MINUTE",
+            "a number",
+        );
+        parse_env(problems, &mut self.limits.session_burst, "RATE_LIMIT_SESSION_BURST", "a number");
+        parse_env(problems, &mut self.limits.batch_cues_per_hour, "RATE_LIMIT_BATCH_CUES_PER_HOUR", "a number");
+        if let Some(value) = env_value("TRANSLATION_ALLOWLIST") {
+            self.limits.allowlist = value.split(',').map(|entry| entry.trim().to_string()).collect();
+        }
+        parse_env(problems, &mut self.limits.trust_forwarded_for, "TRUST_FORWARDED_FOR", "true or false");
+
+        override_with_env(&mut self.session.secret, "SESSION_SECRET");
+        override_with_env(&mut self.admin.token, "ADMIN_TOKEN");
+    }
+
+    pub fn validate(&self) -> Vec<ConfigProblem> {
+        let mut problems = Vec::new();
+
+        match self.translation.provider {
+            ProviderKind::Azure => {
+                require(&mut problems, &self.openai.api_key, "OPENAI_API_KEY", "openai.api_key");
+                require(&mut problems, &self.openai.url, "OPENAI_API_URL", "openai.url");
+                require(&mut problems, &self.openai.deployment, "OPENAI_API_DEPLOYMENT", "openai.deployment");
+                require(&mut problems, &self.openai.api_version, "OPENAI_API_VERSION", "openai.api_version");
+            }
+            ProviderKind::OpenAI => {
+                require(&mut problems, &self.openai.url, "OPENAI_API_URL", "openai.url");
+                require(&mut problems, &self.openai.deployment, "OPENAI_API_DEPLOYMENT", "openai.deployment");
+            }
+            ProviderKind::Fixture => {
+                if let Some(path) = &self.translation.fixture_file {
+                    if !std::path::Path::new(path).exists() {
+                        problems.push(ConfigProblem::new(
+                            "TRANSLATION_FIXTURE_FILE",
+                            format!("TRANSLATION_FIXTURE_FILE {} does not exist", path),
+                        ));
+                    }
+                }
+            }
+        }
+
+        if self.translation.provider != ProviderKind::Fixture {
+            if let Some(url) = &self.openai.url {
+                if reqwest::Url::parse(url).map(|url| !url.scheme().starts_with("http")).unwrap_or(true) {
+                    problems.push(ConfigProblem::new(
+                        "OPENAI_API_URL",
+                        format!("OPENAI_API_URL must be an http(s) URL, got \"{}\"", url),
+                    ));
+                }
+            }
+        }
+
+        if self.translation.provider == ProviderKind::Azure {
+            if let Some(api_version) = &self.openai.api_version {
+                if !is_api_version(api_version) {
+                    problems.push(ConfigProblem::new(
+                        "OPENAI_API_VERSION",
+                        format!(
+                            "OPENAI_API_VERSION must look like 2024-06-01 or 2024-08-01-preview, got \"{}\"",
+                            api_version
+                        ),
+                    ));
+                }
+            }
+        }
+
+        if self.retry.timeout_secs == 0 {
+            problems.push(ConfigProblem::new(
+                "TRANSLATION_TIMEOUT_SECS",
+                "TRANSLATION_TIMEOUT_SECS must be greater than 0",
+            ));
+        }
+        if self.batch.concurrency == 0 {
+            problems.push(ConfigProblem::new("BATCH_CONCURRENCY", "BATCH_CONCURRENCY must be greater than 0"));
+        }
+        if self.usage.prompt_price_per_million < 0.0 {
+            problems.push(ConfigProblem::new(
+                "USAGE_PROMPT_PRICE_PER_MILLION",
+                "USAGE_PROMPT_PRICE_PER_MILLION can't be negative",
+            ));
+        }
+        if self.usage.completion_price_per_million < 0.0 {
+            problems.push(ConfigProblem::new(
+                "USAGE_COMPLETION_PRICE_PER_MILLION",
+                "USAGE_COMPLETION_PRICE_PER_MILLION can't be negative",
+            ));
+        }
+        if self.limits.max_input_characters == 0 {
+            problems.push(ConfigProblem::new(
+                "TRANSLATION_MAX_INPUT_CHARS",
+                "TRANSLATION_MAX_INPUT_CHARS must be greater than 0",
+            ));
+        }
+        for entry in self.limits.allowlist.iter().filter(|entry| !entry.is_empty()) {
+            if crate::rate_limit::IpRange::parse(entry).is_none() {
+                problems.push(ConfigProblem::new(
+                    "TRANSLATION_ALLOWLIST",
+                    format!("TRANSLATION_ALLOWLIST entries must be IP addresses or CIDR ranges, got \"{}\"", entry),
+                ));
+            }
+        }
+
+        problems
+    }
+
+    // The invalid values validate() reports for settings that don't disable translation are replaced by defaults
+    fn reset_invalid_settings(&mut self) {
+        let defaults = Self::default();
+        if self.retry.timeout_secs == 0 {
+            self.retry.timeout_secs = defaults.retry.timeout_secs;
+        }
+        if self.batch.concurrency == 0 {
+            self.batch.concurrency = defaults.batch.concurrency;
+        }
+        if self.usage.prompt_price_per_million < 0.0 {
+            self.usage.prompt_price_per_million = defaults.usage.prompt_price_per_million;
+        }
+        if self.usage.completion_price_per_million < 0.0 {
+            self.usage.completion_price_per_million = defaults.usage.completion_price_per_million;
+        }
+        if self.limits.max_input_characters == 0 {
+            self.limits.max_input_characters = defaults.limits.max_input_characters;
+        }
+    }
+
+    // Only the names of the settings go to the client, the problem messages can quote secrets
+    pub fn translation_status(&self, problems: &[ConfigProblem]) -> TranslationStatus {
+        let mut settings: Vec<String> = Vec::new();
+        for problem in problems.iter().filter(|problem| problem.disables_translation()) {
+            if !settings.contains(&problem.setting) {
+                settings.push(problem.setting.clone());
+            }
+        }
+
+        TranslationStatus {
+            enabled: settings.is_empty(),
+            provider: self.translation.provider.name().to_string(),
+            problems: settings,
+        }
+    }
+}
+
+// Empty variables (as in .env.example) count as unset
+#[cfg(feature = "ssr")]
+fn env_value(name: &str) -> Option<String> {
+    env::var(name).ok().filter(|value| !value.trim().is_empty())
+}
+
+#[cfg(feature = "ssr")]
+fn override_with_env(field: &mut Option<String>, name: &str) {
+    if let Some(value) = env_value(name) {
+        *field = Some(value);
+    }
+}
+
+#[cfg(feature = "ssr")]
+fn parse_env<T: std::str::FromStr>(problems: &mut Vec<ConfigProblem>, field: &mut T, name: &str, expected: &str) {
+    if let Some(value) = env_value(name) {
+        match value.trim().parse::<T>() {
+            Ok(parsed) => *field = parsed,
+            Err(_) => problems.push(malformed_env(name, expected, &value)),
+        }
+    }
+}
+
+#[cfg(feature = "ssr")]
+fn parse_optional_env<T: std::str::FromStr>(
+    problems: &mut Vec<ConfigProblem>,
+    field: &mut Option<T>,
+    name: &str,
+    expected: &str,
+) {
+    if let Some(value) = env_value(name) {
+        match value.trim().parse::<T>() {
+            Ok(parsed) => *field = Some(parsed),
+            Err(_) => problems.push(malformed_env(name, expected, &value)),
+        }
+    }
+}
+
+#[cfg(feature = "ssr")]
+fn malformed_env(name: &str, expected: &str, value: &str) -> ConfigProblem {
+    ConfigProblem::new(name, format!("{} must be {}, got \"{}\"", name, expected, value))
+}
+
+#[cfg(feature = "ssr")]
+fn require(problems: &mut Vec<ConfigProblem>, field: &Option<String>, env_name: &str, toml_name: &str) {
+    if field.as_deref().map(str::trim).unwrap_or_default().is_empty() {
+        let message = format!("{} (or {} in the config file) is not set", env_name, toml_name);
+        problems.push(ConfigProblem::new(env_name, message));
+    }
+}
+
+#[cfg(feature = "ssr")]
+fn is_api_version(api_version: &str) -> bool {
+    let date = api_version.strip_suffix("-preview").unwrap_or(api_version);
+    let parts: Vec<&str> = date.split('-').collect();
+
+    parts.len() == 3
+        && parts[0].len() == 4
+        && parts[1].len() == 2
+        && parts[2].len() == 2
+        && parts.iter().all(|part| part.chars().all(|c| c.is_ascii_digit()))
+}
+
+#[cfg(all(test, feature = "ssr"))]
+mod tests {
+    use super::*;
+
+    fn azure_config() -> AppConfig {
+        let mut config = AppConfig::default();
+        config.openai.api_key = Some("key".to_string());
+        config.openai.url = Some("https://example.openai.azure.com".to_string());
+        config.openai.deployment = Some("gpt-4o".to_string());
+        config.openai.api_version = Some("2024-08-01-preview".to_string());
+        config
+    }
+
+    fn write_config(name: &str, text: &str) -> String {
+        let path = env::temp_dir().join(format!("{}-{}.toml", name, std::process::id()));
+        std::fs::write(&path, text).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn accepts_a_complete_azure_config() {
+        assert!(azure_config().validate().is_empty());
+    }
+
+    #[test]
+    fn names_every_missing_azure_setting() {
+        let problems = AppConfig::default().validate();
+
+        assert_eq!(problems.len(), 4);
+        for name in ["OPENAI_API_KEY", "OPENAI_API_URL", "OPENAI_API_DEPLOYMENT", "OPENAI_API_VERSION"] {
+            assert!(problems.iter().any(|problem| problem.setting == name), "{} in {:?}", name, problems);
+        }
+    }
+
+    #[test]
+    fn openai_compatible_servers_need_no_api_key_or_version() {
+        let mut config = AppConfig::default();
+        config.translation.provider = ProviderKind::OpenAI;
+        config.openai.url = Some("http://localhost:11434/v1".to_string());
+        config.openai.deployment = Some("llama3".to_string());
+
+        assert!(config.validate().is_empty());
+    }
+
+    #[test]
+    fn the_fixture_provider_only_needs_an_existing_file() {
+        let mut config = AppConfig::default();
+        config.translation.provider = ProviderKind::Fixture;
+        assert!(config.validate().is_empty());
+
+        config.translation.fixture_file = Some("no/such/fixture.json".to_string());
+        let problems = config.validate();
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].message, "TRANSLATION_FIXTURE_FILE no/such/fixture.json does not exist");
+    }
+
+    #[test]
+    fn rejects_malformed_urls_and_api_versions() {
+        let mut config = azure_config();
+        config.openai.url = Some("example.openai.azure.com".to_string());
+        config.openai.api_version = Some("2024-8-1".to_string());
+
+        let problems = config.validate();
+        assert_eq!(problems.len(), 2);
+        assert!(problems[0].message.starts_with("OPENAI_API_URL must be an http(s) URL"));
+        assert!(problems[1].message.starts_with("OPENAI_API_VERSION must look like"));
+    }
+
+    #[test]
+    fn recognizes_api_versions() {
+        assert!(is_api_version("2024-06-01"));
+        assert!(is_api_version("2024-08-01-preview"));
+        assert!(!is_api_version("2024-06"));
+        assert!(!is_api_version("v1"));
+        assert!(!is_api_version("2024-06-01-beta"));
+    }
+
+    #[test]
+    fn rejects_out_of_range_limits() {
+        let mut config = azure_config();
+        config.retry.timeout_secs = 0;
+        config.batch.concurrency = 0;
+        config.usage.prompt_price_per_million = -1.0;
+        config.limits.max_input_characters = 0;
+        config.limits.allowlist = vec!["10.0.0.0/8".to_string(), "office".to_string()];
+
+        let problems = config.validate();
+        assert_eq!(problems.len(), 5, "{:?}", problems);
+        assert!(problems[4].message.contains("\"office\""));
+    }
+
+    #[test]
+    fn parses_providers_case_insensitively() {
+        for provider in [ProviderKind::Azure, ProviderKind::OpenAI, ProviderKind::Fixture] {
+            assert_eq!(ProviderKind::parse(provider.name()), Some(provider));
+        }
+        assert_eq!(ProviderKind::parse(" OpenAI "), Some(ProviderKind::OpenAI));
+        assert_eq!(ProviderKind::parse("anthropic"), None);
+    }
+
+    #[test]
+    fn reads_the_example_config_file() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/dramastudytool.example.toml");
+        let config = AppConfig::from_toml_file(path).unwrap();
+
+        assert_eq!(config.translation.provider, ProviderKind::Azure);
+        assert_eq!(config.openai.deployment.as_deref(), Some("gpt-4o"));
+        assert_eq!(config.limits.max_input_characters, 500);
+        // The example leaves the key empty, which counts as not set
+        let problems = config.validate();
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].message, "OPENAI_API_KEY (or openai.api_key in the config file) is not set");
+    }
+
+    #[test]
+    fn fills_in_defaults_for_missing_config_sections() {
+        let text = "[translation]\nprovider = \"fixture\"\n\n[retry]\nmax_retries = 1\n";
+        let path = write_config("partial-config", text);
+        let config = AppConfig::from_toml_file(&path);
+        std::fs::remove_file(&path).ok();
+
+        let config = config.unwrap();
+        assert_eq!(config.translation.provider, ProviderKind::Fixture);
+        assert!(config.translation.structured_output);
+        assert_eq!(config.retry.max_retries, 1);
+        assert_eq!(config.retry.timeout_secs, RetryConfig::default().timeout_secs);
+    }
+
+    #[test]
+    fn reports_unreadable_and_malformed_config_files() {
+        let missing = AppConfig::from_toml_file("no/such/config.toml").unwrap_err();
+        assert!(missing.starts_with("Could not read config file no/such/config.toml"));
+
+        let path = write_config("malformed-config", "[translation]\nprovider = \"bard\"\n");
+        let malformed = AppConfig::from_toml_file(&path).unwrap_err();
+        std::fs::remove_file(&path).ok();
+        assert!(malformed.starts_with(&format!("Config file {} is malformed", path)));
+    }
+
+    // Each test uses its own variables, the environment is shared by the tests running in parallel
+    #[test]
+    fn environment_values_override_and_report_bad_ones() {
+        let mut problems = Vec::new();
+        let mut max_retries = 3u32;
+        let mut structured_output = true;
+        let mut url = Some("https://example.com".to_string());
+
+        env::set_var("DRAMASTUDYTOOL_TEST_MAX_RETRIES", " 5 ");
+        env::set_var("DRAMASTUDYTOOL_TEST_STRUCTURED_OUTPUT", "no");
+        env::set_var("DRAMASTUDYTOOL_TEST_URL", "  ");
+        parse_env(&mut problems, &mut max_retries, "DRAMASTUDYTOOL_TEST_MAX_RETRIES", "a number");
+        parse_env(&mut problems, &mut structured_output, "DRAMASTUDYTOOL_TEST_STRUCTURED_OUTPUT", "true or false");
+        override_with_env(&mut url, "DRAMASTUDYTOOL_TEST_URL");
+
+        assert_eq!(max_retries, 5);
+        assert!(structured_output);
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].setting, "DRAMASTUDYTOOL_TEST_STRUCTURED_OUTPUT");
+        assert_eq!(problems[0].message, "DRAMASTUDYTOOL_TEST_STRUCTURED_OUTPUT must be true or false, got \"no\"");
+        // Empty variables count as unset
+        assert_eq!(url.as_deref(), Some("https://example.com"));
+    }
+
+    #[test]
+    fn optional_environment_values_are_set_when_present() {
+        let mut problems = Vec::new();
+        let mut daily_cap = None;
+
+        parse_optional_env(&mut problems, &mut daily_cap, "DRAMASTUDYTOOL_TEST_UNSET_CAP", "a number");
+        assert_eq!(daily_cap, None);
+
+        env::set_var("DRAMASTUDYTOOL_TEST_DAILY_CAP", "2.5");
+        parse_optional_env(&mut problems, &mut daily_cap, "DRAMASTUDYTOOL_TEST_DAILY_CAP", "a number");
+        assert_eq!(daily_cap, Some(2.5));
+        assert!(problems.is_empty());
+    }
+
+    #[test]
+    fn only_translation_settings_disable_translation() {
+        let config = azure_config();
+        assert!(config.translation_status(&[]).enabled);
+
+        let concurrency = ConfigProblem::new("BATCH_CONCURRENCY", "BATCH_CONCURRENCY must be a number, got \"x\"");
+        assert!(config.translation_status(std::slice::from_ref(&concurrency)).enabled);
+
+        let url = ConfigProblem::new("OPENAI_API_URL", "OPENAI_API_URL must be an http(s) URL, got \"secret\"");
+        let status = config.translation_status(&[concurrency, url.clone(), url]);
+        assert!(!status.enabled);
+        assert_eq!(status.provider, "azure");
+        // Only the setting names reach the client, not the values quoted in the messages
+        assert_eq!(status.problems, vec!["OPENAI_API_URL"]);
+    }
+
+    #[test]
+    fn invalid_limits_fall_back_to_their_defaults() {
+        let mut config = azure_config();
+        config.retry.timeout_secs = 0;
+        config.batch.concurrency = 0;
+        config.usage.completion_price_per_million = -1.0;
+        config.limits.max_input_characters = 0;
+
+        config.reset_invalid_settings();
+        assert!(config.validate().is_empty(), "{:?}", config.validate());
+        assert_eq!(config.retry.timeout_secs, RetryConfig::default().timeout_secs);
+        assert_eq!(config.batch.concurrency, BatchConfig::default().concurrency);
+    }
+}
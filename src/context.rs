@@ -1,38 +1,253 @@
 use leptos::logging::error;
-use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+use subtle::ConstantTimeEq;
 
+use crate::ai_interface::providers::{
+    AzureOpenAIConfig, AzureOpenAIProvider, FixtureProvider, OpenAICompatibleProvider,
+    TranslationProvider,
+};
+use crate::ai_interface::retry::{CircuitBreaker, RetryPolicy};
 use crate::ai_interface::AIInterface;
+use crate::batch::BatchJobs;
+use crate::config::{
+    AppConfig, BatchConfig, CacheConfig, ConfigProblem, LimitsConfig, ProviderKind, RetryConfig, TranslationStatus,
+    UsageConfig,
+};
+use crate::rate_limit::RequestLimits;
+use crate::translation_cache::TranslationCache;
+use crate::usage::{Pricing, UsageTracker};
 
 #[derive(Clone, Debug)]
 pub struct DramaStudyToolAppContext {
-    pub ai_interface: AIInterface,
+    // None when the configuration is invalid and translation is disabled
+    pub ai_interface: Option<AIInterface>,
+    pub translation_status: TranslationStatus,
+    pub translation_cache: Option<Arc<TranslationCache>>,
+    // Pre-translations of whole subtitle files
+    pub batch_jobs: Arc<BatchJobs>,
+    // Token usage and spend, and the caps on it
+    pub usage: Arc<UsageTracker>,
+    // Rate limits, input length cap and allowlist of the translation endpoints
+    pub request_limits: Arc<RequestLimits>,
+    pub admin_token: Option<String>,
 }
 
 impl DramaStudyToolAppContext {
-    pub fn new() -> Self {
-        dotenv::dotenv().ok();
-        let _ai_api_key = env::var("OPENAI_API_KEY").unwrap_or_else(|_| {
-            error!("OPENAI_API_KEY not set");
-            String::new()
-        });
+    pub fn new(config: &AppConfig, config_problems: &[ConfigProblem]) -> Self {
+        let translation_status = config.translation_status(config_problems);
 
-        let _ai_completions_endpoint_base = env::var("OPENAI_API_URL").unwrap_or_else(|_| {
-            error!("OPENAI_API_URL not set");
-            String::new()
-        });
+        let translation_cache = Self::build_cache(&config.cache);
 
-        let _ai_deployment_id = env::var("OPENAI_API_DEPLOYMENT").unwrap_or_else(|_| {
-            error!("OPENAI_API_DEPLOYMENT not set");
-            String::new()
-        });
+        let ai_interface = if translation_status.enabled {
+            let ai_interface = AIInterface::new(Self::build_provider(config))
+                .with_retry_policy(Self::build_retry_policy(&config.retry))
+                .with_circuit_breaker(CircuitBreaker::new(
+                    config.retry.circuit_breaker_threshold,
+                    Duration::from_secs(config.retry.circuit_breaker_cooldown_secs),
+                ));
+            Some(match &translation_cache {
+                Some(cache) => ai_interface.with_cache(cache.clone()),
+                None => ai_interface,
+            })
+        } else {
+            None
+        };
 
-        let _ai_api_version = env::var("OPENAI_API_VERSION").unwrap_or_else(|_| {
-            error!("OPENAI_API_VERSION not set");
-            String::new()
-        });
+        Self {
+            ai_interface,
+            translation_status,
+            translation_cache,
+            batch_jobs: Arc::new(Self::build_batch_jobs(&config.batch)),
+            usage: Arc::new(Self::build_usage_tracker(&config.usage)),
+            request_limits: Arc::new(Self::build_request_limits(&config.limits)),
+            admin_token: config.admin.token.clone().filter(|token| !token.is_empty()),
+        }
+    }
+
+    // Build a context around an already configured provider, e.g. a fixture provider in tests
+    pub fn from_provider(provider: Arc<dyn TranslationProvider>) -> Self {
+        let translation_status = TranslationStatus {
+            enabled: true,
+            provider: provider.name().to_string(),
+            problems: Vec::new(),
+        };
 
         Self {
-            ai_interface: AIInterface::new(),
+            ai_interface: Some(AIInterface::new(provider)),
+            translation_status,
+            translation_cache: None,
+            batch_jobs: Arc::new(Self::build_batch_jobs(&BatchConfig::default())),
+            usage: Arc::new(Self::build_usage_tracker(&UsageConfig::default())),
+            request_limits: Arc::new(Self::build_request_limits(&LimitsConfig::default())),
+            admin_token: None,
         }
     }
+
+    pub fn is_admin(&self, token: &str) -> bool {
+        match &self.admin_token {
+            // Constant time, so that the token can't be guessed byte by byte from response times
+            Some(admin_token) => admin_token.as_bytes().ct_eq(token.as_bytes()).into(),
+            None => false,
+        }
+    }
+
+    fn build_cache(cache_config: &CacheConfig) -> Option<Arc<TranslationCache>> {
+        if !cache_config.enabled {
+            return None;
+        }
+
+        let path = cache_config.path.as_deref().filter(|path| !path.is_empty());
+        let cache = TranslationCache::open(path, cache_config.memory_entries).unwrap_or_else(|e| {
+            error!("{}, keeping translations in memory only", e);
+            TranslationCache::in_memory(cache_config.memory_entries)
+        });
+
+        Some(Arc::new(cache))
+    }
+
+    fn build_batch_jobs(batch_config: &BatchConfig) -> BatchJobs {
+        BatchJobs::new(batch_config.concurrency, batch_config.max_cues)
+    }
+
+    fn build_usage_tracker(usage_config: &UsageConfig) -> UsageTracker {
+        UsageTracker::new(
+            Pricing {
+                prompt_per_million: usage_config.prompt_price_per_million,
+                completion_per_million: usage_config.completion_price_per_million,
+            },
+            usage_config.daily_spend_cap_usd,
+            usage_config.session_daily_spend_cap_usd,
+        )
+    }
+
+    fn build_request_limits(limits_config: &LimitsConfig) -> RequestLimits {
+        RequestLimits::new(limits_config, crate::app::translation_server_fn_paths())
+            .with_streaming_paths(crate::app::streaming_server_fn_paths())
+    }
+
+    fn build_retry_policy(retry_config: &RetryConfig) -> RetryPolicy {
+        RetryPolicy {
+            request_timeout: Duration::from_secs(retry_config.timeout_secs),
+            max_retries: retry_config.max_retries,
+            initial_backoff: Duration::from_millis(retry_config.initial_backoff_ms),
+            max_backoff: Duration::from_millis(retry_config.max_backoff_ms),
+        }
+    }
+
+    fn build_provider(config: &AppConfig) -> Arc<dyn TranslationProvider> {
+        let openai = config.openai.clone();
+
+        match config.translation.provider {
+            // The OpenAI compatible API has no api version and takes the deployment as the model name
+            ProviderKind::OpenAI => Arc::new(OpenAICompatibleProvider::new(
+                openai.url.unwrap_or_default(),
+                openai.api_key.unwrap_or_default(),
+                openai.deployment.unwrap_or_default(),
+            )
+            .with_structured_output(config.translation.structured_output)),
+            ProviderKind::Fixture => match &config.translation.fixture_file {
+                Some(path) => Arc::new(FixtureProvider::from_file(path).unwrap_or_else(|e| {
+                    error!("{}", e);
+                    FixtureProvider::new()
+                })),
+                None => Arc::new(FixtureProvider::new()),
+            },
+            ProviderKind::Azure => Arc::new(AzureOpenAIProvider::new(AzureOpenAIConfig {
+                api_key: openai.api_key.unwrap_or_default(),
+                endpoint: openai.url.unwrap_or_default(),
+                deployment: openai.deployment.unwrap_or_default(),
+                api_version: openai.api_version.unwrap_or_default(),
+            })
+            .with_structured_output(config.translation.structured_output)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Without a cache, so that the tests don't open the sled database in the working directory
+    fn config(provider: ProviderKind) -> AppConfig {
+        let mut config = AppConfig::default();
+        config.translation.provider = provider;
+        config.cache.enabled = false;
+        config
+    }
+
+    fn azure_config() -> AppConfig {
+        let mut config = config(ProviderKind::Azure);
+        config.openai.api_key = Some("key".to_string());
+        config.openai.url = Some("https://example.openai.azure.com".to_string());
+        config.openai.deployment = Some("gpt-4o".to_string());
+        config.openai.api_version = Some("2024-08-01-preview".to_string());
+        config
+    }
+
+    fn context(config: &AppConfig) -> DramaStudyToolAppContext {
+        DramaStudyToolAppContext::new(config, &config.validate())
+    }
+
+    #[test]
+    fn builds_the_configured_provider() {
+        let mut openai = config(ProviderKind::OpenAI);
+        openai.openai.url = Some("http://localhost:11434/v1".to_string());
+        openai.openai.deployment = Some("llama3".to_string());
+
+        let configs = [(azure_config(), "azure"), (openai, "openai"), (config(ProviderKind::Fixture), "fixture")];
+
+        for (config, provider) in configs {
+            let context = context(&config);
+
+            assert!(context.translation_status.enabled, "{:?}", context.translation_status.problems);
+            assert_eq!(context.translation_status.provider, provider);
+            assert_eq!(context.ai_interface.as_ref().map(AIInterface::provider_name), Some(provider));
+        }
+    }
+
+    #[test]
+    fn contexts_with_different_providers_coexist() {
+        let azure = context(&azure_config());
+        let fixture = context(&config(ProviderKind::Fixture));
+
+        assert_eq!(azure.ai_interface.as_ref().map(AIInterface::provider_name), Some("azure"));
+        assert_eq!(fixture.ai_interface.as_ref().map(AIInterface::provider_name), Some("fixture"));
+    }
+
+    #[test]
+    fn disables_translation_when_the_config_has_problems() {
+        let config = config(ProviderKind::Azure);
+        let context = context(&config);
+
+        assert!(context.ai_interface.is_none());
+        assert!(!context.translation_status.enabled);
+        assert_eq!(context.translation_status.provider, "azure");
+        assert!(context.translation_status.problems.contains(&"OPENAI_API_KEY".to_string()));
+    }
+
+    #[test]
+    fn keeps_the_cache_out_when_disabled() {
+        let mut fixture = config(ProviderKind::Fixture);
+        assert!(context(&fixture).translation_cache.is_none());
+
+        fixture.cache.enabled = true;
+        fixture.cache.path = None;
+        assert!(context(&fixture).translation_cache.is_some());
+    }
+
+    #[test]
+    fn only_accepts_the_configured_admin_token() {
+        let mut config = config(ProviderKind::Fixture);
+        assert!(!context(&config).is_admin(""));
+
+        config.admin.token = Some(String::new());
+        assert!(!context(&config).is_admin(""));
+
+        config.admin.token = Some("secret".to_string());
+        let context = context(&config);
+        assert!(context.is_admin("secret"));
+        assert!(!context.is_admin("secreT"));
+        assert!(!context.is_admin("secret "));
+    }
 }
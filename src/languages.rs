@@ -0,0 +1,432 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    #[default]
+    Korean,
+    Japanese,
+    Mandarin,
+    Spanish,
+    English,
+}
+
+impl Language {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Korean => "ko",
+            Self::Japanese => "ja",
+            Self::Mandarin => "zh",
+            Self::Spanish => "es",
+            Self::English => "en",
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Korean => "Korean",
+            Self::Japanese => "Japanese",
+            Self::Mandarin => "Mandarin Chinese",
+            Self::Spanish => "Spanish",
+            Self::English => "English",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<Self> {
+        match code {
+            "ko" => Some(Self::Korean),
+            "ja" => Some(Self::Japanese),
+            "zh" => Some(Self::Mandarin),
+            "es" => Some(Self::Spanish),
+            "en" => Some(Self::English),
+            _ => None,
+        }
+    }
+}
+
+// The language of the show and the language translations and explanations are written in.
+// Serialized as its id so that it can be sent as a single form field.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq, Hash)]
+#[serde(try_from = "String", into = "String")]
+pub struct LanguagePair {
+    pub source: Language,
+    pub explanation: Language,
+}
+
+impl Default for LanguagePair {
+    fn default() -> Self {
+        Self {
+            source: Language::Korean,
+            explanation: Language::English,
+        }
+    }
+}
+
+impl TryFrom<String> for LanguagePair {
+    type Error = String;
+
+    fn try_from(id: String) -> Result<Self, Self::Error> {
+        Self::from_id(&id).ok_or_else(|| format!("Unknown language pair {}", id))
+    }
+}
+
+impl From<LanguagePair> for String {
+    fn from(language_pair: LanguagePair) -> Self {
+        language_pair.id()
+    }
+}
+
+impl LanguagePair {
+    pub const SUPPORTED: [LanguagePair; 4] = [
+        LanguagePair {
+            source: Language::Korean,
+            explanation: Language::English,
+        },
+        LanguagePair {
+            source: Language::Japanese,
+            explanation: Language::English,
+        },
+        LanguagePair {
+            source: Language::Mandarin,
+            explanation: Language::English,
+        },
+        LanguagePair {
+            source: Language::Spanish,
+            explanation: Language::English,
+        },
+    ];
+
+    // e.g. "ko-en", used in select values and cache keys
+    pub fn id(&self) -> String {
+        format!("{}-{}", self.source.code(), self.explanation.code())
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        let (source, explanation) = id.split_once('-')?;
+        Some(Self {
+            source: Language::from_code(source)?,
+            explanation: Language::from_code(explanation)?,
+        })
+    }
+
+    pub fn label(&self) -> String {
+        format!("{} → {}", self.source.name(), self.explanation.name())
+    }
+
+    pub fn is_supported(&self) -> bool {
+        Self::SUPPORTED.contains(self)
+    }
+
+    pub fn system_prompt(&self) -> String {
+        format!(
+            "You're an agent who helps provide information to {explanation} speaking learners of {source} who are inputting a specific subtitle of a {source} show they are watching. Translate the subtitle into {explanation}, and write the vocabulary translations and grammar descriptions in {explanation}. {vocabulary} All of your answers and output will only be in JSON with no prose.",
+            source = self.source.name(),
+            explanation = self.explanation.name(),
+            vocabulary = self.vocabulary_prompt(),
+        )
+    }
+
+    // For glossing a list of words, one per line, such as the study list of an episode
+    pub fn gloss_prompt(&self) -> String {
+        format!(
+            "You're an agent who helps {explanation} speaking learners of {source} study the words of a {source} show before they watch it. The user sends {source} words, one per line. Give the {explanation} translation of every one of them, and no other words, as the vocabulary array of a JSON object, using each word exactly as it was sent as its word. {vocabulary} All of your answers and output will only be in JSON with no prose.",
+            source = self.source.name(),
+            explanation = self.explanation.name(),
+            vocabulary = self.vocabulary_prompt(),
+        )
+    }
+
+    // The vocabulary of the few-shot example as a gloss request and its answer
+    pub fn gloss_example(&self) -> (String, serde_json::Value) {
+        let (_, example_output) = self.few_shot_example();
+        let vocabulary = example_output.get("vocabulary").cloned().unwrap_or_default();
+        let words = vocabulary
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|vocab| vocab.get("word").and_then(|word| word.as_str()))
+            .collect::<Vec<&str>>()
+            .join("\n");
+        (words, serde_json::json!({ "vocabulary": vocabulary }))
+    }
+
+    // What to give for each vocabulary word besides its translation, null for what doesn't apply
+    fn vocabulary_prompt(&self) -> String {
+        let romanization = match self.source {
+            Language::Korean => "its Revised Romanization",
+            Language::Japanese => "its Hepburn romanization",
+            Language::Mandarin => "its pinyin with tone marks",
+            Language::Spanish | Language::English => "null as romanization",
+        };
+        let hanja = match self.source {
+            Language::Korean => ", the Hanja of Sino-Korean words",
+            _ => "",
+        };
+        format!(
+            "For each vocabulary word also give its dictionary form as lemma, its part of speech in lowercase {explanation} (e.g. \"noun\", \"verb\"), {romanization}{hanja}, its register (\"neutral\", \"formal\", \"polite\", \"casual\" or \"slang\") and a short example sentence using it, followed by its {explanation} translation in parentheses.",
+            explanation = self.explanation.name(),
+        )
+    }
+
+    pub fn placeholder(&self) -> String {
+        format!("e.g: {}", self.few_shot_example().0)
+    }
+
+    // Example subtitle and answer shown to the model before the real subtitle.
+    // All shipped pairs explain in English.
+    pub fn few_shot_example(&self) -> (&'static str, serde_json::Value) {
+        match self.source {
+            Language::Korean | Language::English => korean_example(),
+            Language::Japanese => japanese_example(),
+            Language::Mandarin => mandarin_example(),
+            Language::Spanish => spanish_example(),
+        }
+    }
+}
+
+fn korean_example() -> (&'static str, serde_json::Value) {
+    (
+        "사전을 못 찾아",
+        json!({
+            "translation": "I can't find the dictionary",
+            "vocabulary": [
+                {
+                    "word": "사전",
+                    "translation": "dictionary",
+                    "lemma": "사전",
+                    "part_of_speech": "noun",
+                    "romanization": "sajeon",
+                    "hanja": "辭典",
+                    "register": "neutral",
+                    "example": "사전에서 단어를 찾았어요. (I looked the word up in the dictionary.)"
+                },
+                {
+                    "word": "못",
+                    "translation": "not",
+                    "lemma": "못",
+                    "part_of_speech": "adverb",
+                    "romanization": "mot",
+                    "hanja": null,
+                    "register": "neutral",
+                    "example": "오늘은 못 가요. (I can't go today.)"
+                },
+                {
+                    "word": "찾아",
+                    "translation": "find",
+                    "lemma": "찾다",
+                    "part_of_speech": "verb",
+                    "romanization": "chaja",
+                    "hanja": null,
+                    "register": "casual",
+                    "example": "열쇠를 찾고 있어요. (I'm looking for my keys.)"
+                }
+            ],
+            "grammar_points": [
+                {
+                    "name": "Object Marker",
+                    "relevant_text": "사전을",
+                    "description": "The suffix \"을\" is the object marker, indicating that \"dictionary\" is the object of the verb."
+                },
+                {
+                    "name": "Negative Verb Form",
+                    "relevant_text": "못 찾아",
+                    "description": "\"못\" is used to indicate inability or impossibility, similar to \"cannot\" in English. \"찾아\" is the verb stem of \"찾다,\" which means \"to find.\" Together, \"못 찾아\" means \"cannot find.\""
+                }
+            ]
+        }),
+    )
+}
+
+fn japanese_example() -> (&'static str, serde_json::Value) {
+    (
+        "辞書が見つからない",
+        json!({
+            "translation": "I can't find the dictionary",
+            "vocabulary": [
+                {
+                    "word": "辞書",
+                    "translation": "dictionary",
+                    "lemma": "辞書",
+                    "part_of_speech": "noun",
+                    "romanization": "jisho",
+                    "hanja": null,
+                    "register": "neutral",
+                    "example": "辞書で調べます。 (I'll look it up in the dictionary.)"
+                },
+                {
+                    "word": "見つからない",
+                    "translation": "can't be found",
+                    "lemma": "見つかる",
+                    "part_of_speech": "verb",
+                    "romanization": "mitsukaranai",
+                    "hanja": null,
+                    "register": "casual",
+                    "example": "鍵が見つかった。 (I found the key.)"
+                }
+            ],
+            "grammar_points": [
+                {
+                    "name": "Subject Marker",
+                    "relevant_text": "辞書が",
+                    "description": "\"が\" marks \"辞書\" (dictionary) as the subject of the intransitive verb \"見つかる\" (to be found)."
+                },
+                {
+                    "name": "Plain Negative Form",
+                    "relevant_text": "見つからない",
+                    "description": "\"見つからない\" is the plain negative of \"見つかる\". The う-verb ending \"る\" changes to \"ら\" before \"ない\". Literally \"the dictionary is not found\", it is the natural way to say \"I can't find it\"."
+                }
+            ]
+        }),
+    )
+}
+
+fn mandarin_example() -> (&'static str, serde_json::Value) {
+    (
+        "我找不到字典",
+        json!({
+            "translation": "I can't find the dictionary",
+            "vocabulary": [
+                {
+                    "word": "我",
+                    "translation": "I",
+                    "lemma": "我",
+                    "part_of_speech": "pronoun",
+                    "romanization": "wǒ",
+                    "hanja": null,
+                    "register": "neutral",
+                    "example": "我是学生。 (I am a student.)"
+                },
+                {
+                    "word": "找",
+                    "translation": "to look for",
+                    "lemma": "找",
+                    "part_of_speech": "verb",
+                    "romanization": "zhǎo",
+                    "hanja": null,
+                    "register": "neutral",
+                    "example": "你在找什么？ (What are you looking for?)"
+                },
+                {
+                    "word": "字典",
+                    "translation": "dictionary",
+                    "lemma": "字典",
+                    "part_of_speech": "noun",
+                    "romanization": "zìdiǎn",
+                    "hanja": null,
+                    "register": "neutral",
+                    "example": "这本字典很有用。 (This dictionary is very useful.)"
+                }
+            ],
+            "grammar_points": [
+                {
+                    "name": "Potential Complement",
+                    "relevant_text": "找不到",
+                    "description": "Putting \"不\" between a verb and its result complement (\"找\" + \"到\") expresses inability: \"找到\" means \"to find\", so \"找不到\" means \"cannot find\"."
+                }
+            ]
+        }),
+    )
+}
+
+fn spanish_example() -> (&'static str, serde_json::Value) {
+    (
+        "No encuentro el diccionario",
+        json!({
+            "translation": "I can't find the dictionary",
+            "vocabulary": [
+                {
+                    "word": "encuentro",
+                    "translation": "I find",
+                    "lemma": "encontrar",
+                    "part_of_speech": "verb",
+                    "romanization": null,
+                    "hanja": null,
+                    "register": "neutral",
+                    "example": "No encuentro mis llaves. (I can't find my keys.)"
+                },
+                {
+                    "word": "diccionario",
+                    "translation": "dictionary",
+                    "lemma": "diccionario",
+                    "part_of_speech": "noun",
+                    "romanization": null,
+                    "hanja": null,
+                    "register": "neutral",
+                    "example": "Busca la palabra en el diccionario. (Look the word up in the dictionary.)"
+                }
+            ],
+            "grammar_points": [
+                {
+                    "name": "Stem-changing Verb",
+                    "relevant_text": "encuentro",
+                    "description": "\"Encontrar\" changes its stem from \"o\" to \"ue\" in stressed forms, so the first person present is \"encuentro\"."
+                },
+                {
+                    "name": "Negation with \"no\"",
+                    "relevant_text": "No encuentro",
+                    "description": "Placing \"no\" before the verb negates it. Spanish uses the plain present \"no encuentro\" where English says \"I can't find\"."
+                }
+            ]
+        }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids_round_trip() {
+        for language_pair in LanguagePair::SUPPORTED {
+            assert_eq!(LanguagePair::from_id(&language_pair.id()), Some(language_pair));
+        }
+        assert_eq!(LanguagePair::default().id(), "ko-en");
+        assert_eq!(LanguagePair::from_id("ko"), None);
+        assert_eq!(LanguagePair::from_id("fr-en"), None);
+    }
+
+    #[test]
+    fn serializes_as_its_id() {
+        let language_pair = LanguagePair::SUPPORTED[1];
+        assert_eq!(serde_json::to_value(language_pair).unwrap(), json!("ja-en"));
+        assert_eq!(serde_json::from_value::<LanguagePair>(json!("ja-en")).unwrap(), language_pair);
+        assert!(serde_json::from_value::<LanguagePair>(json!("xx-en")).is_err());
+    }
+
+    #[test]
+    fn only_the_shipped_pairs_are_supported() {
+        assert!(LanguagePair::default().is_supported());
+        let reversed = LanguagePair {
+            source: Language::English,
+            explanation: Language::Korean,
+        };
+        assert!(!reversed.is_supported());
+    }
+
+    #[test]
+    fn system_prompt_names_both_languages() {
+        for language_pair in LanguagePair::SUPPORTED {
+            let prompt = language_pair.system_prompt();
+            assert!(prompt.contains(language_pair.source.name()), "{}", prompt);
+            assert!(prompt.contains(language_pair.explanation.name()), "{}", prompt);
+        }
+    }
+
+    #[cfg(feature = "ssr")]
+    #[test]
+    fn few_shot_examples_are_valid_answers() {
+        use crate::ai_interface::parsing::{parse_translation, ParsePath};
+
+        for language_pair in LanguagePair::SUPPORTED {
+            let (example_input, example_output) = language_pair.few_shot_example();
+            let (info, parse_path) = parse_translation(&example_output.to_string()).unwrap();
+
+            assert_eq!(parse_path, ParsePath::Direct);
+            assert!(!info.vocabulary.is_empty() && !info.grammar_points.is_empty());
+            for grammar_point in &info.grammar_points {
+                assert!(example_input.contains(&grammar_point.relevant_text), "{}", grammar_point.relevant_text);
+            }
+        }
+    }
+}
@@ -1,6 +1,21 @@
 pub mod app;
+pub mod batch;
+pub mod config;
+pub mod languages;
+#[cfg(feature = "ssr")]
 pub mod context;
 mod ai_interface;
+#[cfg(feature = "ssr")]
+pub mod mock_openai;
+#[cfg(feature = "ssr")]
+pub mod rate_limit;
+#[cfg(feature = "ssr")]
+pub mod session;
+pub mod romanization;
+pub mod translation_cache;
+pub mod subtitles;
+pub mod usage;
+pub mod vocabulary;
 
 #[cfg(feature = "hydrate")]
 #[wasm_bindgen::prelude::wasm_bindgen]
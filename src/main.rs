@@ -3,11 +3,16 @@
 #[tokio::main]
 async fn main() {
     use axum::Router;
-    use leptos::logging::log;
+    use std::net::SocketAddr;
+    use leptos::logging::{error, log};
     use leptos::prelude::*;
     use leptos_axum::{generate_route_list, LeptosRoutes};
     use dramastudytool::app::*;
+    use dramastudytool::config::AppConfig;
     use dramastudytool::context::DramaStudyToolAppContext;
+    use dramastudytool::rate_limit::rate_limit_middleware;
+    use dramastudytool::session::{session_middleware, SessionKey};
+    use std::sync::Arc;
 
     let conf = get_configuration(None).unwrap();
     let addr = conf.leptos_options.site_addr;
@@ -15,7 +20,20 @@ async fn main() {
     // Generate the list of routes in your Leptos App
     let routes = generate_route_list(App);
 
-    let my_context = DramaStudyToolAppContext::new();
+    let (app_config, config_problems) = AppConfig::load();
+    for problem in &config_problems {
+        error!("Configuration problem: {}", problem);
+    }
+    if config_problems.iter().any(|problem| problem.disables_translation()) {
+        if app_config.require_translation {
+            error!("Refusing to start because REQUIRE_TRANSLATION is set and the configuration is invalid");
+            std::process::exit(1);
+        }
+        error!("Starting with translation disabled");
+    }
+
+    let my_context = DramaStudyToolAppContext::new(&app_config, &config_problems);
+    let session_key = Arc::new(SessionKey::new(app_config.session.secret.as_deref()));
 
     let app = Router::new()
         .leptos_routes_with_context(&leptos_options, routes,
@@ -31,13 +49,20 @@ async fn main() {
         },
     )
         .fallback(leptos_axum::file_and_error_handler(shell))
+        // Layers added last run first, so the session is known when requests are rate limited
+        .layer(axum::middleware::from_fn_with_state(
+            my_context.request_limits.clone(),
+            rate_limit_middleware,
+        ))
+        .layer(axum::middleware::from_fn_with_state(session_key, session_middleware))
         .with_state(leptos_options);
 
     // run our app with hyper
     // `axum::Server` is a re-export of `hyper::Server`
     log!("listening on http://{}", &addr);
     let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
-    axum::serve(listener, app.into_make_service())
+    // Connection info gives the rate limiter the client's address
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
         .await
         .unwrap();
 }
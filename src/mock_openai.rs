@@ -0,0 +1,261 @@
+use axum::response::sse::{Event, Sse};
+use axum::response::IntoResponse;
+use axum::{routing::post, Json, Router};
+use serde::Deserialize;
+use serde_json::json;
+use std::convert::Infallible;
+use std::sync::Arc;
+
+use crate::ai_interface::providers::{stream_chunks, ChatMessage, FixtureProvider};
+use crate::usage::TokenUsage;
+
+// Local stand-in for the OpenAI and Azure OpenAI chat completion APIs, answering from a fixture file.
+// Point OPENAI_API_URL at it to run the app (and the end2end tests) without network access.
+
+#[derive(Debug, Deserialize)]
+struct MockChatRequest {
+    messages: Vec<ChatMessage>,
+    #[serde(default)]
+    stream: bool,
+    #[serde(default)]
+    stream_options: Option<MockStreamOptions>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MockStreamOptions {
+    #[serde(default)]
+    include_usage: bool,
+}
+
+pub fn router(fixtures: FixtureProvider) -> Router {
+    let fixtures = Arc::new(fixtures);
+
+    let handler = move |Json(request): Json<MockChatRequest>| {
+        let fixtures = fixtures.clone();
+        async move {
+            if request.stream {
+                let include_usage = request.stream_options.map(|options| options.include_usage).unwrap_or(false);
+                mock_completion_stream(&fixtures, &request.messages, include_usage).into_response()
+            } else {
+                Json(mock_completion(&fixtures, &request.messages)).into_response()
+            }
+        }
+    };
+
+    Router::new()
+        // OpenAI
+        .route("/chat/completions", post(handler.clone()))
+        .route("/v1/chat/completions", post(handler.clone()))
+        // Azure OpenAI
+        .route("/openai/deployments/:deployment/chat/completions", post(handler))
+}
+
+pub fn load_fixtures(fixture_file: Option<&str>) -> FixtureProvider {
+    match fixture_file {
+        Some(path) => FixtureProvider::from_file(path).unwrap_or_else(|e| {
+            leptos::logging::error!("{}", e);
+            FixtureProvider::new()
+        }),
+        None => FixtureProvider::new(),
+    }
+}
+
+fn last_user_message(messages: &[ChatMessage]) -> String {
+    messages
+        .iter()
+        .rev()
+        .find(|message| message.role == "user")
+        .map(|message| message.content.clone())
+        .unwrap_or_default()
+}
+
+// Real token counts depend on the model's tokenizer, the estimate is close enough to exercise usage accounting
+fn mock_usage(messages: &[ChatMessage], content: &str) -> serde_json::Value {
+    let prompt_characters = messages.iter().map(|message| message.content.chars().count()).sum();
+    let usage = TokenUsage::estimate(prompt_characters, content.chars().count());
+    json!({
+        "prompt_tokens": usage.prompt_tokens,
+        "completion_tokens": usage.completion_tokens,
+        "total_tokens": usage.total_tokens()
+    })
+}
+
+fn mock_completion(fixtures: &FixtureProvider, messages: &[ChatMessage]) -> serde_json::Value {
+    let content = fixtures.response_for(&last_user_message(messages)).to_string();
+
+    json!({
+        "id": "chatcmpl-mock",
+        "object": "chat.completion",
+        "model": "mock",
+        "choices": [
+            {
+                "index": 0,
+                "message": {
+                    "role": "assistant",
+                    "content": content
+                },
+                "finish_reason": "stop"
+            }
+        ],
+        "usage": mock_usage(messages, &content)
+    })
+}
+
+// The answer as server-sent chat completion chunks, ending with [DONE] like the real APIs.
+// With include_usage the usage comes in a last chunk without choices, as OpenAI sends it.
+fn mock_completion_stream(
+    fixtures: &FixtureProvider,
+    messages: &[ChatMessage],
+    include_usage: bool,
+) -> Sse<impl futures::Stream<Item = Result<Event, Infallible>>> {
+    let content = fixtures.response_for(&last_user_message(messages)).to_string();
+
+    let events: Vec<Event> = stream_chunks(&content)
+        .into_iter()
+        .map(|chunk| {
+            let chunk_body = json!({
+                "id": "chatcmpl-mock",
+                "object": "chat.completion.chunk",
+                "model": "mock",
+                "choices": [
+                    {
+                        "index": 0,
+                        "delta": {
+                            "content": chunk
+                        },
+                        "finish_reason": null
+                    }
+                ]
+            });
+            Event::default().data(chunk_body.to_string())
+        })
+        .chain(include_usage.then(|| {
+            let usage_body = json!({
+                "id": "chatcmpl-mock",
+                "object": "chat.completion.chunk",
+                "model": "mock",
+                "choices": [],
+                "usage": mock_usage(messages, &content)
+            });
+            Event::default().data(usage_body.to_string())
+        }))
+        .chain(std::iter::once(Event::default().data("[DONE]")))
+        .collect();
+
+    Sse::new(futures::stream::iter(events.into_iter().map(Ok)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai_interface::providers::{
+        AzureOpenAIConfig, AzureOpenAIProvider, ChatRequest, CompletionChunk, OpenAICompatibleProvider,
+        TranslationProvider,
+    };
+    use crate::languages::LanguagePair;
+    use futures::StreamExt;
+
+    fn messages(user_messages: &[&str]) -> Vec<ChatMessage> {
+        let mut messages = vec![ChatMessage::new("system", "Translate the subtitle.")];
+        messages.extend(user_messages.iter().map(|content| ChatMessage::new("user", content)));
+        messages
+    }
+
+    fn answer(completion: &serde_json::Value) -> serde_json::Value {
+        serde_json::from_str(completion["choices"][0]["message"]["content"].as_str().unwrap()).unwrap()
+    }
+
+    // Serves the mock on a free local port, returns its URL
+    async fn serve(fixtures: FixtureProvider) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, router(fixtures).into_make_service()).await.unwrap() });
+        url
+    }
+
+    #[test]
+    fn completions_have_the_chat_completion_shape() {
+        let (example_input, example_output) = LanguagePair::default().few_shot_example();
+        let completion = mock_completion(&FixtureProvider::new(), &messages(&[example_input]));
+
+        assert_eq!(completion["object"], "chat.completion");
+        assert_eq!(completion["choices"][0]["message"]["role"], "assistant");
+        assert_eq!(completion["choices"][0]["finish_reason"], "stop");
+        assert_eq!(answer(&completion), example_output);
+
+        let usage = &completion["usage"];
+        let (prompt_tokens, completion_tokens) = (usage["prompt_tokens"].as_u64(), usage["completion_tokens"].as_u64());
+        assert!(prompt_tokens.unwrap() > 0 && completion_tokens.unwrap() > 0);
+        assert_eq!(usage["total_tokens"].as_u64(), Some(prompt_tokens.unwrap() + completion_tokens.unwrap()));
+    }
+
+    #[test]
+    fn answers_the_last_user_message_from_the_fixture_file() {
+        let path = std::env::temp_dir().join(format!("mock-openai-fixtures-{}.json", std::process::id()));
+        std::fs::write(&path, r#"{"안녕": {"translation": "Hi", "vocabulary": [], "grammar_points": []}}"#).unwrap();
+        let fixtures = load_fixtures(path.to_str());
+        std::fs::remove_file(&path).ok();
+
+        // The few-shot example comes before the subtitle
+        let (example_input, example_output) = LanguagePair::default().few_shot_example();
+        let completion = mock_completion(&fixtures, &messages(&[example_input, " 안녕 "]));
+        assert_eq!(answer(&completion)["translation"], "Hi");
+
+        let completion = mock_completion(&fixtures, &messages(&["잘 가"]));
+        assert_eq!(answer(&completion)["translation"], "[fixture] 잘 가");
+
+        // Without a readable fixture file the few-shot examples are answered
+        let fixtures = load_fixtures(Some("no/such/fixtures.json"));
+        assert_eq!(answer(&mock_completion(&fixtures, &messages(&[example_input]))), example_output);
+    }
+
+    #[tokio::test]
+    async fn openai_clients_get_the_answer_whole_and_streamed() {
+        let url = serve(FixtureProvider::new()).await;
+        let provider = OpenAICompatibleProvider::new(format!("{}/v1", url), String::new(), "mock".to_string());
+        let (example_input, example_output) = LanguagePair::default().few_shot_example();
+        let request = ChatRequest {
+            messages: messages(&[example_input]),
+            max_tokens: 2000,
+            response_schema: None,
+        };
+
+        let completion = provider.complete(&request).await.unwrap();
+        assert_eq!(completion.text, example_output.to_string());
+        assert!(completion.usage.is_some());
+
+        let chunks: Vec<CompletionChunk> =
+            provider.complete_stream(&request).await.unwrap().map(Result::unwrap).collect().await;
+        let text: String = chunks
+            .iter()
+            .filter_map(|chunk| match chunk {
+                CompletionChunk::Text(text) => Some(text.as_str()),
+                CompletionChunk::Usage(_) => None,
+            })
+            .collect();
+        assert!(chunks.len() > 2);
+        assert_eq!(text, completion.text);
+        // Asked for with stream_options, the usage comes last
+        assert!(matches!(chunks.last(), Some(CompletionChunk::Usage(usage)) if Some(*usage) == completion.usage));
+    }
+
+    #[tokio::test]
+    async fn azure_clients_are_answered_on_the_deployment_route() {
+        let url = serve(FixtureProvider::new()).await;
+        let provider = AzureOpenAIProvider::new(AzureOpenAIConfig {
+            api_key: "key".to_string(),
+            endpoint: url,
+            deployment: "gpt-4o".to_string(),
+            api_version: "2024-08-01-preview".to_string(),
+        });
+        let request = ChatRequest {
+            messages: messages(&["잘 가"]),
+            max_tokens: 2000,
+            response_schema: None,
+        };
+
+        let completion = provider.complete(&request).await.unwrap();
+        let answer: serde_json::Value = serde_json::from_str(&completion.text).unwrap();
+        assert_eq!(answer["translation"], "[fixture] 잘 가");
+    }
+}
@@ -0,0 +1,495 @@
+use axum::extract::{ConnectInfo, Request, State};
+use axum::http::header::RETRY_AFTER;
+use axum::http::{HeaderValue, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Extension;
+use leptos::server_fn::error::{NoCustomError, ServerFnError, ServerFnErrorSerde};
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::ai_interface::{TranslateError, TranslationContext, TranslationStreamEvent};
+use crate::config::LimitsConfig;
+use crate::session::SessionId;
+
+// Buckets are dropped once they have refilled and there are more than this many
+const MAX_IDLE_BUCKETS: usize = 10_000;
+
+// Throttles the server functions that call the model, per client IP and per session, and enforces the allowlist
+#[derive(Debug)]
+pub struct RequestLimits {
+    max_input_characters: usize,
+    max_context_lines: usize,
+    max_context_characters: usize,
+    per_ip: KeyedRateLimiter<IpAddr>,
+    per_session: KeyedRateLimiter<String>,
+    // Pre-translating a file calls the model once per cue, so batches are charged per cue from their own buckets
+    batch_per_ip: KeyedRateLimiter<IpAddr>,
+    batch_per_session: KeyedRateLimiter<String>,
+    allowlist: Vec<IpRange>,
+    trust_forwarded_for: bool,
+    limited_paths: Vec<&'static str>,
+    // Limited paths that stream TranslationStreamEvents, their errors are sent as a Failed event
+    streaming_paths: Vec<&'static str>,
+}
+
+impl RequestLimits {
+    pub fn new(config: &LimitsConfig, limited_paths: Vec<&'static str>) -> Self {
+        Self {
+            max_input_characters: config.max_input_characters,
+            max_context_lines: config.max_context_lines,
+            max_context_characters: config.max_context_characters,
+            per_ip: KeyedRateLimiter::new(config.ip_requests_per_minute, config.ip_burst),
+            per_session: KeyedRateLimiter::new(config.session_requests_per_minute, config.session_burst),
+            batch_per_ip: KeyedRateLimiter::new(config.batch_cues_per_hour.div_ceil(60), config.batch_cues_per_hour),
+            batch_per_session: KeyedRateLimiter::new(
+                config.batch_cues_per_hour.div_ceil(60),
+                config.batch_cues_per_hour,
+            ),
+            allowlist: config.allowlist.iter().filter_map(|entry| IpRange::parse(entry)).collect(),
+            trust_forwarded_for: config.trust_forwarded_for,
+            limited_paths,
+            streaming_paths: Vec::new(),
+        }
+    }
+
+    pub fn with_streaming_paths(mut self, streaming_paths: Vec<&'static str>) -> Self {
+        self.streaming_paths = streaming_paths;
+        self
+    }
+
+    pub fn check_input(&self, input_text: &str) -> Result<(), TranslateError> {
+        if input_text.trim().is_empty() {
+            return Err(TranslateError::EmptyInput);
+        }
+        if input_text.chars().count() > self.max_input_characters {
+            return Err(TranslateError::InputTooLong {
+                max_characters: self.max_input_characters,
+            });
+        }
+        Ok(())
+    }
+
+    pub fn check_context(&self, context: Option<&TranslationContext>) -> Result<(), TranslateError> {
+        let Some(context) = context else {
+            return Ok(());
+        };
+        let lines = context.previous_lines.iter().chain(&context.next_lines);
+        let characters: usize = lines
+            .clone()
+            .chain(&context.title)
+            .chain(&context.reference_translation)
+            .map(|text| text.chars().count())
+            .sum();
+        if lines.count() > self.max_context_lines || characters > self.max_context_characters {
+            return Err(TranslateError::ContextTooLong {
+                max_lines: self.max_context_lines,
+                max_characters: self.max_context_characters,
+            });
+        }
+        Ok(())
+    }
+
+    // Charges a batch of cues to the client's batch budget, rejecting batches larger than the whole budget
+    pub fn check_batch(&self, client_ip: Option<IpAddr>, session_id: &str, cues: usize) -> Result<(), TranslateError> {
+        let cues = u32::try_from(cues).unwrap_or(u32::MAX);
+        if self.batch_per_session.is_enabled() && cues > self.batch_per_session.burst {
+            return Err(TranslateError::Other(format!(
+                "at most {} cues can be pre-translated per hour, this subtitle file has {}",
+                self.batch_per_session.burst, cues
+            )));
+        }
+
+        if let Some(ip) = client_ip {
+            self.batch_per_ip.take(ip, cues).map_err(too_many_requests)?;
+        }
+        if let Err(wait) = self.batch_per_session.take(session_id.to_string(), cues) {
+            if let Some(ip) = client_ip {
+                self.batch_per_ip.give_back(&ip, cues);
+            }
+            return Err(too_many_requests(wait));
+        }
+        Ok(())
+    }
+
+    fn check_request(&self, client_ip: Option<IpAddr>, session_id: Option<&str>) -> Result<(), TranslateError> {
+        if !self.allowlist.is_empty() {
+            let allowed = client_ip
+                .map(|ip| self.allowlist.iter().any(|range| range.contains(ip)))
+                .unwrap_or(false);
+            if !allowed {
+                return Err(TranslateError::NotAllowed);
+            }
+        }
+
+        if let Some(ip) = client_ip {
+            self.per_ip.check(ip).map_err(too_many_requests)?;
+        }
+        if let Some(session_id) = session_id {
+            self.per_session.check(session_id.to_string()).map_err(too_many_requests)?;
+        }
+        Ok(())
+    }
+
+    fn client_ip(&self, request: &Request) -> Option<IpAddr> {
+        // The proxy appends the address it saw, so the last entry is the one that can't be spoofed
+        let forwarded_for = request
+            .headers()
+            .get("x-forwarded-for")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.rsplit(',').next())
+            .and_then(|ip| parse_ip(ip.trim()))
+            .filter(|_| self.trust_forwarded_for);
+
+        forwarded_for.or_else(|| {
+            request
+                .extensions()
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(address)| address.ip())
+        })
+    }
+}
+
+fn too_many_requests(wait: Duration) -> TranslateError {
+    TranslateError::TooManyRequests {
+        retry_after_secs: wait.as_secs_f64().ceil() as u64,
+    }
+}
+
+// The address a limited request was rate limited by, for server functions that charge more than one request
+#[derive(Clone, Copy, Debug)]
+pub struct ClientIp(pub IpAddr);
+
+pub async fn current_client_ip() -> Option<IpAddr> {
+    leptos_axum::extract::<Extension<ClientIp>>().await.ok().map(|Extension(ClientIp(ip))| ip)
+}
+
+// Middleware for the limited server functions. Must run inside the session middleware to limit per session.
+pub async fn rate_limit_middleware(
+    State(limits): State<Arc<RequestLimits>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    if !limits.limited_paths.contains(&request.uri().path()) {
+        return next.run(request).await;
+    }
+
+    let client_ip = limits.client_ip(&request);
+    let session_id = request.extensions().get::<SessionId>().map(|SessionId(session_id)| session_id.as_str());
+
+    if let Err(e) = limits.check_request(client_ip, session_id) {
+        return error_response(e, limits.streaming_paths.contains(&request.uri().path()));
+    }
+    if let Some(ip) = client_ip {
+        request.extensions_mut().insert(ClientIp(ip));
+    }
+    next.run(request).await
+}
+
+// Encoded like an error returned by the server function itself, so the client gets the TranslateError back.
+// Streaming server functions can't return a TranslateError, they get it as the line of a Failed event instead.
+fn error_response(error: TranslateError, streaming: bool) -> Response {
+    let (status, retry_after_secs) = match &error {
+        TranslateError::TooManyRequests { retry_after_secs } => {
+            (StatusCode::TOO_MANY_REQUESTS, Some(*retry_after_secs))
+        }
+        _ => (StatusCode::FORBIDDEN, None),
+    };
+
+    let body = if streaming {
+        let event = TranslationStreamEvent::Failed(error).to_line();
+        ServerFnError::<NoCustomError>::ServerError(event.trim_end().to_string()).ser()
+    } else {
+        ServerFnError::WrappedServerError(error).ser()
+    };
+    let mut response = (status, body.unwrap_or_default()).into_response();
+    if let Some(value) = retry_after_secs.and_then(|secs| HeaderValue::from_str(&secs.to_string()).ok()) {
+        response.headers_mut().insert(RETRY_AFTER, value);
+    }
+    response
+}
+
+// X-Forwarded-For entries can have a port, e.g. Azure App Service sends 203.0.113.7:52144
+fn parse_ip(value: &str) -> Option<IpAddr> {
+    value
+        .parse::<IpAddr>()
+        .ok()
+        .or_else(|| value.parse::<SocketAddr>().ok().map(|address| address.ip()))
+}
+
+// An IP address or CIDR range from the allowlist
+#[derive(Clone, Copy, Debug)]
+pub struct IpRange {
+    network: IpAddr,
+    prefix_length: u32,
+}
+
+impl IpRange {
+    pub fn parse(value: &str) -> Option<Self> {
+        let (address, prefix_length) = match value.trim().split_once('/') {
+            Some((address, prefix_length)) => (address, Some(prefix_length.parse::<u32>().ok()?)),
+            None => (value.trim(), None),
+        };
+        let network = address.parse::<IpAddr>().ok()?;
+        let max_prefix_length = if network.is_ipv4() { 32 } else { 128 };
+        let prefix_length = prefix_length.unwrap_or(max_prefix_length);
+        if prefix_length > max_prefix_length {
+            return None;
+        }
+
+        Some(Self {
+            network,
+            prefix_length,
+        })
+    }
+
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.network, ip) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix_length).unwrap_or(0);
+                u32::from(network) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix_length).unwrap_or(0);
+                u128::from(network) & mask == u128::from(ip) & mask
+            }
+            // IPv4 clients of a dual stack listener show up as IPv4-mapped IPv6 addresses
+            (IpAddr::V4(_), IpAddr::V6(ip)) => match ip.to_ipv4_mapped() {
+                Some(ip) => self.contains(IpAddr::V4(ip)),
+                None => false,
+            },
+            (IpAddr::V6(_), IpAddr::V4(_)) => false,
+        }
+    }
+}
+
+// A token bucket per key, refilled continuously at requests_per_minute up to burst
+#[derive(Debug)]
+struct KeyedRateLimiter<K> {
+    requests_per_minute: u32,
+    burst: u32,
+    buckets: Mutex<HashMap<K, TokenBucket>>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct TokenBucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+impl<K: Hash + Eq> KeyedRateLimiter<K> {
+    fn new(requests_per_minute: u32, burst: u32) -> Self {
+        Self {
+            requests_per_minute,
+            burst: burst.max(1),
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.requests_per_minute != 0
+    }
+
+    // Takes a token, or returns how long until the next one is available
+    fn check(&self, key: K) -> Result<(), Duration> {
+        self.take(key, 1)
+    }
+
+    // Takes `tokens` tokens at once, or returns how long until that many are available
+    fn take(&self, key: K, tokens: u32) -> Result<(), Duration> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+
+        let now = Instant::now();
+        let refill_per_sec = self.requests_per_minute as f64 / 60.0;
+        let burst = self.burst as f64;
+        let refilled = |bucket: &TokenBucket| {
+            (bucket.tokens + now.duration_since(bucket.updated_at).as_secs_f64() * refill_per_sec).min(burst)
+        };
+
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        if buckets.len() > MAX_IDLE_BUCKETS {
+            buckets.retain(|_, bucket| refilled(bucket) < burst);
+        }
+
+        let bucket = buckets.entry(key).or_insert(TokenBucket {
+            tokens: burst,
+            updated_at: now,
+        });
+        bucket.tokens = refilled(bucket);
+        bucket.updated_at = now;
+
+        let tokens = tokens as f64;
+        if bucket.tokens >= tokens {
+            bucket.tokens -= tokens;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((tokens - bucket.tokens) / refill_per_sec))
+        }
+    }
+
+    // Returns tokens taken for a request that was then refused for another reason
+    fn give_back(&self, key: &K, tokens: u32) {
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(bucket) = buckets.get_mut(key) {
+            bucket.tokens = (bucket.tokens + tokens as f64).min(self.burst as f64);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+
+    fn ip(value: &str) -> IpAddr {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn ranges_contain_their_addresses() {
+        let range = IpRange::parse("192.168.1.0/24").unwrap();
+        assert!(range.contains(ip("192.168.1.200")));
+        assert!(!range.contains(ip("192.168.2.1")));
+        assert!(range.contains(ip("::ffff:192.168.1.7")));
+        assert!(!range.contains(ip("fe80::1")));
+
+        let single = IpRange::parse(" 203.0.113.7 ").unwrap();
+        assert!(single.contains(ip("203.0.113.7")));
+        assert!(!single.contains(ip("203.0.113.8")));
+        assert!(IpRange::parse("0.0.0.0/0").unwrap().contains(ip("8.8.8.8")));
+
+        let range = IpRange::parse("2001:db8::/32").unwrap();
+        assert!(range.contains(ip("2001:db8:1234::1")));
+        assert!(!range.contains(ip("2001:db9::1")));
+        assert!(!range.contains(ip("10.0.0.1")));
+    }
+
+    #[test]
+    fn invalid_ranges_are_rejected() {
+        assert!(IpRange::parse("10.0.0.0/33").is_none());
+        assert!(IpRange::parse("10.0.0.0/x").is_none());
+        assert!(IpRange::parse("example.com").is_none());
+    }
+
+    #[test]
+    fn forwarded_addresses_may_have_a_port() {
+        assert_eq!(parse_ip("203.0.113.7"), Some(ip("203.0.113.7")));
+        assert_eq!(parse_ip("203.0.113.7:52144"), Some(ip("203.0.113.7")));
+        assert_eq!(parse_ip("[2001:db8::1]:443"), Some(ip("2001:db8::1")));
+        assert_eq!(parse_ip("2001:db8::1"), Some(ip("2001:db8::1")));
+        assert_eq!(parse_ip("unknown"), None);
+    }
+
+    fn request(forwarded_for: Option<&str>) -> Request {
+        let mut request = Request::new(Body::empty());
+        if let Some(forwarded_for) = forwarded_for {
+            request.headers_mut().insert("x-forwarded-for", HeaderValue::from_str(forwarded_for).unwrap());
+        }
+        request.extensions_mut().insert(ConnectInfo(SocketAddr::new(ip("10.0.0.1"), 50000)));
+        request
+    }
+
+    #[test]
+    fn forwarded_for_is_only_used_when_trusted() {
+        let untrusted = RequestLimits::new(&LimitsConfig::default(), Vec::new());
+        assert_eq!(untrusted.client_ip(&request(Some("203.0.113.7"))), Some(ip("10.0.0.1")));
+
+        let trusted = RequestLimits::new(
+            &LimitsConfig {
+                trust_forwarded_for: true,
+                ..LimitsConfig::default()
+            },
+            Vec::new(),
+        );
+        // The client can put anything first, only the address the proxy appended counts
+        assert_eq!(trusted.client_ip(&request(Some("1.2.3.4, 203.0.113.7:52144"))), Some(ip("203.0.113.7")));
+        assert_eq!(trusted.client_ip(&request(Some("garbage"))), Some(ip("10.0.0.1")));
+        assert_eq!(trusted.client_ip(&request(None)), Some(ip("10.0.0.1")));
+    }
+
+    async fn body_text(response: Response) -> String {
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn errors_are_encoded_for_the_server_function_they_stop() {
+        let error = TranslateError::TooManyRequests { retry_after_secs: 42 };
+
+        let response = error_response(error.clone(), false);
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[RETRY_AFTER], "42");
+        let decoded = ServerFnError::<TranslateError>::de(&body_text(response).await);
+        assert_eq!(decoded, ServerFnError::WrappedServerError(error.clone()));
+
+        // Streaming clients only decode untyped errors, the TranslateError is in the Failed event they carry
+        let response = error_response(error.clone(), true);
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        let ServerFnError::<NoCustomError>::ServerError(line) = ServerFnError::de(&body_text(response).await) else {
+            panic!("expected a server error");
+        };
+        match serde_json::from_str::<TranslationStreamEvent>(&line).unwrap() {
+            TranslationStreamEvent::Failed(failed) => assert_eq!(failed, error),
+            event => panic!("expected a Failed event, got {:?}", event),
+        }
+    }
+
+    #[test]
+    fn bursts_are_allowed_then_limited() {
+        let limiter = KeyedRateLimiter::new(60, 3);
+        for _ in 0..3 {
+            assert!(limiter.check("a").is_ok());
+        }
+        let wait = limiter.check("a").unwrap_err();
+        assert!(wait > Duration::from_millis(900) && wait <= Duration::from_secs(1));
+        // Keys have their own buckets
+        assert!(limiter.check("b").is_ok());
+    }
+
+    #[test]
+    fn buckets_refill_over_time() {
+        // 100 tokens a second
+        let limiter = KeyedRateLimiter::new(6000, 1);
+        assert!(limiter.check("a").is_ok());
+        assert!(limiter.check("a").is_err());
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(limiter.check("a").is_ok());
+    }
+
+    #[test]
+    fn large_takes_wait_for_every_token_and_can_be_given_back() {
+        let limiter = KeyedRateLimiter::new(60, 10);
+        assert!(limiter.take("a", 8).is_ok());
+        let wait = limiter.take("a", 5).unwrap_err();
+        assert!(wait > Duration::from_millis(2900) && wait <= Duration::from_secs(3));
+
+        limiter.give_back(&"a", 8);
+        assert!(limiter.take("a", 10).is_ok());
+    }
+
+    #[test]
+    fn full_buckets_are_evicted() {
+        let limiter = KeyedRateLimiter::new(6_000_000, 1);
+        for key in 0..=MAX_IDLE_BUCKETS {
+            assert!(limiter.check(key).is_ok());
+        }
+        assert_eq!(limiter.buckets.lock().unwrap().len(), MAX_IDLE_BUCKETS + 1);
+
+        std::thread::sleep(Duration::from_millis(10));
+        assert!(limiter.check(MAX_IDLE_BUCKETS + 1).is_ok());
+        assert_eq!(limiter.buckets.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn zero_rate_disables_the_limit() {
+        let limiter = KeyedRateLimiter::new(0, 1);
+        for _ in 0..100 {
+            assert!(limiter.check("a").is_ok());
+        }
+    }
+}
@@ -0,0 +1,252 @@
+mod jamo;
+
+pub use jamo::{compose, decompose, has_hangul, is_hangul, is_syllable, Jamo};
+
+// Revised Romanization of Korean, as the words are pronounced: final consonants carry over to a following vowel
+// and assimilate to the next consonant (국물 gungmul, 신라 silla, 좋다 jota). Tensing isn't written, as in the
+// standard. Everything that isn't Hangul is kept as it is.
+pub fn romanize(text: &str) -> String {
+    let mut romanized = String::with_capacity(text.len());
+    let mut word: Vec<Jamo> = Vec::new();
+    for c in text.chars() {
+        match Jamo::from_syllable(c) {
+            Some(jamo) => word.push(jamo),
+            None => {
+                romanized.push_str(&romanize_word(&word));
+                word.clear();
+                romanized.push(c);
+            }
+        }
+    }
+    romanized.push_str(&romanize_word(&word));
+    romanized
+}
+
+// Sound changes only happen within a word, a run of syllables
+fn romanize_word(syllables: &[Jamo]) -> String {
+    let mut syllables = syllables.to_vec();
+    for idx in 1..syllables.len() {
+        let (before, after) = syllables.split_at_mut(idx);
+        join_syllables(&mut before[idx - 1], &mut after[0]);
+    }
+
+    let mut romanized = String::new();
+    let mut previous_tail = None;
+    for syllable in &syllables {
+        // ㄹ is l after another ㄹ, as in 설날 seollal
+        let lead = match (syllable.lead, previous_tail) {
+            ('ㄹ', Some('ㄹ')) => "l",
+            (lead, _) => romanize_lead(lead),
+        };
+        let tail = syllable.tail.map(neutralize);
+        romanized.push_str(lead);
+        romanized.push_str(romanize_vowel(syllable.vowel));
+        romanized.push_str(tail.map(romanize_tail).unwrap_or_default());
+        previous_tail = tail;
+    }
+    romanized
+}
+
+// Applies the sound changes between a syllable's final consonant and the next syllable's leading consonant
+fn join_syllables(previous: &mut Jamo, next: &mut Jamo) {
+    let Some(tail) = previous.tail else {
+        return;
+    };
+    let (kept, last) = split_tail(tail);
+
+    // Before a vowel the final consonant is pronounced as the next syllable's, ㅎ is silent
+    if next.lead == 'ㅇ' {
+        match last {
+            'ㅇ' => {}
+            // 않아 ana, 싫어 sireo
+            'ㅎ' => {
+                previous.tail = None;
+                next.lead = kept.unwrap_or('ㅇ');
+            }
+            // 같이 gachi, 굳이 guji
+            'ㄷ' | 'ㅌ' if next.vowel == 'ㅣ' && kept.is_none() => {
+                previous.tail = None;
+                next.lead = if last == 'ㄷ' { 'ㅈ' } else { 'ㅊ' };
+            }
+            _ => {
+                previous.tail = kept;
+                next.lead = last;
+            }
+        }
+        return;
+    }
+
+    // ㅎ and a plain consonant next to each other make an aspirated one: 좋고 joko, 못해 motae
+    let (aspirated_tail, aspirated_lead) = match (last, next.lead) {
+        ('ㅎ', lead) if lead != 'ㅅ' => (kept, aspirate(lead)),
+        (last, 'ㅎ') => (kept, aspirate(last)),
+        _ => (None, None),
+    };
+    if let Some(lead) = aspirated_lead {
+        previous.tail = aspirated_tail;
+        next.lead = lead;
+        return;
+    }
+    // 좋습니다 josseumnida
+    if last == 'ㅎ' && next.lead == 'ㅅ' {
+        previous.tail = kept;
+        next.lead = 'ㅆ';
+        return;
+    }
+    // 놓는 non-neun, 싫네 sille
+    let tail = match last {
+        'ㅎ' => kept.unwrap_or('ㄴ'),
+        _ => neutralize(tail),
+    };
+
+    let (tail, lead) = match (tail, next.lead) {
+        // 국물 gungmul, 입니다 imnida
+        ('ㄱ' | 'ㄷ' | 'ㅂ', 'ㄴ' | 'ㅁ') => (nasalize(tail), next.lead),
+        // 종로 jongno, 독립 dongnip
+        ('ㅁ' | 'ㅇ', 'ㄹ') => (tail, 'ㄴ'),
+        ('ㄱ' | 'ㄷ' | 'ㅂ', 'ㄹ') => (nasalize(tail), 'ㄴ'),
+        // 신라 silla, 설날 seollal
+        ('ㄴ', 'ㄹ') => ('ㄹ', 'ㄹ'),
+        ('ㄹ', 'ㄴ') => ('ㄹ', 'ㄹ'),
+        (tail, lead) => (tail, lead),
+    };
+    previous.tail = Some(tail);
+    next.lead = lead;
+}
+
+// A double final consonant as the part that stays and the part that can move on, ㄺ -> (ㄹ, ㄱ)
+fn split_tail(tail: char) -> (Option<char>, char) {
+    match tail {
+        'ㄳ' => (Some('ㄱ'), 'ㅅ'),
+        'ㄵ' => (Some('ㄴ'), 'ㅈ'),
+        'ㄶ' => (Some('ㄴ'), 'ㅎ'),
+        'ㄺ' => (Some('ㄹ'), 'ㄱ'),
+        'ㄻ' => (Some('ㄹ'), 'ㅁ'),
+        'ㄼ' => (Some('ㄹ'), 'ㅂ'),
+        'ㄽ' => (Some('ㄹ'), 'ㅅ'),
+        'ㄾ' => (Some('ㄹ'), 'ㅌ'),
+        'ㄿ' => (Some('ㄹ'), 'ㅍ'),
+        'ㅀ' => (Some('ㄹ'), 'ㅎ'),
+        'ㅄ' => (Some('ㅂ'), 'ㅅ'),
+        tail => (None, tail),
+    }
+}
+
+// The seven sounds a syllable can end in
+fn neutralize(tail: char) -> char {
+    match tail {
+        'ㄱ' | 'ㄲ' | 'ㅋ' | 'ㄳ' | 'ㄺ' => 'ㄱ',
+        'ㄴ' | 'ㄵ' | 'ㄶ' => 'ㄴ',
+        'ㄹ' | 'ㄼ' | 'ㄽ' | 'ㄾ' | 'ㅀ' => 'ㄹ',
+        'ㅁ' | 'ㄻ' => 'ㅁ',
+        'ㅂ' | 'ㅍ' | 'ㅄ' | 'ㄿ' => 'ㅂ',
+        'ㅇ' => 'ㅇ',
+        _ => 'ㄷ',
+    }
+}
+
+fn nasalize(tail: char) -> char {
+    match tail {
+        'ㄱ' => 'ㅇ',
+        'ㄷ' => 'ㄴ',
+        'ㅂ' => 'ㅁ',
+        tail => tail,
+    }
+}
+
+fn aspirate(consonant: char) -> Option<char> {
+    match consonant {
+        'ㄱ' | 'ㄲ' => Some('ㅋ'),
+        'ㄷ' | 'ㅅ' => Some('ㅌ'),
+        'ㅂ' => Some('ㅍ'),
+        'ㅈ' => Some('ㅊ'),
+        _ => None,
+    }
+}
+
+fn romanize_lead(lead: char) -> &'static str {
+    match lead {
+        'ㄱ' => "g",
+        'ㄲ' => "kk",
+        'ㄴ' => "n",
+        'ㄷ' => "d",
+        'ㄸ' => "tt",
+        'ㄹ' => "r",
+        'ㅁ' => "m",
+        'ㅂ' => "b",
+        'ㅃ' => "pp",
+        'ㅅ' => "s",
+        'ㅆ' => "ss",
+        'ㅈ' => "j",
+        'ㅉ' => "jj",
+        'ㅊ' => "ch",
+        'ㅋ' => "k",
+        'ㅌ' => "t",
+        'ㅍ' => "p",
+        'ㅎ' => "h",
+        _ => "",
+    }
+}
+
+fn romanize_vowel(vowel: char) -> &'static str {
+    match vowel {
+        'ㅏ' => "a",
+        'ㅐ' => "ae",
+        'ㅑ' => "ya",
+        'ㅒ' => "yae",
+        'ㅓ' => "eo",
+        'ㅔ' => "e",
+        'ㅕ' => "yeo",
+        'ㅖ' => "ye",
+        'ㅗ' => "o",
+        'ㅘ' => "wa",
+        'ㅙ' => "wae",
+        'ㅚ' => "oe",
+        'ㅛ' => "yo",
+        'ㅜ' => "u",
+        'ㅝ' => "wo",
+        'ㅞ' => "we",
+        'ㅟ' => "wi",
+        'ㅠ' => "yu",
+        'ㅡ' => "eu",
+        'ㅢ' => "ui",
+        _ => "i",
+    }
+}
+
+// Final consonants are only ever one of the seven neutralized sounds
+fn romanize_tail(tail: char) -> &'static str {
+    match tail {
+        'ㄱ' => "k",
+        'ㄴ' => "n",
+        'ㄹ' => "l",
+        'ㅁ' => "m",
+        'ㅂ' => "p",
+        'ㅇ' => "ng",
+        _ => "t",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sound_changes_are_romanized_as_pronounced() {
+        let cases = [
+            ("국물", "gungmul"),
+            ("신라", "silla"),
+            ("좋습니다", "josseumnida"),
+            ("같이", "gachi"),
+            ("설날", "seollal"),
+        ];
+        for (hangul, expected) in cases {
+            assert_eq!(romanize(hangul), expected, "{}", hangul);
+        }
+    }
+
+    #[test]
+    fn other_characters_are_kept_and_split_words() {
+        assert_eq!(romanize("안녕, Bob!"), "annyeong, Bob!");
+    }
+}
@@ -0,0 +1,112 @@
+const HANGUL_SYLLABLES_START: u32 = 0xac00;
+const HANGUL_SYLLABLES_END: u32 = 0xd7a3;
+const VOWEL_COUNT: usize = 21;
+const TAIL_COUNT: usize = 28;
+// Conjoining jamo, as in text decomposed by macOS
+const CHOSEONG_START: u32 = 0x1100;
+const JUNGSEONG_START: u32 = 0x1161;
+// One before the first final consonant, so that index 0 is "no final consonant"
+const JONGSEONG_START: u32 = 0x11a7;
+
+// Compatibility jamo, in the order of the syllable block arithmetic
+pub const LEADS: [char; 19] = [
+    'ㄱ', 'ㄲ', 'ㄴ', 'ㄷ', 'ㄸ', 'ㄹ', 'ㅁ', 'ㅂ', 'ㅃ', 'ㅅ', 'ㅆ', 'ㅇ', 'ㅈ', 'ㅉ', 'ㅊ', 'ㅋ', 'ㅌ', 'ㅍ', 'ㅎ',
+];
+pub const VOWELS: [char; 21] = [
+    'ㅏ', 'ㅐ', 'ㅑ', 'ㅒ', 'ㅓ', 'ㅔ', 'ㅕ', 'ㅖ', 'ㅗ', 'ㅘ', 'ㅙ', 'ㅚ', 'ㅛ', 'ㅜ', 'ㅝ', 'ㅞ', 'ㅟ', 'ㅠ', 'ㅡ', 'ㅢ', 'ㅣ',
+];
+// Index 0 of the arithmetic is "no final consonant", so these start at 1
+pub const TAILS: [char; 27] = [
+    'ㄱ', 'ㄲ', 'ㄳ', 'ㄴ', 'ㄵ', 'ㄶ', 'ㄷ', 'ㄹ', 'ㄺ', 'ㄻ', 'ㄼ', 'ㄽ', 'ㄾ', 'ㄿ', 'ㅀ', 'ㅁ', 'ㅂ', 'ㅄ', 'ㅅ', 'ㅆ',
+    'ㅇ', 'ㅈ', 'ㅊ', 'ㅋ', 'ㅌ', 'ㅍ', 'ㅎ',
+];
+
+// A precomposed Hangul syllable split into its letters
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Jamo {
+    pub lead: char,
+    pub vowel: char,
+    pub tail: Option<char>,
+}
+
+impl Jamo {
+    pub const fn new(lead: char, vowel: char, tail: Option<char>) -> Self {
+        Self { lead, vowel, tail }
+    }
+
+    pub fn from_syllable(c: char) -> Option<Self> {
+        let code = c as u32;
+        if !is_syllable(c) {
+            return None;
+        }
+        let index = (code - HANGUL_SYLLABLES_START) as usize;
+        Some(Self {
+            lead: LEADS[index / (VOWEL_COUNT * TAIL_COUNT)],
+            vowel: VOWELS[index / TAIL_COUNT % VOWEL_COUNT],
+            tail: (index % TAIL_COUNT).checked_sub(1).map(|tail| TAILS[tail]),
+        })
+    }
+
+    // None when the letters don't make up a syllable, like a vowel as the leading consonant
+    pub fn to_syllable(self) -> Option<char> {
+        let lead = LEADS.iter().position(|lead| *lead == self.lead)?;
+        let vowel = VOWELS.iter().position(|vowel| *vowel == self.vowel)?;
+        let tail = match self.tail {
+            Some(tail) => TAILS.iter().position(|candidate| *candidate == tail)? + 1,
+            None => 0,
+        };
+        char::from_u32(HANGUL_SYLLABLES_START + ((lead * VOWEL_COUNT + vowel) * TAIL_COUNT + tail) as u32)
+    }
+}
+
+// 한국 -> ㅎㅏㄴㄱㅜㄱ, other characters are kept as they are
+pub fn decompose(text: &str) -> String {
+    text.chars()
+        .flat_map(|c| match Jamo::from_syllable(c) {
+            Some(jamo) => [Some(jamo.lead), Some(jamo.vowel), jamo.tail],
+            None => [Some(c), None, None],
+        })
+        .flatten()
+        .collect()
+}
+
+// Composes leading consonant + vowel (+ final consonant) conjoining jamo into precomposed syllables
+pub fn compose(text: &str) -> String {
+    let mut composed = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let choseong = (c as u32).wrapping_sub(CHOSEONG_START) as usize;
+        let jungseong = chars.peek().map(|next| (*next as u32).wrapping_sub(JUNGSEONG_START) as usize);
+        match jungseong {
+            Some(jungseong) if choseong < LEADS.len() && jungseong < VOWEL_COUNT => {
+                chars.next();
+                let jongseong = chars
+                    .peek()
+                    .map(|next| (*next as u32).wrapping_sub(JONGSEONG_START) as usize)
+                    .filter(|jongseong| (1..TAIL_COUNT).contains(jongseong));
+                if jongseong.is_some() {
+                    chars.next();
+                }
+                let jamo = Jamo::new(LEADS[choseong], VOWELS[jungseong], jongseong.map(|tail| TAILS[tail - 1]));
+                composed.extend(jamo.to_syllable());
+            }
+            _ => composed.push(c),
+        }
+    }
+    composed
+}
+
+// A precomposed syllable, as opposed to a single jamo
+pub fn is_syllable(c: char) -> bool {
+    (HANGUL_SYLLABLES_START..=HANGUL_SYLLABLES_END).contains(&(c as u32))
+}
+
+// Syllables and jamo, both conjoining and compatibility
+pub fn is_hangul(c: char) -> bool {
+    is_syllable(c) || matches!(c, '\u{1100}'..='\u{11ff}' | '\u{3130}'..='\u{318f}')
+}
+
+pub fn has_hangul(text: &str) -> bool {
+    text.chars().any(is_syllable)
+}
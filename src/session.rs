@@ -0,0 +1,160 @@
+use axum::extract::{Request, State};
+use axum::http::header::{COOKIE, SET_COOKIE};
+use axum::http::{HeaderMap, HeaderValue};
+use axum::middleware::Next;
+use axum::response::Response;
+use axum::Extension;
+use hmac::{Hmac, Mac};
+use leptos::logging::warn;
+use sha2::Sha256;
+use std::sync::Arc;
+
+// Anonymous browser sessions, used to attribute token usage and apply the per session limits. There are no
+// accounts, the cookie holds a random id signed by the server so that clients can't make up their own.
+
+const SESSION_COOKIE: &str = "dramastudytool_session";
+const SESSION_MAX_AGE_SECS: u64 = 60 * 60 * 24 * 365;
+
+// Server functions running outside of a request (or without the middleware) are billed to this session
+const NO_SESSION: &str = "none";
+
+#[derive(Clone, Debug)]
+pub struct SessionId(pub String);
+
+// Signs and checks session cookies
+#[derive(Clone)]
+pub struct SessionKey(Vec<u8>);
+
+impl std::fmt::Debug for SessionKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SessionKey(..)")
+    }
+}
+
+impl SessionKey {
+    // Without a configured secret a random key is used, so sessions only last until the server restarts
+    pub fn new(secret: Option<&str>) -> Self {
+        match secret.filter(|secret| !secret.is_empty()) {
+            Some(secret) => Self(secret.as_bytes().to_vec()),
+            None => {
+                warn!("SESSION_SECRET is not set, browser sessions will be reset when the server restarts");
+                Self(random_bytes::<32>().to_vec())
+            }
+        }
+    }
+
+    fn sign(&self, session_id: &str) -> String {
+        format!("{}.{}", session_id, to_hex(&self.mac(session_id).finalize().into_bytes()))
+    }
+
+    // The session id of a cookie value, if we signed it
+    fn verify(&self, cookie_value: &str) -> Option<String> {
+        let (session_id, signature) = cookie_value.split_once('.')?;
+        if session_id.len() != 32 || !session_id.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        // verify_slice compares in constant time
+        self.mac(session_id).verify_slice(&from_hex(signature)?).ok()?;
+        Some(session_id.to_string())
+    }
+
+    fn mac(&self, session_id: &str) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.0).expect("HMAC takes keys of any length");
+        mac.update(session_id.as_bytes());
+        mac
+    }
+}
+
+// Middleware that gives every request a SessionId extension, setting the cookie on the first visit
+pub async fn session_middleware(State(key): State<Arc<SessionKey>>, mut request: Request, next: Next) -> Response {
+    let existing_session_id = session_id_from_cookies(&key, request.headers());
+    let session_id = existing_session_id.clone().unwrap_or_else(new_session_id);
+    request.extensions_mut().insert(SessionId(session_id.clone()));
+
+    let mut response = next.run(request).await;
+
+    if existing_session_id.is_none() {
+        let cookie = format!(
+            "{}={}; Path=/; Max-Age={}; HttpOnly; SameSite=Lax",
+            SESSION_COOKIE,
+            key.sign(&session_id),
+            SESSION_MAX_AGE_SECS
+        );
+        if let Ok(cookie) = HeaderValue::from_str(&cookie) {
+            response.headers_mut().append(SET_COOKIE, cookie);
+        }
+    }
+
+    response
+}
+
+// The session of the request a server function is handling
+pub async fn current_session_id() -> String {
+    match leptos_axum::extract::<Extension<SessionId>>().await {
+        Ok(Extension(SessionId(session_id))) => session_id,
+        Err(_) => NO_SESSION.to_string(),
+    }
+}
+
+fn session_id_from_cookies(key: &SessionKey, headers: &HeaderMap) -> Option<String> {
+    headers
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|cookies| cookies.split(';'))
+        .filter_map(|cookie| cookie.trim().split_once('='))
+        .find(|(name, _)| *name == SESSION_COOKIE)
+        // Anything else was not set by us, so it gets a new session
+        .and_then(|(_, value)| key.verify(value))
+}
+
+fn new_session_id() -> String {
+    to_hex(&random_bytes::<16>())
+}
+
+// Random bytes from the operating system, also used for batch job ids
+pub fn random_bytes<const N: usize>() -> [u8; N] {
+    let mut bytes = [0; N];
+    getrandom::getrandom(&mut bytes).expect("the operating system should provide random numbers");
+    bytes
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_cookies_signed_with_the_key_are_trusted() {
+        let key = SessionKey::new(Some("secret"));
+        let session_id = new_session_id();
+        let cookie_value = key.sign(&session_id);
+
+        assert_eq!(key.verify(&cookie_value), Some(session_id.clone()));
+        // An unsigned id, as issued before cookies were signed, or one chosen by the client
+        assert_eq!(key.verify(&session_id), None);
+        assert_eq!(key.verify(&format!("{}.{}", "0".repeat(32), "0".repeat(64))), None);
+        assert_eq!(SessionKey::new(Some("other secret")).verify(&cookie_value), None);
+    }
+
+    #[test]
+    fn new_session_ids_are_random_hex() {
+        let session_id = new_session_id();
+        assert_eq!(session_id.len(), 32);
+        assert!(session_id.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(session_id, new_session_id());
+    }
+}
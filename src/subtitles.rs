@@ -0,0 +1,252 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+use crate::languages::Language;
+
+mod ass;
+mod encoding;
+mod export;
+mod sami;
+mod search;
+mod srt;
+mod webvtt;
+
+pub use encoding::{decode, detect_encoding, TextEncoding};
+pub use export::{AnnotatedCue, ExportFormat};
+pub use search::{normalize_hangul, search_cues, SearchMode};
+
+// One subtitle, times are milliseconds from the start of the video
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Cue {
+    pub start_ms: u64,
+    pub end_ms: u64,
+    pub text: String,
+    // ASS/SSA style, used to tell dialogue from signs and songs
+    #[serde(default)]
+    pub style: Option<String>,
+}
+
+impl Cue {
+    pub fn duration_ms(&self) -> u64 {
+        self.end_ms.saturating_sub(self.start_ms)
+    }
+}
+
+// The cues of one language in a subtitle file. Only SAMI files have more than one track.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub struct SubtitleTrack {
+    pub name: String,
+    // Language tag, e.g. ko-KR, when the file declares it
+    #[serde(default)]
+    pub language: Option<String>,
+    pub cues: Vec<Cue>,
+}
+
+impl SubtitleTrack {
+    fn single(cues: Vec<Cue>) -> Vec<Self> {
+        vec![Self {
+            name: "Subtitles".to_string(),
+            language: None,
+            cues,
+        }]
+    }
+
+    pub fn is_language(&self, language: Language) -> bool {
+        let code = self.language.as_deref().and_then(|tag| tag.split(['-', '_']).next()).unwrap_or_default();
+        code.eq_ignore_ascii_case(language.code())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SubtitleFormat {
+    Srt,
+    WebVtt,
+    Ass,
+    Sami,
+}
+
+impl SubtitleFormat {
+    // File types the upload input accepts
+    pub const ACCEPT: &'static str = ".srt,.vtt,.ass,.ssa,.smi,.sami";
+
+    pub fn from_file_name(file_name: &str) -> Option<Self> {
+        let (_, extension) = file_name.rsplit_once('.')?;
+        match extension.to_lowercase().as_str() {
+            "srt" => Some(Self::Srt),
+            "vtt" => Some(Self::WebVtt),
+            "ass" | "ssa" => Some(Self::Ass),
+            "smi" | "sami" => Some(Self::Sami),
+            _ => None,
+        }
+    }
+
+    // Uses the extension, or the content when the extension is missing or unknown
+    pub fn detect(file_name: &str, text: &str) -> Self {
+        match Self::from_file_name(file_name) {
+            Some(format) => format,
+            None if webvtt::has_header(text) => Self::WebVtt,
+            None if ass::has_header(text) => Self::Ass,
+            None if sami::has_header(text) => Self::Sami,
+            None => Self::Srt,
+        }
+    }
+
+    pub fn parse(&self, text: &str) -> Result<Vec<SubtitleTrack>> {
+        match self {
+            Self::Srt => srt::parse(text).map(SubtitleTrack::single),
+            Self::WebVtt => webvtt::parse(text).map(SubtitleTrack::single),
+            Self::Ass => ass::parse(text).map(SubtitleTrack::single),
+            Self::Sami => sami::parse(text),
+        }
+    }
+}
+
+pub fn parse_subtitle_file(file_name: &str, text: &str) -> Result<Vec<SubtitleTrack>> {
+    let tracks: Vec<SubtitleTrack> = SubtitleFormat::detect(file_name, text)
+        .parse(text)?
+        .into_iter()
+        .filter(|track| !track.cues.is_empty())
+        .collect();
+    if tracks.is_empty() {
+        return Err(anyhow!("No subtitles found in {}", file_name));
+    }
+    Ok(tracks)
+}
+
+// For uploads, which are raw bytes in whatever encoding the file was saved in
+pub fn parse_subtitle_bytes(file_name: &str, bytes: &[u8], encoding: TextEncoding) -> Result<Vec<SubtitleTrack>> {
+    parse_subtitle_file(file_name, &decode(bytes, encoding))
+}
+
+// The track in the language of the show, or the first one
+pub fn default_track(tracks: &[SubtitleTrack], language: Language) -> usize {
+    tracks.iter().position(|track| track.is_language(language)).unwrap_or(0)
+}
+
+// Styles used by the cues, in order of first use. Empty for formats without styles.
+pub fn cue_styles(cues: &[Cue]) -> Vec<String> {
+    let mut styles: Vec<String> = Vec::new();
+    for style in cues.iter().filter_map(|cue| cue.style.as_ref()) {
+        if !styles.contains(style) {
+            styles.push(style.clone());
+        }
+    }
+    styles
+}
+
+// Fansubs put signs, songs and karaoke in their own styles. Everything else is assumed to be dialogue.
+pub fn default_dialogue_styles(styles: &[String]) -> HashSet<String> {
+    const NOT_DIALOGUE: [&str; 7] = ["sign", "song", "karaoke", "title", "op", "ed", "lyrics"];
+
+    let dialogue: HashSet<String> = styles
+        .iter()
+        .filter(|style| {
+            let style = style.to_lowercase();
+            !style
+                .split(|c: char| !c.is_alphanumeric())
+                .any(|word| NOT_DIALOGUE.iter().any(|name| word == *name || word.strip_suffix('s') == Some(name)))
+        })
+        .cloned()
+        .collect();
+
+    // Better to show signs too than nothing at all
+    if dialogue.is_empty() {
+        styles.iter().cloned().collect()
+    } else {
+        dialogue
+    }
+}
+
+// HH:MM:SS, what the jump-to-time input shows
+pub fn format_time_without_ms(time_ms: u64) -> String {
+    let seconds = time_ms / 1000;
+    format!("{:02}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
+
+// H:MM:SS or MM:SS, optionally with milliseconds after a '.' or ','. Hours can have any number of digits.
+pub fn parse_time(time: &str) -> Option<u64> {
+    let time = time.trim();
+    let (clock, millis) = match time.split_once(['.', ',']) {
+        Some((clock, millis)) if !millis.is_empty() && millis.len() <= 3 => {
+            (clock, millis.parse::<u64>().ok()? * 10u64.pow(3 - millis.len() as u32))
+        }
+        Some(_) => return None,
+        None => (time, 0),
+    };
+
+    let number = |part: &str, max_digits: usize| match part.len() {
+        len if (1..=max_digits).contains(&len) && part.bytes().all(|byte| byte.is_ascii_digit()) => {
+            part.parse::<u64>().ok()
+        }
+        _ => None,
+    };
+    let (hours, minutes, seconds) = match clock.split(':').collect::<Vec<&str>>().as_slice() {
+        [hours, minutes, seconds] => (number(hours, usize::MAX)?, number(minutes, 2)?, number(seconds, 2)?),
+        [minutes, seconds] => (0, number(minutes, 2)?, number(seconds, 2)?),
+        _ => return None,
+    };
+    if minutes >= 60 || seconds >= 60 {
+        return None;
+    }
+
+    hours.checked_mul(3_600_000)?.checked_add((minutes * 60 + seconds) * 1000 + millis)
+}
+
+// The first cue starting at or after time_ms, or the first cue of the file when they all start before it
+pub fn closest_cue(cues: &[Cue], time_ms: u64) -> usize {
+    cues.iter()
+        .enumerate()
+        .filter(|(_, cue)| cue.start_ms >= time_ms)
+        .min_by_key(|(_, cue)| cue.start_ms)
+        .or_else(|| cues.iter().enumerate().min_by_key(|(_, cue)| cue.start_ms))
+        .map(|(idx, _)| idx)
+        .unwrap_or(0)
+}
+
+// For each cue, the text of the reference cues (e.g. the official English subtitles) shown at the same time.
+// A reference cue belongs to a cue when they overlap for at least half of the shorter of the two, so a cue split
+// in two in the reference gets both halves and a reference cue covering two cues is given to both.
+pub fn align_cues(cues: &[Cue], reference_cues: &[Cue]) -> Vec<Option<String>> {
+    let mut reference_cues: Vec<&Cue> = reference_cues.iter().collect();
+    reference_cues.sort_by_key(|cue| cue.start_ms);
+
+    cues.iter()
+        .map(|cue| {
+            let texts: Vec<&str> = reference_cues
+                .iter()
+                .take_while(|reference| reference.start_ms < cue.end_ms)
+                .filter(|reference| {
+                    let overlap = cue.end_ms.min(reference.end_ms).saturating_sub(cue.start_ms.max(reference.start_ms));
+                    let shorter = cue.duration_ms().min(reference.duration_ms());
+                    overlap > 0 && overlap * 2 >= shorter
+                })
+                .map(|reference| reference.text.as_str())
+                .collect();
+            Some(texts.join("\n")).filter(|text| !text.is_empty())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn times_are_parsed_with_or_without_hours() {
+        assert_eq!(parse_time("00:01:02.500"), Some(62_500));
+        assert_eq!(parse_time("01:02,5"), Some(62_500));
+        assert_eq!(parse_time("1:00:00"), Some(3_600_000));
+        // Recordings longer than 99 hours, and hours padded to three digits
+        assert_eq!(parse_time("100:00:00.000"), Some(360_000_000));
+        assert_eq!(parse_time("001:00:00.000"), Some(3_600_000));
+    }
+
+    #[test]
+    fn invalid_times_are_rejected() {
+        for time in ["", "12", "00:60:00", "00:00:60", "00:001:00", "+1:00:00", "00:00:00.1234", "00:00:00.", "a:b"] {
+            assert_eq!(parse_time(time), None, "{}", time);
+        }
+        assert_eq!(parse_time("99999999999999999999:00:00"), None);
+    }
+}
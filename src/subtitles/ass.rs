@@ -0,0 +1,169 @@
+use anyhow::{anyhow, Result};
+
+use super::Cue;
+
+// Event fields of ASS files without a Format line
+const DEFAULT_EVENT_FORMAT: &str = "Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text";
+
+pub fn has_header(text: &str) -> bool {
+    text.trim_start_matches('\u{feff}').trim_start().starts_with("[Script Info]")
+}
+
+// Dialogue events of an ASS / SSA file with override tags removed. Comments and drawings are skipped.
+pub fn parse(text: &str) -> Result<Vec<Cue>> {
+    let mut in_events = false;
+    let mut format = parse_format(DEFAULT_EVENT_FORMAT);
+    let mut found_events = false;
+    let mut cues: Vec<Cue> = Vec::new();
+
+    for line in text.trim_start_matches('\u{feff}').lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_events = line.eq_ignore_ascii_case("[Events]");
+            found_events |= in_events;
+            continue;
+        }
+        if !in_events {
+            continue;
+        }
+
+        let (kind, fields) = match line.split_once(':') {
+            Some(line_parts) => line_parts,
+            None => continue,
+        };
+        match kind {
+            "Format" => format = parse_format(fields),
+            // One broken event shouldn't make the rest of the file unusable
+            "Dialogue" => match parse_dialogue(&format, fields) {
+                Ok(Some(cue)) => cues.push(cue),
+                Ok(None) => {}
+                Err(e) => leptos::logging::warn!("Skipping ASS event: {}", e),
+            },
+            _ => {}
+        }
+    }
+
+    if !found_events {
+        return Err(anyhow!("Not an ASS/SSA file, it has no [Events] section"));
+    }
+
+    // Events are in no particular order, and effects often repeat the same line on several layers
+    cues.sort_by_key(|cue| (cue.start_ms, cue.end_ms));
+    cues.dedup();
+    Ok(cues)
+}
+
+fn parse_format(fields: &str) -> Vec<String> {
+    fields.split(',').map(|field| field.trim().to_lowercase()).collect()
+}
+
+fn parse_dialogue(format: &[String], fields: &str) -> Result<Option<Cue>> {
+    // The text is the last field and can itself contain commas
+    let values: Vec<&str> = fields.trim_start().splitn(format.len(), ',').collect();
+    let field = |name: &str| {
+        format
+            .iter()
+            .position(|field| field == name)
+            .and_then(|idx| values.get(idx))
+            .map(|value| value.trim())
+    };
+
+    let (start, end) = match (field("start"), field("end")) {
+        (Some(start), Some(end)) => (start, end),
+        _ => return Err(anyhow!("Dialogue line without start and end times: {}", fields)),
+    };
+    let (start_ms, end_ms) = match (parse_ass_time(start), parse_ass_time(end)) {
+        (Some(start_ms), Some(end_ms)) => (start_ms, end_ms),
+        _ => return Err(anyhow!("Invalid ASS timing \"{}\" - \"{}\"", start, end)),
+    };
+
+    let text = strip_override_tags(field("text").unwrap_or_default());
+    if text.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(Cue {
+        start_ms,
+        end_ms,
+        text,
+        style: field("style").map(|style| style.trim_start_matches('*').to_string()),
+    }))
+}
+
+// H:MM:SS.cc, in hundredths of a second
+fn parse_ass_time(time: &str) -> Option<u64> {
+    let (clock, centiseconds) = time.split_once('.')?;
+    let mut parts = clock.split(':').map(|part| part.parse::<u64>().ok());
+    let (hours, minutes, seconds) = (parts.next()??, parts.next()??, parts.next()??);
+    if parts.next().is_some() {
+        return None;
+    }
+
+    let fraction_ms = match centiseconds.len() {
+        1 => centiseconds.parse::<u64>().ok()? * 100,
+        2 => centiseconds.parse::<u64>().ok()? * 10,
+        3 => centiseconds.parse::<u64>().ok()?,
+        _ => return None,
+    };
+    Some(((hours * 60 + minutes) * 60 + seconds) * 1000 + fraction_ms)
+}
+
+// Removes {\...} override blocks (positioning, italics, karaoke timing) and the drawings they switch on,
+// and turns \N line breaks into newlines
+fn strip_override_tags(text: &str) -> String {
+    let mut stripped = String::new();
+    let mut drawing = false;
+    let mut rest = text;
+
+    while let Some(block_start) = rest.find('{') {
+        if !drawing {
+            stripped.push_str(&rest[..block_start]);
+        }
+        let block_end = match rest[block_start..].find('}') {
+            Some(block_end) => block_start + block_end,
+            None => {
+                rest = "";
+                break;
+            }
+        };
+
+        // \p1 and up draw vector shapes until \p0, their commands aren't text
+        let block = &rest[block_start + 1..block_end];
+        for tag in block.split('\\').filter(|tag| tag.starts_with('p')) {
+            if let Ok(scale) = tag[1..].parse::<u32>() {
+                drawing = scale > 0;
+            }
+        }
+        rest = &rest[block_end + 1..];
+    }
+    if !drawing {
+        stripped.push_str(rest);
+    }
+
+    stripped
+        .replace("\\N", "\n")
+        // A soft line break, only honoured by one wrapping style
+        .replace("\\n", " ")
+        .replace("\\h", "\u{a0}")
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<&str>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_with_invalid_timing_are_skipped() {
+        let text = "[Script Info]\n\n[Events]\n\
+                    Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n\
+                    Dialogue: 0,0:00:01.00,0:00:02.00,Default,,0,0,0,,안녕\n\
+                    Dialogue: 0,0:00:xx.00,0:00:04.00,Default,,0,0,0,,깨진 자막\n\
+                    Dialogue: 0,0:00:05.00,0:00:06.00,Default,,0,0,0,,{\\i1}고마워\n";
+        let cues = parse(text).unwrap();
+        let texts: Vec<&str> = cues.iter().map(|cue| cue.text.as_str()).collect();
+        assert_eq!(texts, vec!["안녕", "고마워"]);
+    }
+}
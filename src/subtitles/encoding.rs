@@ -0,0 +1,249 @@
+use encoding_rs::Encoding;
+
+use crate::romanization::is_hangul;
+
+// Encodings subtitle files come in. Older Korean files are mostly CP949, older Japanese ones Shift-JIS.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextEncoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    // EUC-KR and its Windows superset CP949 (UHC)
+    EucKr,
+    ShiftJis,
+    Big5,
+    Gb18030,
+}
+
+// Syllables common enough to show up in any few lines of Korean. Shift-JIS and Chinese text often decode
+// cleanly as CP949, but into rare syllables from its extension area.
+const COMMON_HANGUL: &str = "이가은는을를에의도다요고하지서어아나그거게해한있없했네자니야말면까시수안";
+
+// Chinese characters common enough to show up in any few lines of dialogue, in both scripts.
+// Text decoded with the wrong Chinese encoding rarely contains them.
+const COMMON_HAN: &str = "的一是不了我你他她在有人这這个個们們说說来來么麼吗嗎没沒要就好什会會";
+
+impl TextEncoding {
+    pub const ALL: [TextEncoding; 7] = [
+        Self::Utf8,
+        Self::Utf16Le,
+        Self::Utf16Be,
+        Self::EucKr,
+        Self::ShiftJis,
+        Self::Big5,
+        Self::Gb18030,
+    ];
+
+    pub fn id(&self) -> &'static str {
+        match self {
+            Self::Utf8 => "utf-8",
+            Self::Utf16Le => "utf-16le",
+            Self::Utf16Be => "utf-16be",
+            Self::EucKr => "cp949",
+            Self::ShiftJis => "shift_jis",
+            Self::Big5 => "big5",
+            Self::Gb18030 => "gb18030",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|encoding| encoding.id() == id)
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Utf8 => "UTF-8",
+            Self::Utf16Le => "UTF-16 LE",
+            Self::Utf16Be => "UTF-16 BE",
+            Self::EucKr => "Korean (CP949 / EUC-KR)",
+            Self::ShiftJis => "Japanese (Shift-JIS)",
+            Self::Big5 => "Traditional Chinese (Big5)",
+            Self::Gb18030 => "Simplified Chinese (GB18030)",
+        }
+    }
+
+    fn encoding(&self) -> &'static Encoding {
+        match self {
+            Self::Utf8 => encoding_rs::UTF_8,
+            Self::Utf16Le => encoding_rs::UTF_16LE,
+            Self::Utf16Be => encoding_rs::UTF_16BE,
+            Self::EucKr => encoding_rs::EUC_KR,
+            Self::ShiftJis => encoding_rs::SHIFT_JIS,
+            Self::Big5 => encoding_rs::BIG5,
+            Self::Gb18030 => encoding_rs::GB18030,
+        }
+    }
+
+    fn from_encoding(encoding: &'static Encoding) -> Option<Self> {
+        Self::ALL.into_iter().find(|text_encoding| text_encoding.encoding() == encoding)
+    }
+
+    // Whether text decoded with this encoding reads like the language it is used for
+    fn is_plausible(&self, text: &str) -> bool {
+        let non_ascii: Vec<char> = text.chars().filter(|c| !c.is_ascii()).collect();
+        // At least half of the non-ASCII characters are of the language's script
+        let share = |matches: fn(char) -> bool| {
+            non_ascii.iter().filter(|c| matches(**c)).count() * 2 >= non_ascii.len()
+        };
+
+        match self {
+            Self::EucKr => share(is_hangul) && text.chars().any(|c| COMMON_HANGUL.contains(c)),
+            // Big5 lead bytes are halfwidth katakana in Shift-JIS, so only full width kana show that it is Japanese
+            Self::ShiftJis => {
+                non_ascii.iter().any(|c| matches!(c, '\u{3040}'..='\u{30ff}')) && share(|c| is_kana(c) || is_han(c))
+            }
+            Self::Big5 | Self::Gb18030 => share(is_han),
+            Self::Utf8 | Self::Utf16Le | Self::Utf16Be => true,
+        }
+    }
+}
+
+// BOMs first, then UTF-8, then the legacy encodings that decode the bytes cleanly into plausible text,
+// trying CP949 before Shift-JIS before the Chinese encodings
+pub fn detect_encoding(bytes: &[u8]) -> TextEncoding {
+    if let Some(encoding) = Encoding::for_bom(bytes).and_then(|(encoding, _)| TextEncoding::from_encoding(encoding)) {
+        return encoding;
+    }
+    if std::str::from_utf8(bytes).is_ok() {
+        return TextEncoding::Utf8;
+    }
+
+    let decodes_cleanly = |encoding: &TextEncoding| {
+        encoding
+            .encoding()
+            .decode_without_bom_handling_and_without_replacement(bytes)
+            .map(|text| (*encoding, text.into_owned()))
+    };
+    let candidates: Vec<(TextEncoding, String)> = [
+        TextEncoding::EucKr,
+        TextEncoding::ShiftJis,
+        TextEncoding::Big5,
+        TextEncoding::Gb18030,
+    ]
+    .iter()
+    .filter_map(decodes_cleanly)
+    .collect();
+
+    let mut plausible = candidates.iter().filter(|(encoding, text)| encoding.is_plausible(text));
+    match plausible.next() {
+        // GBK text often decodes cleanly as Big5 too, the one with more common characters is right
+        Some((TextEncoding::Big5, big5_text)) => {
+            match plausible.find(|(encoding, _)| *encoding == TextEncoding::Gb18030) {
+                Some((_, gb18030_text)) if common_han_count(gb18030_text) > common_han_count(big5_text) => {
+                    TextEncoding::Gb18030
+                }
+                _ => TextEncoding::Big5,
+            }
+        }
+        Some((encoding, _)) => *encoding,
+        None => candidates.first().map(|(encoding, _)| *encoding).unwrap_or(TextEncoding::Utf8),
+    }
+}
+
+// Undecodable bytes become U+FFFD rather than failing, so a wrong choice can still be seen and corrected
+pub fn decode(bytes: &[u8], encoding: TextEncoding) -> String {
+    let (text, _) = encoding.encoding().decode_with_bom_removal(bytes);
+    text.into_owned()
+}
+
+fn common_han_count(text: &str) -> usize {
+    text.chars().filter(|c| COMMON_HAN.contains(*c)).count()
+}
+
+fn is_kana(c: char) -> bool {
+    matches!(c, '\u{3040}'..='\u{30ff}' | '\u{ff66}'..='\u{ff9f}')
+}
+
+fn is_han(c: char) -> bool {
+    matches!(c, '\u{4e00}'..='\u{9fff}' | '\u{3400}'..='\u{4dbf}' | '\u{3000}'..='\u{303f}' | '\u{ff00}'..='\u{ffef}')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KOREAN: &str = "1\n00:00:01,000 --> 00:00:03,000\n오늘 뭐 먹을까? 똠얌꿍 먹고 싶어요.\n";
+    const JAPANESE: &str = "1\n00:00:01,000 --> 00:00:03,000\n今日は何を食べようか？ラーメンがいいな。\n";
+    const TRADITIONAL_CHINESE: &str = "1\n00:00:01,000 --> 00:00:03,000\n你今天想吃什麼？我覺得牛肉麵不錯。\n";
+    const SIMPLIFIED_CHINESE: &str = "1\n00:00:01,000 --> 00:00:03,000\n你今天想吃什么？我觉得牛肉面不错。\n";
+
+    fn encode(text: &str, encoding: TextEncoding) -> Vec<u8> {
+        let (bytes, _, unmappable) = encoding.encoding().encode(text);
+        assert!(!unmappable, "{} can't encode {:?}", encoding.label(), text);
+        bytes.into_owned()
+    }
+
+    fn utf16(text: &str, bom: [u8; 2], to_bytes: fn(u16) -> [u8; 2]) -> Vec<u8> {
+        bom.into_iter().chain(text.encode_utf16().flat_map(to_bytes)).collect()
+    }
+
+    fn assert_detects(text: &str, encoding: TextEncoding) {
+        let bytes = encode(text, encoding);
+        assert_eq!(detect_encoding(&bytes), encoding, "{:?}", text);
+        assert_eq!(decode(&bytes, encoding), text);
+    }
+
+    #[test]
+    fn byte_order_marks_decide_the_encoding_and_are_removed() {
+        let utf8 = [&[0xef, 0xbb, 0xbf][..], KOREAN.as_bytes()].concat();
+        let utf16_le = utf16(KOREAN, [0xff, 0xfe], u16::to_le_bytes);
+        let utf16_be = utf16(KOREAN, [0xfe, 0xff], u16::to_be_bytes);
+
+        for (bytes, encoding) in [
+            (utf8, TextEncoding::Utf8),
+            (utf16_le, TextEncoding::Utf16Le),
+            (utf16_be, TextEncoding::Utf16Be),
+        ] {
+            assert_eq!(detect_encoding(&bytes), encoding);
+            assert_eq!(decode(&bytes, encoding), KOREAN);
+        }
+    }
+
+    #[test]
+    fn valid_utf8_is_utf8() {
+        for text in [KOREAN, JAPANESE, TRADITIONAL_CHINESE, SIMPLIFIED_CHINESE, "1\nHello\n"] {
+            assert_eq!(detect_encoding(text.as_bytes()), TextEncoding::Utf8);
+        }
+    }
+
+    #[test]
+    fn detects_cp949_including_syllables_outside_euc_kr() {
+        assert_detects(KOREAN, TextEncoding::EucKr);
+        assert_detects("안녕하세요. 저는 민수예요.\n", TextEncoding::EucKr);
+    }
+
+    #[test]
+    fn detects_shift_jis_after_cp949() {
+        assert_detects(JAPANESE, TextEncoding::ShiftJis);
+        assert_detects("ありがとう。また明日ね。\n", TextEncoding::ShiftJis);
+    }
+
+    #[test]
+    fn detects_big5_and_gb18030() {
+        assert_detects(TRADITIONAL_CHINESE, TextEncoding::Big5);
+        assert_detects("謝謝你，我們明天見。他說沒關係。\n", TextEncoding::Big5);
+        assert_detects(SIMPLIFIED_CHINESE, TextEncoding::Gb18030);
+        assert_detects("谢谢你，我们明天见。他说没关系。\n", TextEncoding::Gb18030);
+    }
+
+    #[test]
+    fn a_chosen_encoding_overrides_the_detected_one() {
+        let bytes = encode(KOREAN, TextEncoding::EucKr);
+
+        // A wrong choice still decodes, into text that shows it is wrong
+        let wrong = decode(&bytes, TextEncoding::Utf8);
+        assert!(wrong.contains('\u{fffd}'));
+        assert_ne!(decode(&bytes, TextEncoding::ShiftJis), KOREAN);
+
+        let chosen = TextEncoding::from_id(TextEncoding::EucKr.id()).unwrap();
+        assert_eq!(decode(&bytes, chosen), KOREAN);
+    }
+
+    #[test]
+    fn encodings_are_found_by_id() {
+        for encoding in TextEncoding::ALL {
+            assert_eq!(TextEncoding::from_id(encoding.id()), Some(encoding));
+        }
+        assert_eq!(TextEncoding::from_id("latin1"), None);
+    }
+}
@@ -0,0 +1,265 @@
+use super::format_time_without_ms;
+
+// A cue as it's written out: the original line with its translation and vocabulary glosses below it
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AnnotatedCue {
+    pub start_ms: u64,
+    pub end_ms: u64,
+    pub text: String,
+    pub translation: Option<String>,
+    // "word: translation", one per vocabulary entry
+    pub glosses: Vec<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    Srt,
+    WebVtt,
+    Ass,
+}
+
+impl ExportFormat {
+    pub const ALL: [ExportFormat; 3] = [Self::Srt, Self::WebVtt, Self::Ass];
+
+    pub fn id(&self) -> &'static str {
+        match self {
+            Self::Srt => "srt",
+            Self::WebVtt => "vtt",
+            Self::Ass => "ass",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|format| format.id() == id)
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Srt => "SRT",
+            Self::WebVtt => "WebVTT",
+            Self::Ass => "ASS",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        self.id()
+    }
+
+    pub fn write(&self, cues: &[AnnotatedCue]) -> String {
+        match self {
+            Self::Srt => write_srt(cues),
+            Self::WebVtt => write_webvtt(cues),
+            Self::Ass => write_ass(cues),
+        }
+    }
+}
+
+// Translations in italics, glosses in grey, which most players that read SRT understand
+fn write_srt(cues: &[AnnotatedCue]) -> String {
+    let subtitles = cues
+        .iter()
+        .enumerate()
+        .map(|(idx, cue)| {
+            let mut lines = vec![without_blank_lines(&cue.text)];
+            lines.extend(
+                cue.translation
+                    .iter()
+                    .map(|translation| format!("<i>{}</i>", without_blank_lines(translation))),
+            );
+            if !cue.glosses.is_empty() {
+                lines.push(format!("<font color=\"#aaaaaa\">{}</font>", cue.glosses.join("; ")));
+            }
+            srtlib::Subtitle::new(idx + 1, srt_timestamp(cue.start_ms), srt_timestamp(cue.end_ms), lines.join("\n"))
+        })
+        .collect();
+    srtlib::Subtitles::new_from_vec(subtitles).to_string()
+}
+
+fn srt_timestamp(time_ms: u64) -> srtlib::Timestamp {
+    srtlib::Timestamp::from_milliseconds(u32::try_from(time_ms).unwrap_or(u32::MAX))
+}
+
+// Translations and glosses get the classes translation and gloss, so a player's stylesheet can target them
+fn write_webvtt(cues: &[AnnotatedCue]) -> String {
+    let mut output = "WEBVTT\n".to_string();
+    for cue in cues {
+        output.push_str(&format!(
+            "\n{} --> {}\n{}\n",
+            webvtt_timestamp(cue.start_ms),
+            webvtt_timestamp(cue.end_ms),
+            escape_webvtt(&cue.text)
+        ));
+        if let Some(translation) = &cue.translation {
+            output.push_str(&format!("<c.translation><i>{}</i></c>\n", escape_webvtt(translation)));
+        }
+        if !cue.glosses.is_empty() {
+            output.push_str(&format!("<c.gloss>{}</c>\n", escape_webvtt(&cue.glosses.join("; "))));
+        }
+    }
+    output
+}
+
+fn webvtt_timestamp(time_ms: u64) -> String {
+    format!("{}.{:03}", format_time_without_ms(time_ms), time_ms % 1000)
+}
+
+fn escape_webvtt(text: &str) -> String {
+    without_blank_lines(&text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;"))
+}
+
+// A blank line ends an SRT or WebVTT cue early
+fn without_blank_lines(text: &str) -> String {
+    text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .collect::<Vec<&str>>()
+        .join("\n")
+}
+
+const ASS_HEADER: &str = "[Script Info]
+ScriptType: v4.00+
+WrapStyle: 0
+ScaledBorderAndShadow: yes
+PlayResX: 1920
+PlayResY: 1080
+
+[V4+ Styles]
+Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding
+Style: Default,Arial,64,&H00FFFFFF,&H000000FF,&H00000000,&H80000000,0,0,0,0,100,100,0,0,1,3,1,2,60,60,50,1
+
+[Events]
+Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text
+";
+
+// Everything in the Default style, with the translation in italics and the glosses smaller and grey
+fn write_ass(cues: &[AnnotatedCue]) -> String {
+    let mut output = ASS_HEADER.to_string();
+    for cue in cues {
+        let mut text = escape_ass(&cue.text);
+        if let Some(translation) = &cue.translation {
+            text.push_str(&format!("\\N{{\\i1}}{}{{\\i0}}", escape_ass(translation)));
+        }
+        if !cue.glosses.is_empty() {
+            text.push_str(&format!("\\N{{\\fs40\\c&HAAAAAA&}}{}", escape_ass(&cue.glosses.join("; "))));
+        }
+        output.push_str(&format!(
+            "Dialogue: 0,{},{},Default,,0,0,0,,{}\n",
+            ass_timestamp(cue.start_ms),
+            ass_timestamp(cue.end_ms),
+            text
+        ));
+    }
+    output
+}
+
+// H:MM:SS.cc, ASS times are in centiseconds
+fn ass_timestamp(time_ms: u64) -> String {
+    let centiseconds = time_ms / 10;
+    let seconds = centiseconds / 100;
+    format!("{}:{:02}:{:02}.{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60, centiseconds % 100)
+}
+
+// Braces would start an override block and there is no way to escape them
+fn escape_ass(text: &str) -> String {
+    text.replace('{', "(")
+        .replace('}', ")")
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<&str>>()
+        .join("\\N")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cue(start_ms: u64, end_ms: u64, text: &str, translation: Option<&str>, glosses: &[&str]) -> AnnotatedCue {
+        AnnotatedCue {
+            start_ms,
+            end_ms,
+            text: text.to_string(),
+            translation: translation.map(str::to_string),
+            glosses: glosses.iter().map(|gloss| gloss.to_string()).collect(),
+        }
+    }
+
+    fn cues() -> Vec<AnnotatedCue> {
+        vec![
+            cue(1_000, 2_500, "안녕?", Some("Hi?"), &["안녕: hi", "너: you"]),
+            cue(3_000, 4_000, "잘 가.", None, &[]),
+        ]
+    }
+
+    #[test]
+    fn srt_has_the_translation_in_italics_and_the_glosses_in_grey() {
+        assert_eq!(
+            write_srt(&cues()),
+            "1\n00:00:01,000 --> 00:00:02,500\n안녕?\n<i>Hi?</i>\n<font color=\"#aaaaaa\">안녕: hi; 너: you</font>\n\n\
+             2\n00:00:03,000 --> 00:00:04,000\n잘 가."
+        );
+    }
+
+    #[test]
+    fn blank_lines_are_removed_from_srt_cues() {
+        let cues = [cue(0, 1_000, "첫 줄\n\n  \n둘째 줄", Some("First line\n\nSecond line"), &[])];
+        let srt = write_srt(&cues);
+
+        assert!(srt.contains("첫 줄\n둘째 줄\n<i>First line\nSecond line</i>"), "{:?}", srt);
+    }
+
+    #[test]
+    fn webvtt_cues_are_escaped_and_classed() {
+        let mut cues = cues();
+        cues[0].text = "<b>안녕</b> & 잘 가".to_string();
+        cues[0].translation = Some("Hi & <bye>\n\nfor now".to_string());
+
+        assert_eq!(
+            write_webvtt(&cues),
+            "WEBVTT\n\n\
+             00:00:01.000 --> 00:00:02.500\n&lt;b&gt;안녕&lt;/b&gt; &amp; 잘 가\n\
+             <c.translation><i>Hi &amp; &lt;bye&gt;\nfor now</i></c>\n\
+             <c.gloss>안녕: hi; 너: you</c>\n\n\
+             00:00:03.000 --> 00:00:04.000\n잘 가.\n"
+        );
+    }
+
+    #[test]
+    fn webvtt_hours_can_go_past_99() {
+        assert_eq!(webvtt_timestamp(0), "00:00:00.000");
+        assert_eq!(webvtt_timestamp(3_723_004), "01:02:03.004");
+        assert_eq!(webvtt_timestamp(360_001_500), "100:00:01.500");
+    }
+
+    #[test]
+    fn ass_times_are_in_centiseconds() {
+        assert_eq!(ass_timestamp(0), "0:00:00.00");
+        assert_eq!(ass_timestamp(999), "0:00:00.99");
+        assert_eq!(ass_timestamp(1_234), "0:00:01.23");
+        assert_eq!(ass_timestamp(3_723_456), "1:02:03.45");
+        assert_eq!(ass_timestamp(36_000_000), "10:00:00.00");
+    }
+
+    #[test]
+    fn ass_text_has_no_braces_and_joins_lines_with_n() {
+        assert_eq!(escape_ass("{\\i1}안녕{\\i0}"), "(\\i1)안녕(\\i0)");
+        assert_eq!(escape_ass(" 첫 줄 \n\n둘째 줄\n"), "첫 줄\\N둘째 줄");
+    }
+
+    #[test]
+    fn ass_dialogue_has_the_translation_and_glosses_on_their_own_lines() {
+        let ass = write_ass(&cues());
+        let dialogue: Vec<&str> = ass.lines().filter(|line| line.starts_with("Dialogue:")).collect();
+
+        assert!(ass.starts_with(ASS_HEADER));
+        assert_eq!(
+            dialogue,
+            vec![
+                concat!(
+                    "Dialogue: 0,0:00:01.00,0:00:02.50,Default,,0,0,0,,",
+                    "안녕?\\N{\\i1}Hi?{\\i0}\\N{\\fs40\\c&HAAAAAA&}안녕: hi; 너: you"
+                ),
+                "Dialogue: 0,0:00:03.00,0:00:04.00,Default,,0,0,0,,잘 가.",
+            ]
+        );
+    }
+}
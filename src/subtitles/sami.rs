@@ -0,0 +1,225 @@
+use anyhow::{anyhow, Result};
+use std::collections::BTreeMap;
+
+use super::{Cue, SubtitleTrack};
+
+// How long the last cue of a track stays up, SAMI only marks where captions start
+const LAST_CUE_DURATION_MS: u64 = 5000;
+
+pub fn has_header(text: &str) -> bool {
+    text.trim_start_matches('\u{feff}').trim_start().to_ascii_lowercase().starts_with("<sami")
+}
+
+// One track per language class (KRCC, ENCC...). A cue lasts until the next SYNC with a paragraph of its class,
+// blank paragraphs (&nbsp;) only end the previous cue.
+pub fn parse(text: &str) -> Result<Vec<SubtitleTrack>> {
+    // ASCII lowercasing keeps byte offsets, so tags are found in `lower` and text is cut from `text`
+    let lower = text.to_ascii_lowercase();
+    if !lower.contains("<sync") {
+        return Err(anyhow!("Not a SAMI file, it has no <SYNC> blocks"));
+    }
+
+    let class_names = parse_class_names(text, &lower);
+    // Cue starts and texts per class, in file order. A None text is a blank.
+    let mut class_syncs: BTreeMap<String, Vec<(u64, Option<String>)>> = BTreeMap::new();
+
+    let sync_starts: Vec<usize> = lower.match_indices("<sync").map(|(idx, _)| idx).collect();
+    for (sync_idx, &sync_start) in sync_starts.iter().enumerate() {
+        let sync_end = sync_starts.get(sync_idx + 1).copied().unwrap_or_else(|| body_end(&lower));
+        let tag_end = match lower[sync_start..].find('>') {
+            Some(tag_end) => sync_start + tag_end,
+            None => continue,
+        };
+        let start_ms = attribute(&lower[sync_start..tag_end], "start").and_then(|start| start.parse::<u64>().ok());
+        let start_ms = match start_ms {
+            Some(start_ms) => start_ms,
+            None => return Err(anyhow!("SYNC without a valid Start: {}", &text[sync_start..=tag_end])),
+        };
+
+        for (class, paragraph) in paragraphs(&text[tag_end + 1..sync_end.max(tag_end + 1)]) {
+            class_syncs.entry(class).or_default().push((start_ms, paragraph_text(&paragraph)));
+        }
+    }
+
+    let tracks = class_syncs
+        .into_iter()
+        .map(|(class, syncs)| {
+            let cues = syncs
+                .iter()
+                .enumerate()
+                .filter_map(|(idx, (start_ms, text))| {
+                    let end_ms = match syncs.get(idx + 1) {
+                        Some((next_start_ms, _)) => *next_start_ms,
+                        None => start_ms + LAST_CUE_DURATION_MS,
+                    };
+                    text.as_ref().map(|text| Cue {
+                        start_ms: *start_ms,
+                        end_ms,
+                        text: text.clone(),
+                        style: None,
+                    })
+                })
+                .collect();
+            let (name, language) = match class_names.get(&class) {
+                Some((name, language)) => (name.clone(), language.clone()),
+                None if class.is_empty() => ("Subtitles".to_string(), None),
+                None => (class.clone(), None),
+            };
+            SubtitleTrack { name, language, cues }
+        })
+        .filter(|track| !track.cues.is_empty())
+        .collect();
+
+    Ok(tracks)
+}
+
+// Everything after the last SYNC up to </body>, or the end of the file
+fn body_end(lower: &str) -> usize {
+    lower.rfind("</body>").unwrap_or(lower.len())
+}
+
+// ".KRCC { Name: Korean; lang: ko-KR; SAMIType: CC; }" in the <STYLE> block, by upper case class
+fn parse_class_names(text: &str, lower: &str) -> BTreeMap<String, (String, Option<String>)> {
+    let mut class_names = BTreeMap::new();
+    let style = match (lower.find("<style"), lower.find("</style>")) {
+        (Some(style_start), Some(style_end)) if style_start < style_end => &text[style_start..style_end],
+        _ => return class_names,
+    };
+
+    for rule in style.split('}') {
+        let (selector, declarations) = match rule.split_once('{') {
+            Some(rule_parts) => rule_parts,
+            None => continue,
+        };
+        let class = match selector.split_whitespace().last().and_then(|selector| selector.strip_prefix('.')) {
+            Some(class) => class.to_uppercase(),
+            None => continue,
+        };
+
+        let mut name = None;
+        let mut language = None;
+        for declaration in declarations.split(';') {
+            if let Some((property, value)) = declaration.split_once(':') {
+                match property.trim().to_ascii_lowercase().as_str() {
+                    "name" => name = Some(value.trim().to_string()),
+                    "lang" => language = Some(value.trim().to_string()),
+                    _ => {}
+                }
+            }
+        }
+        class_names.insert(class.clone(), (name.unwrap_or(class), language));
+    }
+
+    class_names
+}
+
+// The value of name=value in a tag, quoted or not
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let mut rest = tag;
+    while let Some(idx) = rest.find(name) {
+        let after_name = rest[idx + name.len()..].trim_start();
+        let preceded_by_space = rest[..idx].ends_with(|c: char| c.is_whitespace());
+        if let (true, Some(value)) = (preceded_by_space, after_name.strip_prefix('=')) {
+            let value = value.trim_start();
+            let value = match value.strip_prefix(['"', '\'']) {
+                Some(quoted) => quoted.split(['"', '\'']).next().unwrap_or_default(),
+                None => value.split(|c: char| c.is_whitespace() || c == '>').next().unwrap_or_default(),
+            };
+            return Some(value.to_string());
+        }
+        rest = &rest[idx + name.len()..];
+    }
+    None
+}
+
+// The <P Class=...> paragraphs of a SYNC block, with their upper case class. Text outside of a paragraph
+// belongs to an unnamed class.
+fn paragraphs(block: &str) -> Vec<(String, String)> {
+    let lower = block.to_ascii_lowercase();
+    let paragraph_starts: Vec<usize> = lower
+        .match_indices("<p")
+        .map(|(idx, _)| idx)
+        .filter(|idx| lower[idx + 2..].starts_with(|c: char| c.is_whitespace() || c == '>'))
+        .collect();
+
+    if paragraph_starts.is_empty() {
+        return vec![(String::new(), block.to_string())];
+    }
+
+    paragraph_starts
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, &paragraph_start)| {
+            let paragraph_end = paragraph_starts.get(idx + 1).copied().unwrap_or(block.len());
+            // A <P tag that isn't closed before the next paragraph is skipped
+            let tag_end = paragraph_start + lower[paragraph_start..paragraph_end].find('>')?;
+            let class = attribute(&lower[paragraph_start..tag_end], "class").unwrap_or_default().to_uppercase();
+            Some((class, block[tag_end + 1..paragraph_end].to_string()))
+        })
+        .collect()
+}
+
+// Plain text of a paragraph, None when it only blanks the screen
+fn paragraph_text(paragraph: &str) -> Option<String> {
+    // Line breaks in the markup are just whitespace, only <br> breaks lines
+    let paragraph = paragraph.replace(['\r', '\n'], " ");
+    let mut text = String::new();
+    let mut rest = paragraph.as_str();
+    while let Some(tag_start) = rest.find('<') {
+        text.push_str(&rest[..tag_start]);
+        let tag_end = match rest[tag_start..].find('>') {
+            Some(tag_end) => tag_start + tag_end,
+            None => {
+                rest = "";
+                break;
+            }
+        };
+        if rest[tag_start + 1..tag_end].trim().to_ascii_lowercase().trim_end_matches('/').trim() == "br" {
+            text.push('\n');
+        }
+        rest = &rest[tag_end + 1..];
+    }
+    text.push_str(rest);
+
+    let text = decode_entities(&text)
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<&str>>().join(" "))
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    Some(text).filter(|text| !text.is_empty())
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&nbsp;", " ")
+        .replace("&NBSP;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paragraphs_are_split_by_class() {
+        let block = "<P Class=KRCC>안녕하세요<P Class=ENCC>Hello";
+        assert_eq!(
+            paragraphs(block),
+            vec![("KRCC".to_string(), "안녕하세요".to_string()), ("ENCC".to_string(), "Hello".to_string())]
+        );
+    }
+
+    #[test]
+    fn unclosed_paragraph_tags_are_skipped() {
+        let block = "<P Class=KRCC<P Class=ENCC>Hello";
+        assert_eq!(paragraphs(block), vec![("ENCC".to_string(), "Hello".to_string())]);
+
+        let text = "<SAMI><BODY><SYNC Start=0><P Class=KRCC<P Class=ENCC>Hello<SYNC Start=1000><P Class=KRCC</BODY></SAMI>";
+        assert!(parse(text).is_ok());
+    }
+}
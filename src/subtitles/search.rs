@@ -0,0 +1,147 @@
+use anyhow::{anyhow, Result};
+
+use super::Cue;
+use crate::romanization::compose;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SearchMode {
+    // Case insensitive
+    #[default]
+    Text,
+    Regex,
+    // Ignores spacing and punctuation, and matches decomposed Hangul (as in files saved on a Mac) too
+    Hangul,
+}
+
+impl SearchMode {
+    pub const ALL: [SearchMode; 3] = [Self::Text, Self::Regex, Self::Hangul];
+
+    pub fn id(&self) -> &'static str {
+        match self {
+            Self::Text => "text",
+            Self::Regex => "regex",
+            Self::Hangul => "hangul",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|mode| mode.id() == id)
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Text => "Text",
+            Self::Regex => "Regex",
+            Self::Hangul => "Hangul, ignoring spacing",
+        }
+    }
+}
+
+// Indexes of the cues matching the query, in cue order. An empty query matches nothing.
+pub fn search_cues(cues: &[Cue], query: &str, mode: SearchMode) -> Result<Vec<usize>> {
+    if query.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let matches = |is_match: &dyn Fn(&str) -> bool| {
+        cues.iter()
+            .enumerate()
+            .filter(|(_, cue)| is_match(&cue.text))
+            .map(|(idx, _)| idx)
+            .collect::<Vec<usize>>()
+    };
+
+    match mode {
+        SearchMode::Text => {
+            let query = query.trim().to_lowercase();
+            Ok(matches(&|text| text.to_lowercase().contains(&query)))
+        }
+        SearchMode::Regex => {
+            let regex = match regex::RegexBuilder::new(query).case_insensitive(true).build() {
+                Ok(regex) => regex,
+                Err(e) => return Err(anyhow!("Invalid regex: {}", e)),
+            };
+            Ok(matches(&|text| regex.is_match(text)))
+        }
+        SearchMode::Hangul => {
+            let query = normalize_hangul(query);
+            if query.is_empty() {
+                return Ok(Vec::new());
+            }
+            Ok(matches(&|text| normalize_hangul(text).contains(&query)))
+        }
+    }
+}
+
+// Letters and digits only, lowercase, with conjoining jamo composed into syllables
+pub fn normalize_hangul(text: &str) -> String {
+    compose(text)
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cues(texts: &[&str]) -> Vec<Cue> {
+        texts
+            .iter()
+            .enumerate()
+            .map(|(idx, text)| Cue {
+                start_ms: idx as u64 * 1_000,
+                end_ms: idx as u64 * 1_000 + 500,
+                text: text.to_string(),
+                style: None,
+            })
+            .collect()
+    }
+
+    // 사랑해 as conjoining jamo, the way macOS saves file names and some subtitle files
+    const DECOMPOSED_SARANGHAE: &str = "\u{1109}\u{1161}\u{1105}\u{1161}\u{11bc}\u{1112}\u{1162}";
+
+    #[test]
+    fn text_search_ignores_case() {
+        let cues = cues(&["Hello there", "oh, HELLO!", "안녕하세요", "Goodbye"]);
+
+        assert_eq!(search_cues(&cues, "hello", SearchMode::Text).unwrap(), vec![0, 1]);
+        assert_eq!(search_cues(&cues, " HeLLo ", SearchMode::Text).unwrap(), vec![0, 1]);
+        assert_eq!(search_cues(&cues, "안녕", SearchMode::Text).unwrap(), vec![2]);
+        assert!(search_cues(&cues, "  ", SearchMode::Text).unwrap().is_empty());
+    }
+
+    #[test]
+    fn regex_search_ignores_case_and_reports_invalid_patterns() {
+        let cues = cues(&["Hello there", "Goodbye", "안녕히 가세요"]);
+
+        assert_eq!(search_cues(&cues, "^(hello|good)", SearchMode::Regex).unwrap(), vec![0, 1]);
+        assert_eq!(search_cues(&cues, "가세요$", SearchMode::Regex).unwrap(), vec![2]);
+
+        let error = search_cues(&cues, "(unclosed", SearchMode::Regex).unwrap_err();
+        assert!(error.to_string().starts_with("Invalid regex"), "{}", error);
+    }
+
+    #[test]
+    fn hangul_search_ignores_spacing_and_punctuation() {
+        let cues = cues(&["사랑 해!", "사랑해요.", "사 랑", "미안해"]);
+
+        assert_eq!(search_cues(&cues, "사랑해", SearchMode::Hangul).unwrap(), vec![0, 1]);
+        assert_eq!(search_cues(&cues, "사, 랑...", SearchMode::Hangul).unwrap(), vec![0, 1, 2]);
+        // A query of only punctuation matches nothing rather than everything
+        assert!(search_cues(&cues, "?!", SearchMode::Hangul).unwrap().is_empty());
+    }
+
+    #[test]
+    fn hangul_search_matches_decomposed_jamo() {
+        let decomposed = format!("{}요.", DECOMPOSED_SARANGHAE);
+        let cues = cues(&[&decomposed, "사랑 해", "미안해"]);
+
+        assert_eq!(search_cues(&cues, "사랑해", SearchMode::Hangul).unwrap(), vec![0, 1]);
+        assert_eq!(search_cues(&cues, DECOMPOSED_SARANGHAE, SearchMode::Hangul).unwrap(), vec![0, 1]);
+        assert_eq!(normalize_hangul(&decomposed), "사랑해요");
+        // Plain text search doesn't compose them
+        assert_eq!(search_cues(&cues, "사랑해", SearchMode::Text).unwrap(), Vec::<usize>::new());
+    }
+}
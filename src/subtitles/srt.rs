@@ -0,0 +1,26 @@
+use anyhow::{anyhow, Result};
+
+use super::Cue;
+
+pub fn parse(text: &str) -> Result<Vec<Cue>> {
+    let subtitles = match srtlib::Subtitles::parse_from_str(text.trim_start_matches('\u{feff}').to_string()) {
+        Ok(subtitles) => subtitles,
+        Err(e) => return Err(anyhow!("Error parsing SRT file: {}", e)),
+    };
+
+    Ok(subtitles
+        .to_vec()
+        .into_iter()
+        .map(|subtitle| Cue {
+            start_ms: timestamp_ms(&subtitle.start_time),
+            end_ms: timestamp_ms(&subtitle.end_time),
+            text: subtitle.text,
+            style: None,
+        })
+        .collect())
+}
+
+fn timestamp_ms(timestamp: &srtlib::Timestamp) -> u64 {
+    let (hours, minutes, seconds, milliseconds) = timestamp.get();
+    ((hours as u64 * 60 + minutes as u64) * 60 + seconds as u64) * 1000 + milliseconds as u64
+}
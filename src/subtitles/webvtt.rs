@@ -0,0 +1,137 @@
+use anyhow::{anyhow, Result};
+
+use super::{parse_time, Cue};
+
+pub fn has_header(text: &str) -> bool {
+    let first_line = text.trim_start_matches('\u{feff}').lines().next().unwrap_or_default();
+    first_line == "WEBVTT" || first_line.starts_with("WEBVTT ") || first_line.starts_with("WEBVTT\t")
+}
+
+// Cue settings (position, align...) and STYLE / REGION / NOTE blocks are ignored, markup is stripped from the text
+pub fn parse(text: &str) -> Result<Vec<Cue>> {
+    if !has_header(text) {
+        return Err(anyhow!("Not a WebVTT file, it doesn't start with WEBVTT"));
+    }
+
+    let text = text.trim_start_matches('\u{feff}').replace("\r\n", "\n").replace('\r', "\n");
+    let mut cues = Vec::new();
+
+    // Blocks are separated by blank lines, the first one is the header
+    let mut blocks: Vec<Vec<&str>> = vec![Vec::new()];
+    for line in text.lines() {
+        match blocks.last_mut() {
+            Some(block) if !line.trim().is_empty() => block.push(line),
+            Some(block) if block.is_empty() => {}
+            _ => blocks.push(Vec::new()),
+        }
+    }
+
+    for lines in blocks.iter().skip(1) {
+        let first_line = match lines.first() {
+            Some(first_line) => first_line.trim_start(),
+            None => continue,
+        };
+        if ["NOTE", "STYLE", "REGION"]
+            .iter()
+            .any(|keyword| first_line == *keyword || first_line.starts_with(&format!("{} ", keyword)))
+        {
+            continue;
+        }
+
+        // The timing line can follow an optional cue identifier
+        let timing_idx = match lines.iter().take(2).position(|line| line.contains("-->")) {
+            Some(timing_idx) => timing_idx,
+            None => continue,
+        };
+        // One broken cue shouldn't make the rest of the file unusable
+        let (start_ms, end_ms) = match parse_timing(lines[timing_idx]) {
+            Some(timing) => timing,
+            None => {
+                leptos::logging::warn!("Skipping WebVTT cue with invalid timing \"{}\"", lines[timing_idx]);
+                continue;
+            }
+        };
+
+        let cue_text = lines[timing_idx + 1..]
+            .iter()
+            .map(|line| strip_markup(line).trim().to_string())
+            .filter(|line| !line.is_empty())
+            .collect::<Vec<String>>()
+            .join("\n");
+        if !cue_text.is_empty() {
+            cues.push(Cue {
+                start_ms,
+                end_ms,
+                text: cue_text,
+                style: None,
+            });
+        }
+    }
+
+    Ok(cues)
+}
+
+// "00:01:02.500 --> 00:01:04.000 position:10% align:start"
+fn parse_timing(line: &str) -> Option<(u64, u64)> {
+    let (start, rest) = line.split_once("-->")?;
+    let end = rest.split_whitespace().next()?;
+    Some((parse_time(start)?, parse_time(end)?))
+}
+
+// Removes tags (<v Speaker>, <c.yellow>, <i>, <00:00:01.000>...) and ruby text, and decodes character references
+fn strip_markup(line: &str) -> String {
+    let mut text = String::new();
+    let mut in_ruby_text = false;
+    let mut rest = line;
+
+    while let Some(tag_start) = rest.find('<') {
+        if !in_ruby_text {
+            text.push_str(&rest[..tag_start]);
+        }
+        let tag_end = match rest[tag_start..].find('>') {
+            Some(tag_end) => tag_start + tag_end,
+            None => {
+                rest = "";
+                break;
+            }
+        };
+
+        let tag = &rest[tag_start + 1..tag_end];
+        match tag.split(['.', ' ']).next().unwrap_or_default() {
+            // Furigana and other ruby annotations aren't part of the line
+            "rt" => in_ruby_text = true,
+            "/rt" => in_ruby_text = false,
+            _ => {}
+        }
+        rest = &rest[tag_end + 1..];
+    }
+    if !in_ruby_text {
+        text.push_str(rest);
+    }
+
+    decode_character_references(&text)
+}
+
+fn decode_character_references(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&nbsp;", "\u{a0}")
+        .replace("&lrm;", "\u{200e}")
+        .replace("&rlm;", "\u{200f}")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cues_with_invalid_timing_are_skipped() {
+        let text = "WEBVTT\n\n00:00:01.000 --> 00:00:02.000\n안녕\n\n00:00:0x.000 --> 00:00:04.000\n깨진 자막\n\n\
+                    1\n00:00:05.000 --> 00:00:06.000 align:start\n<v 민수>고마워</v>\n";
+        let cues = parse(text).unwrap();
+        let texts: Vec<&str> = cues.iter().map(|cue| cue.text.as_str()).collect();
+        assert_eq!(texts, vec!["안녕", "고마워"]);
+        assert_eq!((cues[1].start_ms, cues[1].end_ms), (5000, 6000));
+    }
+}
@@ -0,0 +1,292 @@
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "ssr")]
+use crate::ai_interface::SubtitleTranslationInfo;
+#[cfg(feature = "ssr")]
+use anyhow::{anyhow, Result};
+#[cfg(feature = "ssr")]
+use lru::LruCache;
+#[cfg(feature = "ssr")]
+use std::{num::NonZeroUsize, sync::Mutex};
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct CacheStats {
+    pub memory_hits: u64,
+    pub disk_hits: u64,
+    pub misses: u64,
+    pub memory_entries: usize,
+    pub disk_entries: usize,
+    pub persistent: bool,
+}
+
+// Separates the parts of a storage key, can't appear in normalized text
+#[cfg(feature = "ssr")]
+const KEY_SEPARATOR: char = '\u{1f}';
+
+// Collapses whitespace so that the same line from different files shares an entry
+pub fn normalize_text(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+#[cfg(feature = "ssr")]
+#[derive(Clone, Debug)]
+pub struct CacheKey {
+    pub text: String,
+    pub target_language: String,
+    pub prompt_version: u32,
+    // Fingerprint of anything else in the prompt that changes the answer, e.g. surrounding subtitles
+    pub variant: String,
+}
+
+#[cfg(feature = "ssr")]
+impl CacheKey {
+    pub fn new(text: &str, target_language: &str, prompt_version: u32) -> Self {
+        Self {
+            text: normalize_text(text),
+            target_language: target_language.to_string(),
+            prompt_version,
+            variant: String::new(),
+        }
+    }
+
+    pub fn with_variant(mut self, prompt_part: &str) -> Self {
+        self.variant = fingerprint(&format!("{}{}", self.variant, prompt_part));
+        self
+    }
+
+    // Text comes first so that every entry for a line can be found with a prefix scan
+    fn storage_key(&self) -> String {
+        format!(
+            "{}{}{}{}{}{}{}",
+            self.text,
+            KEY_SEPARATOR,
+            self.target_language,
+            KEY_SEPARATOR,
+            self.prompt_version,
+            KEY_SEPARATOR,
+            self.variant
+        )
+    }
+}
+
+// 64 bit FNV-1a, unlike std's DefaultHasher it is stable across Rust releases so keys on disk stay valid
+#[cfg(feature = "ssr")]
+fn fingerprint(value: &str) -> String {
+    let hash = value.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
+    format!("{:016x}", hash)
+}
+
+#[cfg(feature = "ssr")]
+fn text_prefix(text: &str) -> String {
+    format!("{}{}", normalize_text(text), KEY_SEPARATOR)
+}
+
+// In-memory LRU in front of an optional sled database on disk
+#[cfg(feature = "ssr")]
+#[derive(Debug)]
+pub struct TranslationCache {
+    memory: Mutex<LruCache<String, SubtitleTranslationInfo>>,
+    disk: Option<sled::Db>,
+    stats: Mutex<CacheStats>,
+}
+
+#[cfg(feature = "ssr")]
+impl TranslationCache {
+    pub fn open(path: Option<&str>, memory_entries: usize) -> Result<Self> {
+        let disk = match path {
+            Some(path) => match sled::open(path) {
+                Ok(db) => Some(db),
+                Err(e) => return Err(anyhow!("Error opening translation cache {}: {:?}", path, e)),
+            },
+            None => None,
+        };
+
+        Ok(Self::with_disk(disk, memory_entries))
+    }
+
+    pub fn in_memory(memory_entries: usize) -> Self {
+        Self::with_disk(None, memory_entries)
+    }
+
+    fn with_disk(disk: Option<sled::Db>, memory_entries: usize) -> Self {
+        let capacity = NonZeroUsize::new(memory_entries).unwrap_or(NonZeroUsize::MIN);
+
+        Self {
+            memory: Mutex::new(LruCache::new(capacity)),
+            stats: Mutex::new(CacheStats {
+                persistent: disk.is_some(),
+                ..Default::default()
+            }),
+            disk,
+        }
+    }
+
+    pub fn get(&self, key: &CacheKey) -> Option<SubtitleTranslationInfo> {
+        let storage_key = key.storage_key();
+
+        if let Some(info) = self.memory.lock().unwrap_or_else(|e| e.into_inner()).get(&storage_key) {
+            self.stats.lock().unwrap_or_else(|e| e.into_inner()).memory_hits += 1;
+            return Some(info.clone());
+        }
+
+        let disk_info = self.disk.as_ref().and_then(|db| match db.get(storage_key.as_bytes()) {
+            Ok(Some(bytes)) => serde_json::from_slice::<SubtitleTranslationInfo>(&bytes).ok(),
+            _ => None,
+        });
+
+        match disk_info {
+            Some(info) => {
+                self.stats.lock().unwrap_or_else(|e| e.into_inner()).disk_hits += 1;
+                self.memory.lock().unwrap_or_else(|e| e.into_inner()).put(storage_key, info.clone());
+                Some(info)
+            }
+            None => {
+                self.stats.lock().unwrap_or_else(|e| e.into_inner()).misses += 1;
+                None
+            }
+        }
+    }
+
+    pub fn insert(&self, key: &CacheKey, info: &SubtitleTranslationInfo) {
+        let storage_key = key.storage_key();
+
+        if let Some(db) = &self.disk {
+            match serde_json::to_vec(info) {
+                Ok(bytes) => {
+                    if let Err(e) = db.insert(storage_key.as_bytes(), bytes) {
+                        leptos::logging::error!("Error writing translation cache: {:?}", e);
+                    }
+                }
+                Err(e) => leptos::logging::error!("Error serializing translation cache entry: {:?}", e),
+            }
+        }
+
+        self.memory.lock().unwrap_or_else(|e| e.into_inner()).put(storage_key, info.clone());
+    }
+
+    // Removes every entry for the text, whatever its language or prompt version. Returns how many were removed.
+    pub fn invalidate_text(&self, text: &str) -> usize {
+        let prefix = text_prefix(text);
+
+        let mut memory = self.memory.lock().unwrap_or_else(|e| e.into_inner());
+        let memory_keys: Vec<String> = memory
+            .iter()
+            .filter(|(key, _)| key.starts_with(&prefix))
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in &memory_keys {
+            memory.pop(key);
+        }
+
+        let mut disk_removed = 0;
+        if let Some(db) = &self.disk {
+            let disk_keys: Vec<sled::IVec> = db
+                .scan_prefix(prefix.as_bytes())
+                .keys()
+                .filter_map(|key| key.ok())
+                .collect();
+            for key in disk_keys {
+                if let Ok(Some(_)) = db.remove(key) {
+                    disk_removed += 1;
+                }
+            }
+        }
+
+        memory_keys.len().max(disk_removed)
+    }
+
+    pub fn clear(&self) -> usize {
+        let mut memory = self.memory.lock().unwrap_or_else(|e| e.into_inner());
+        let mut removed = memory.len();
+        memory.clear();
+
+        if let Some(db) = &self.disk {
+            removed = removed.max(db.len());
+            if let Err(e) = db.clear() {
+                leptos::logging::error!("Error clearing translation cache: {:?}", e);
+            }
+        }
+
+        removed
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let mut stats = self.stats.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        stats.memory_entries = self.memory.lock().unwrap_or_else(|e| e.into_inner()).len();
+        stats.disk_entries = self.disk.as_ref().map(|db| db.len()).unwrap_or(0);
+        stats
+    }
+}
+
+#[cfg(all(test, feature = "ssr"))]
+mod tests {
+    use super::*;
+
+    fn info(translation: &str) -> SubtitleTranslationInfo {
+        SubtitleTranslationInfo {
+            translation: translation.to_string(),
+            vocabulary: Vec::new(),
+            grammar_points: Vec::new(),
+        }
+    }
+
+    fn key(text: &str) -> CacheKey {
+        CacheKey::new(text, "ko-en", 1)
+    }
+
+    #[test]
+    fn hits_and_misses_are_counted() {
+        let cache = TranslationCache::in_memory(10);
+        assert!(cache.get(&key("안녕")).is_none());
+
+        cache.insert(&key("안녕"), &info("Hi"));
+        // Whitespace doesn't matter
+        assert_eq!(cache.get(&key(" 안녕\n")).map(|info| info.translation).as_deref(), Some("Hi"));
+
+        let stats = cache.stats();
+        assert_eq!((stats.memory_hits, stats.disk_hits, stats.misses), (1, 0, 1));
+        assert_eq!(stats.memory_entries, 1);
+    }
+
+    #[test]
+    fn entries_on_disk_outlive_the_memory_cache() {
+        let disk = sled::Config::new().temporary(true).open().unwrap();
+        let cache = TranslationCache::with_disk(Some(disk), 1);
+        cache.insert(&key("안녕"), &info("Hi"));
+        cache.insert(&key("고마워"), &info("Thanks"));
+
+        assert_eq!(cache.get(&key("안녕")).map(|info| info.translation).as_deref(), Some("Hi"));
+        let stats = cache.stats();
+        assert_eq!((stats.memory_hits, stats.disk_hits), (0, 1));
+        assert_eq!(stats.disk_entries, 2);
+    }
+
+    #[test]
+    fn language_prompt_version_and_variant_are_part_of_the_key() {
+        let cache = TranslationCache::in_memory(10);
+        cache.insert(&key("안녕"), &info("Hi"));
+
+        assert!(cache.get(&CacheKey::new("안녕", "ko-ja", 1)).is_none());
+        assert!(cache.get(&CacheKey::new("안녕", "ko-en", 2)).is_none());
+        assert!(cache.get(&key("안녕").with_variant("Previous lines: 누구야?")).is_none());
+        assert!(cache.get(&key("안녕")).is_some());
+    }
+
+    #[test]
+    fn invalidation_removes_every_entry_for_the_text() {
+        let disk = sled::Config::new().temporary(true).open().unwrap();
+        let cache = TranslationCache::with_disk(Some(disk), 10);
+        cache.insert(&key("안녕"), &info("Hi"));
+        cache.insert(&CacheKey::new("안녕", "ko-ja", 1), &info("やあ"));
+        cache.insert(&key("안녕하세요"), &info("Hello"));
+
+        assert_eq!(cache.invalidate_text("안녕"), 2);
+        assert!(cache.get(&key("안녕")).is_none());
+        assert!(cache.get(&key("안녕하세요")).is_some());
+
+        assert_eq!(cache.clear(), 1);
+        assert!(cache.get(&key("안녕하세요")).is_none());
+    }
+}
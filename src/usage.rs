@@ -0,0 +1,468 @@
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "ssr")]
+use crate::ai_interface::{
+    AIInterface, BilledError, TranslateError, TranslationContext, TranslationResult, TranslationStreamEvent,
+    VocabularyInfo,
+};
+#[cfg(feature = "ssr")]
+use futures::stream::{Stream, StreamExt};
+#[cfg(feature = "ssr")]
+use crate::languages::LanguagePair;
+#[cfg(feature = "ssr")]
+use std::collections::{BTreeMap, HashMap};
+#[cfg(feature = "ssr")]
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "ssr")]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Tokens billed for a translation, summed over repair attempts
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub struct TokenUsage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    // The provider didn't report usage (e.g. Azure streaming), so it was estimated from the text length
+    #[serde(default)]
+    pub estimated: bool,
+}
+
+impl TokenUsage {
+    pub fn total_tokens(&self) -> u64 {
+        self.prompt_tokens + self.completion_tokens
+    }
+
+    // Roughly four characters per token, close enough for spend caps
+    pub fn estimate(prompt_characters: usize, completion_characters: usize) -> Self {
+        Self {
+            prompt_tokens: prompt_characters.div_ceil(4) as u64,
+            completion_tokens: completion_characters.div_ceil(4) as u64,
+            estimated: true,
+        }
+    }
+
+    pub fn add(&mut self, other: &TokenUsage) {
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+        self.estimated |= other.estimated;
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct UsageTotals {
+    // Calls to the model, including failed ones that cost tokens. Cache hits cost nothing and aren't counted.
+    pub translations: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub cost_usd: f64,
+}
+
+impl UsageTotals {
+    #[cfg(feature = "ssr")]
+    fn add(&mut self, usage: &TokenUsage, cost_usd: f64) {
+        self.translations += 1;
+        self.prompt_tokens += usage.prompt_tokens;
+        self.completion_tokens += usage.completion_tokens;
+        self.cost_usd += cost_usd;
+    }
+}
+
+// What the /usage page shows. Only totals are shared between sessions, files are per session.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct UsageReport {
+    pub today: String,
+    pub session_today: UsageTotals,
+    pub session_total: UsageTotals,
+    pub session_files: Vec<(String, UsageTotals)>,
+    // Every session, most recent day first
+    pub days: Vec<(String, UsageTotals)>,
+    pub daily_spend_cap_usd: Option<f64>,
+    pub session_daily_spend_cap_usd: Option<f64>,
+}
+
+// USD per million tokens
+#[cfg(feature = "ssr")]
+#[derive(Clone, Copy, Debug)]
+pub struct Pricing {
+    pub prompt_per_million: f64,
+    pub completion_per_million: f64,
+}
+
+#[cfg(feature = "ssr")]
+impl Pricing {
+    pub fn cost(&self, usage: &TokenUsage) -> f64 {
+        (usage.prompt_tokens as f64 * self.prompt_per_million + usage.completion_tokens as f64 * self.completion_per_million)
+            / 1_000_000.0
+    }
+}
+
+// Who a translation is billed to
+#[cfg(feature = "ssr")]
+#[derive(Clone, Debug)]
+pub struct UsageScope {
+    pub session_id: String,
+    // Show / episode title of the subtitle file, None for typed in text
+    pub file: Option<String>,
+}
+
+#[cfg(feature = "ssr")]
+impl UsageScope {
+    pub fn new(session_id: &str, context: Option<&TranslationContext>) -> Self {
+        Self {
+            session_id: session_id.to_string(),
+            file: context
+                .and_then(|context| context.title.as_deref())
+                .map(str::trim)
+                .filter(|title| !title.is_empty())
+                .map(str::to_string),
+        }
+    }
+}
+
+// Sessions without usage for this many days are forgotten
+#[cfg(feature = "ssr")]
+const SESSION_RETENTION_DAYS: u64 = 7;
+
+// Token usage and cost aggregated per day, session and file. Kept in memory, so it starts over on restart.
+#[cfg(feature = "ssr")]
+#[derive(Debug)]
+pub struct UsageTracker {
+    pricing: Pricing,
+    daily_spend_cap_usd: Option<f64>,
+    session_daily_spend_cap_usd: Option<f64>,
+    state: Mutex<UsageState>,
+}
+
+#[cfg(feature = "ssr")]
+#[derive(Debug, Default)]
+struct UsageState {
+    days: BTreeMap<u64, UsageTotals>,
+    sessions: HashMap<String, SessionUsage>,
+}
+
+#[cfg(feature = "ssr")]
+#[derive(Debug, Default)]
+struct SessionUsage {
+    last_day: u64,
+    today: UsageTotals,
+    total: UsageTotals,
+    files: BTreeMap<String, UsageTotals>,
+}
+
+#[cfg(feature = "ssr")]
+impl UsageTracker {
+    pub fn new(pricing: Pricing, daily_spend_cap_usd: Option<f64>, session_daily_spend_cap_usd: Option<f64>) -> Self {
+        Self {
+            pricing,
+            daily_spend_cap_usd,
+            session_daily_spend_cap_usd,
+            state: Mutex::new(UsageState::default()),
+        }
+    }
+
+    // Err once today's spending, overall or by this session, has reached its cap. The caps are soft: calls are only
+    // recorded once they finish, so the calls already running when a cap is reached (e.g. a batch translation)
+    // can take the spending past it.
+    pub fn check_caps(&self, session_id: &str) -> Result<(), TranslateError> {
+        let today = current_day();
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+
+        if let Some(cap) = self.daily_spend_cap_usd {
+            let spent = state.days.get(&today).map(|totals| totals.cost_usd).unwrap_or_default();
+            if spent >= cap {
+                return Err(TranslateError::SpendCapReached { session: false });
+            }
+        }
+
+        if let Some(cap) = self.session_daily_spend_cap_usd {
+            let spent = state
+                .sessions
+                .get(session_id)
+                .filter(|session| session.last_day == today)
+                .map(|session| session.today.cost_usd)
+                .unwrap_or_default();
+            if spent >= cap {
+                return Err(TranslateError::SpendCapReached { session: true });
+            }
+        }
+
+        Ok(())
+    }
+
+    // Translates and records what it cost. Once a cap is reached only cached translations are served.
+    pub async fn translate(
+        &self,
+        ai_interface: &AIInterface,
+        scope: &UsageScope,
+        text: String,
+        language_pair: LanguagePair,
+        context: Option<&TranslationContext>,
+    ) -> Result<TranslationResult, TranslateError> {
+        if let Err(e) = self.check_caps(&scope.session_id) {
+            return ai_interface.cached_translation(&text, language_pair, context).ok_or(e);
+        }
+
+        let result = ai_interface
+            .translate(text, language_pair, context)
+            .await
+            .map_err(|billed_error| self.record_failure(scope, billed_error))?;
+        self.record_result(scope, &result);
+        Ok(result)
+    }
+
+    // Like translate, for AIInterface::translate_stream. The usage is recorded even if the client goes away.
+    pub fn translate_stream(
+        self: Arc<Self>,
+        ai_interface: &AIInterface,
+        scope: UsageScope,
+        text: String,
+        language_pair: LanguagePair,
+        context: Option<TranslationContext>,
+    ) -> impl Stream<Item = TranslationStreamEvent> + Send + 'static {
+        if let Err(e) = self.check_caps(&scope.session_id) {
+            let event = match ai_interface.cached_translation(&text, language_pair, context.as_ref()) {
+                Some(result) => TranslationStreamEvent::Done(result),
+                None => TranslationStreamEvent::Failed(e),
+            };
+            return futures::stream::once(async move { event }).boxed();
+        }
+
+        let (sender, receiver) = futures::channel::mpsc::unbounded();
+        let mut events = ai_interface.translate_stream(text, language_pair, context).boxed();
+        tokio::spawn(async move {
+            while let Some(event) = events.next().await {
+                let event = match event {
+                    Ok(event) => event,
+                    Err(billed_error) => TranslationStreamEvent::Failed(self.record_failure(&scope, billed_error)),
+                };
+                if let TranslationStreamEvent::Done(result) = &event {
+                    self.record_result(&scope, result);
+                }
+                let _ = sender.unbounded_send(event);
+            }
+        });
+
+        receiver.boxed()
+    }
+
+    // Glosses a list of words and records what it cost. There is no cache to fall back on once a cap is reached.
+    pub async fn gloss(
+        &self,
+        ai_interface: &AIInterface,
+        scope: &UsageScope,
+        words: &[String],
+        language_pair: LanguagePair,
+        context: Option<&TranslationContext>,
+    ) -> Result<Vec<VocabularyInfo>, TranslateError> {
+        self.check_caps(&scope.session_id)?;
+
+        let (glosses, usage) = ai_interface
+            .gloss(words, language_pair, context)
+            .await
+            .map_err(|billed_error| self.record_failure(scope, billed_error))?;
+        self.record(scope, &usage);
+        Ok(glosses)
+    }
+
+    // Failed calls are billed for the tokens they used, calls that failed before the model answered cost nothing
+    fn record_failure(&self, scope: &UsageScope, (error, usage): BilledError) -> TranslateError {
+        if usage.total_tokens() > 0 {
+            self.record(scope, &usage);
+        }
+        error
+    }
+
+    // Cache hits have no usage and cost nothing
+    pub fn record_result(&self, scope: &UsageScope, result: &TranslationResult) {
+        if let Some(usage) = &result.metadata.usage {
+            self.record(scope, usage);
+        }
+    }
+
+    pub fn record(&self, scope: &UsageScope, usage: &TokenUsage) {
+        let today = current_day();
+        let cost_usd = self.pricing.cost(usage);
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+
+        state.days.entry(today).or_default().add(usage, cost_usd);
+
+        let session = state.sessions.entry(scope.session_id.clone()).or_default();
+        if session.last_day != today {
+            session.last_day = today;
+            session.today = UsageTotals::default();
+        }
+        session.today.add(usage, cost_usd);
+        session.total.add(usage, cost_usd);
+        if let Some(file) = &scope.file {
+            session.files.entry(file.clone()).or_default().add(usage, cost_usd);
+        }
+
+        state
+            .sessions
+            .retain(|_, session| today.saturating_sub(session.last_day) < SESSION_RETENTION_DAYS);
+    }
+
+    pub fn report(&self, session_id: &str) -> UsageReport {
+        let today = current_day();
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let session = state.sessions.get(session_id);
+
+        UsageReport {
+            today: format_day(today),
+            session_today: session
+                .filter(|session| session.last_day == today)
+                .map(|session| session.today.clone())
+                .unwrap_or_default(),
+            session_total: session.map(|session| session.total.clone()).unwrap_or_default(),
+            session_files: session
+                .map(|session| session.files.iter().map(|(file, totals)| (file.clone(), totals.clone())).collect())
+                .unwrap_or_default(),
+            days: state
+                .days
+                .iter()
+                .rev()
+                .map(|(day, totals)| (format_day(*day), totals.clone()))
+                .collect(),
+            daily_spend_cap_usd: self.daily_spend_cap_usd,
+            session_daily_spend_cap_usd: self.session_daily_spend_cap_usd,
+        }
+    }
+}
+
+// Days since the Unix epoch, in UTC
+#[cfg(feature = "ssr")]
+fn current_day() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since_epoch| since_epoch.as_secs() / 86_400)
+        .unwrap_or_default()
+}
+
+// YYYY-MM-DD of a day since the epoch, using Howard Hinnant's civil_from_days
+#[cfg(feature = "ssr")]
+fn format_day(day: u64) -> String {
+    let z = day as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day_of_month = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day_of_month)
+}
+
+#[cfg(all(test, feature = "ssr"))]
+mod tests {
+    use super::*;
+
+    // A dollar per prompt token keeps the costs easy to follow
+    fn tracker(daily_cap: Option<f64>, session_daily_cap: Option<f64>) -> UsageTracker {
+        let pricing = Pricing {
+            prompt_per_million: 1_000_000.0,
+            completion_per_million: 0.0,
+        };
+        UsageTracker::new(pricing, daily_cap, session_daily_cap)
+    }
+
+    fn scope(session_id: &str, file: Option<&str>) -> UsageScope {
+        UsageScope {
+            session_id: session_id.to_string(),
+            file: file.map(str::to_string),
+        }
+    }
+
+    fn usage(prompt_tokens: u64) -> TokenUsage {
+        TokenUsage {
+            prompt_tokens,
+            completion_tokens: 10,
+            estimated: false,
+        }
+    }
+
+    #[test]
+    fn the_daily_cap_stops_every_session() {
+        let tracker = tracker(Some(3.0), None);
+        tracker.record(&scope("a", None), &usage(2));
+        assert!(tracker.check_caps("a").is_ok());
+
+        tracker.record(&scope("b", None), &usage(1));
+        for session_id in ["a", "c"] {
+            let result = tracker.check_caps(session_id);
+            assert!(matches!(result, Err(TranslateError::SpendCapReached { session: false })));
+        }
+    }
+
+    #[test]
+    fn the_session_cap_only_stops_that_session() {
+        let tracker = tracker(None, Some(2.0));
+        tracker.record(&scope("a", None), &usage(2));
+
+        assert!(matches!(tracker.check_caps("a"), Err(TranslateError::SpendCapReached { session: true })));
+        assert!(tracker.check_caps("b").is_ok());
+    }
+
+    #[test]
+    fn a_new_day_starts_the_session_over() {
+        let tracker = tracker(None, Some(2.0));
+        tracker.record(&scope("a", None), &usage(2));
+        assert!(tracker.check_caps("a").is_err());
+
+        tracker.state.lock().unwrap().sessions.get_mut("a").unwrap().last_day -= 1;
+        assert!(tracker.check_caps("a").is_ok());
+        assert_eq!(tracker.report("a").session_today, UsageTotals::default());
+
+        tracker.record(&scope("a", None), &usage(1));
+        let report = tracker.report("a");
+        assert_eq!(report.session_today.prompt_tokens, 1);
+        assert_eq!(report.session_today.translations, 1);
+        assert_eq!(report.session_total.prompt_tokens, 3);
+        assert_eq!(report.session_total.translations, 2);
+    }
+
+    #[test]
+    fn usage_is_summed_per_file_of_the_session() {
+        let tracker = tracker(None, None);
+        tracker.record(&scope("a", Some("Episode 1")), &usage(1));
+        tracker.record(&scope("a", Some("Episode 1")), &usage(2));
+        tracker.record(&scope("a", Some("Episode 2")), &usage(4));
+        tracker.record(&scope("a", None), &usage(8));
+        tracker.record(&scope("b", Some("Episode 1")), &usage(16));
+
+        let files = tracker.report("a").session_files;
+        let summed: Vec<(&str, u64, u64)> = files
+            .iter()
+            .map(|(file, totals)| (file.as_str(), totals.translations, totals.prompt_tokens))
+            .collect();
+        assert_eq!(summed, vec![("Episode 1", 2, 3), ("Episode 2", 1, 4)]);
+        assert_eq!(tracker.report("a").session_total.prompt_tokens, 15);
+        assert_eq!(tracker.report("a").days[0].1.prompt_tokens, 31);
+    }
+
+    #[test]
+    fn failures_are_only_recorded_when_they_cost_tokens() {
+        let tracker = tracker(None, None);
+        let error = tracker.record_failure(&scope("a", None), (TranslateError::AuthFailure, TokenUsage::default()));
+        assert!(matches!(error, TranslateError::AuthFailure));
+        assert_eq!(tracker.report("a").session_total, UsageTotals::default());
+
+        tracker.record_failure(&scope("a", None), (TranslateError::BadOutput("prose".to_string()), usage(5)));
+        assert_eq!(tracker.report("a").session_total.prompt_tokens, 5);
+    }
+
+    #[test]
+    fn formats_days_across_year_and_leap_year_boundaries() {
+        assert_eq!(format_day(0), "1970-01-01");
+        assert_eq!(format_day(10_956), "1999-12-31");
+        assert_eq!(format_day(10_957), "2000-01-01");
+        // 2000 is a leap year even though it is divisible by 100
+        assert_eq!(format_day(11_016), "2000-02-29");
+        assert_eq!(format_day(11_017), "2000-03-01");
+        assert_eq!(format_day(19_722), "2023-12-31");
+        assert_eq!(format_day(19_782), "2024-02-29");
+        assert_eq!(format_day(19_783), "2024-03-01");
+        // 2100 isn't
+        assert_eq!(format_day(47_540), "2100-02-28");
+        assert_eq!(format_day(47_541), "2100-03-01");
+    }
+}
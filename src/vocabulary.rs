@@ -0,0 +1,212 @@
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "ssr")]
+use std::collections::{HashMap, HashSet};
+
+#[cfg(feature = "ssr")]
+use crate::languages::Language;
+
+#[cfg(feature = "ssr")]
+mod korean;
+#[cfg(feature = "ssr")]
+mod tokenize;
+
+#[cfg(feature = "ssr")]
+pub use korean::{analyze_word, WordAnalysis};
+
+// The episode is split into this many parts of consecutive cues to measure how evenly a word is spread over it
+#[cfg(feature = "ssr")]
+const DISPERSION_PARTS: usize = 10;
+
+// A word of a subtitle file and how it's spread over the episode
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct WordFrequency {
+    pub word: String,
+    pub count: usize,
+    // Number of cues the word is used in
+    pub cues: usize,
+    // Juilland's D, 0 when every use is in the same part of the episode and 1 when they're spread evenly
+    pub dispersion: f64,
+    // The first cue using the word, as an example
+    pub example_cue: usize,
+    pub example: String,
+}
+
+impl WordFrequency {
+    // Juilland's usage coefficient, frequent words that come back throughout the episode rank first
+    pub fn score(&self) -> f64 {
+        self.count as f64 * self.dispersion
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct VocabularyAnalysis {
+    pub cue_count: usize,
+    pub token_count: usize,
+    pub distinct_words: usize,
+    // Words already saved and function words are left out
+    pub study_list: Vec<WordFrequency>,
+}
+
+// Counts the words of the cues and ranks the ones worth studying before watching
+#[cfg(feature = "ssr")]
+pub fn analyze_vocabulary(
+    cues: &[String],
+    language: Language,
+    saved_words: &[String],
+    limit: usize,
+) -> VocabularyAnalysis {
+    let parts = DISPERSION_PARTS.min(cues.len()).max(1);
+    let mut words: HashMap<String, (WordFrequency, Vec<usize>)> = HashMap::new();
+    let mut token_count = 0;
+
+    for (idx, cue) in cues.iter().enumerate() {
+        let part = idx * parts / cues.len();
+        let mut seen_in_cue: HashSet<String> = HashSet::new();
+        for token in tokenize::tokenize(cue, language) {
+            token_count += 1;
+            let (frequency, part_counts) = words.entry(token.clone()).or_insert_with(|| {
+                let frequency = WordFrequency {
+                    word: token.clone(),
+                    example_cue: idx,
+                    example: cue.clone(),
+                    ..Default::default()
+                };
+                (frequency, vec![0; parts])
+            });
+            frequency.count += 1;
+            part_counts[part] += 1;
+            if seen_in_cue.insert(token) {
+                frequency.cues += 1;
+            }
+        }
+    }
+    let distinct_words = words.len();
+
+    // Saved words can have particles or endings attached, so they're compared the way the cues were split up
+    let known: HashSet<String> = saved_words
+        .iter()
+        .flat_map(|word| {
+            let mut forms = tokenize::tokenize(word, language);
+            forms.push(word.trim().to_lowercase());
+            forms
+        })
+        .collect();
+
+    let mut study_list: Vec<WordFrequency> = words
+        .into_values()
+        .filter(|(frequency, _)| {
+            !known.contains(&frequency.word) && !tokenize::is_function_word(&frequency.word, language)
+        })
+        .map(|(mut frequency, part_counts)| {
+            frequency.dispersion = juilland_d(&part_counts);
+            frequency
+        })
+        .collect();
+    study_list.sort_by(|a, b| {
+        b.score()
+            .total_cmp(&a.score())
+            .then(b.count.cmp(&a.count))
+            .then(a.example_cue.cmp(&b.example_cue))
+    });
+    study_list.truncate(limit);
+
+    VocabularyAnalysis {
+        cue_count: cues.len(),
+        token_count,
+        distinct_words,
+        study_list,
+    }
+}
+
+// 1 - V / sqrt(n - 1), where V is the coefficient of variation of the word's counts in the n parts
+#[cfg(feature = "ssr")]
+fn juilland_d(part_counts: &[usize]) -> f64 {
+    let n = part_counts.len() as f64;
+    if part_counts.len() < 2 {
+        return 1.0;
+    }
+    let mean = part_counts.iter().sum::<usize>() as f64 / n;
+    if mean == 0.0 {
+        return 0.0;
+    }
+    let variance = part_counts.iter().map(|count| (*count as f64 - mean).powi(2)).sum::<f64>() / n;
+    (1.0 - variance.sqrt() / mean / (n - 1.0).sqrt()).clamp(0.0, 1.0)
+}
+
+#[cfg(all(test, feature = "ssr"))]
+mod tests {
+    use super::*;
+
+    // Ten cues, one per dispersion part: dragon is in all of them, castle in every other one, and sword is said
+    // ten times but only in the first
+    fn episode() -> Vec<String> {
+        (0..10)
+            .map(|idx| {
+                let mut cue = format!("dragon filler{}", idx);
+                if idx % 2 == 0 {
+                    cue.push_str(" castle");
+                }
+                if idx == 0 {
+                    cue.push_str(&" sword".repeat(10));
+                }
+                cue
+            })
+            .collect()
+    }
+
+    fn ranked_words(analysis: &VocabularyAnalysis) -> Vec<&str> {
+        analysis.study_list.iter().map(|frequency| frequency.word.as_str()).collect()
+    }
+
+    #[test]
+    fn juilland_d_measures_how_evenly_a_word_is_spread() {
+        assert_eq!(juilland_d(&[2, 2, 2, 2]), 1.0);
+        assert_eq!(juilland_d(&[8, 0, 0, 0]), 0.0);
+        assert!((juilland_d(&[1, 0, 1, 0]) - (1.0 - 1.0 / 3f64.sqrt())).abs() < 1e-9);
+        assert_eq!(juilland_d(&[3]), 1.0);
+        assert_eq!(juilland_d(&[0, 0]), 0.0);
+    }
+
+    #[test]
+    fn words_spread_over_the_episode_rank_before_bursts() {
+        let analysis = analyze_vocabulary(&episode(), Language::English, &[], 4);
+
+        assert_eq!(analysis.cue_count, 10);
+        assert_eq!(analysis.token_count, 10 + 10 + 5 + 10);
+        assert_eq!(analysis.distinct_words, 13);
+        // Sword is as frequent as dragon but all in one place, it only beats the words used once by its count
+        assert_eq!(ranked_words(&analysis), vec!["dragon", "castle", "sword", "filler0"]);
+
+        let dragon = &analysis.study_list[0];
+        assert_eq!((dragon.count, dragon.cues, dragon.dispersion), (10, 10, 1.0));
+        assert_eq!((dragon.example_cue, dragon.example.as_str()), (0, episode()[0].as_str()));
+        let sword = &analysis.study_list[2];
+        assert_eq!((sword.count, sword.cues, sword.dispersion), (10, 1, 0.0));
+    }
+
+    #[test]
+    fn saved_and_function_words_are_left_out() {
+        let saved_words = ["  Dragon ".to_string(), "castle".to_string()];
+        let analysis = analyze_vocabulary(&episode(), Language::English, &saved_words, 3);
+        assert_eq!(ranked_words(&analysis), vec!["sword", "filler0", "filler1"]);
+        // They still count towards the totals
+        assert_eq!(analysis.distinct_words, 13);
+
+        let cues = ["el castillo y el dragón".to_string(), "el dragón".to_string()];
+        let analysis = analyze_vocabulary(&cues, Language::Spanish, &[], 10);
+        assert_eq!(ranked_words(&analysis), vec!["dragón", "castillo"]);
+    }
+
+    #[test]
+    fn saved_korean_words_match_with_particles_attached() {
+        let cues = ["학교에 가요".to_string(), "학교가 좋아".to_string(), "우리 학교".to_string()];
+
+        let analysis = analyze_vocabulary(&cues, Language::Korean, &[], 10);
+        assert!(ranked_words(&analysis).contains(&"학교"), "{:?}", ranked_words(&analysis));
+        assert!(!ranked_words(&analysis).contains(&"우리"));
+
+        let analysis = analyze_vocabulary(&cues, Language::Korean, &["학교에서".to_string()], 10);
+        assert!(!ranked_words(&analysis).contains(&"학교"), "{:?}", ranked_words(&analysis));
+    }
+}
@@ -0,0 +1,560 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use crate::romanization::{is_syllable, Jamo};
+
+use super::tokenize::particle_stems;
+
+// Common words in the CSV format of mecab-ko-dic (surface, left id, right id, cost, POS tag, ...), verbs and
+// adjectives by their stem. Only the surface and the tag are used, so rows from mecab-ko-dic can be added as is.
+const LEXICON: &str = include_str!("korean_lexicon.csv");
+
+// Irregular stems that don't follow from their shape
+const DIGEUT_IRREGULAR: [&str; 5] = ["듣", "걷", "묻", "싣", "깨닫"];
+const SIOT_IRREGULAR: [&str; 5] = ["낫", "짓", "붓", "잇", "젓"];
+const BIEUP_IRREGULAR_VERBS: [&str; 3] = ["돕", "굽", "눕"];
+const BIEUP_REGULAR_ADJECTIVES: [&str; 1] = ["좁"];
+// Stems ending in 르 that only drop the ㅡ
+const REU_REGULAR: [&str; 3] = ["따르", "치르", "들르"];
+
+// Endings after the stem of a verb or adjective, with what they mean
+const CONSONANT_ENDINGS: [(&str, &str, Applies); 16] = [
+    ("고", "connective, and", Applies::Both),
+    ("지", "right? / negation", Applies::Both),
+    ("지만", "connective, but", Applies::Both),
+    ("지요", "polite, right?", Applies::Both),
+    ("죠", "polite, right?", Applies::Both),
+    ("네", "exclamation", Applies::Both),
+    ("네요", "polite exclamation", Applies::Both),
+    ("게", "adverbial", Applies::Both),
+    ("기", "nominalized", Applies::Both),
+    ("겠다", "future / presumption", Applies::Both),
+    ("겠어요", "future / presumption, polite", Applies::Both),
+    ("거든요", "because, you see", Applies::Both),
+    ("잖아요", "you know", Applies::Both),
+    ("는", "present modifier", Applies::Verb),
+    ("는데", "connective, but / so", Applies::Verb),
+    ("자", "let's", Applies::Verb),
+];
+// Endings starting with 으, which is dropped after vowels
+const EU_ENDINGS: [(&str, &str, Applies); 15] = [
+    ("으면", "conditional, if", Applies::Both),
+    ("으니까", "because", Applies::Both),
+    ("으세요", "honorific polite / please", Applies::Both),
+    ("을", "future modifier", Applies::Both),
+    ("은", "past modifier", Applies::Verb),
+    ("은", "modifier", Applies::Adjective),
+    ("은데", "connective, but / so", Applies::Adjective),
+    ("으러", "in order to", Applies::Verb),
+    ("으려고", "intending to", Applies::Verb),
+    ("을까", "shall I? / I wonder", Applies::Verb),
+    ("을까요", "shall we?, polite", Applies::Verb),
+    ("을게", "I will", Applies::Verb),
+    ("을게요", "I will, polite", Applies::Verb),
+    ("을래요", "do you want to?, polite", Applies::Verb),
+    ("읍시다", "let's, formal", Applies::Verb),
+];
+// Endings starting with 아 / 어, which contracts with the stem. The bool is the past tense 았 / 었.
+const A_ENDINGS: [(bool, &str, &str); 16] = [
+    (false, "", "informal"),
+    (false, "요", "polite"),
+    (false, "서", "connective, so / and then"),
+    (false, "도", "connective, even if"),
+    (false, "야", "connective, have to"),
+    (false, "주세요", "please do"),
+    (true, "다", "past, plain"),
+    (true, "어", "past, informal"),
+    (true, "어요", "past, polite"),
+    (true, "습니다", "past, formal"),
+    (true, "는데", "past, connective, but / so"),
+    (true, "지", "past, right?"),
+    (true, "지만", "past, connective, but"),
+    (true, "고", "past, connective, and"),
+    (true, "네요", "past, polite exclamation"),
+    (true, "으면", "past, conditional, if"),
+];
+// Endings of the noun + 이다 copula
+const COPULA_ENDINGS: [&str; 13] = [
+    "이에요", "예요", "이야", "야", "이다", "입니다", "이었어요", "였어요", "이었어", "였어", "인데", "이고",
+    "이지만",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Applies {
+    Both,
+    Verb,
+    Adjective,
+}
+
+// Dictionary form, part of speech and conjugation of a word as it appears in a sentence
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WordAnalysis {
+    pub lemma: String,
+    pub part_of_speech: &'static str,
+    pub conjugation: Option<String>,
+}
+
+struct Lexicon {
+    // Conjugated forms of every verb and adjective, and the other words as they are
+    forms: HashMap<String, Vec<WordAnalysis>>,
+    // Forms of 하다, for the X하다 verbs that aren't in the lexicon
+    ha_forms: HashMap<String, String>,
+}
+
+fn lexicon() -> &'static Lexicon {
+    static LEXICON_FORMS: OnceLock<Lexicon> = OnceLock::new();
+    LEXICON_FORMS.get_or_init(|| {
+        let mut forms: HashMap<String, Vec<WordAnalysis>> = HashMap::new();
+        for (surface, tag) in LEXICON.lines().filter_map(|line| {
+            let fields: Vec<&str> = line.split(',').collect();
+            Some((*fields.first()?, *fields.get(4)?))
+        }) {
+            let Some(part_of_speech) = part_of_speech(tag) else {
+                continue;
+            };
+            match tag {
+                "VV" | "VA" | "VX" => {
+                    for (form, conjugation) in conjugations(surface, tag == "VA") {
+                        forms.entry(form).or_default().push(WordAnalysis {
+                            lemma: format!("{}다", surface),
+                            part_of_speech,
+                            conjugation,
+                        });
+                    }
+                }
+                _ => forms.entry(surface.to_string()).or_default().push(WordAnalysis {
+                    lemma: surface.to_string(),
+                    part_of_speech,
+                    conjugation: None,
+                }),
+            }
+        }
+
+        let ha_forms = conjugations("하", false)
+            .into_iter()
+            .map(|(form, conjugation)| (form, conjugation.unwrap_or_default()))
+            .collect();
+        Lexicon { forms, ha_forms }
+    })
+}
+
+fn part_of_speech(tag: &str) -> Option<&'static str> {
+    match tag {
+        "NNG" => Some("noun"),
+        "NNP" => Some("proper noun"),
+        "NNB" => Some("bound noun"),
+        "NP" => Some("pronoun"),
+        "NR" => Some("numeral"),
+        "VV" => Some("verb"),
+        "VA" => Some("adjective"),
+        "VX" => Some("auxiliary verb"),
+        "MM" => Some("determiner"),
+        "MAG" => Some("adverb"),
+        "MAJ" => Some("conjunctive adverb"),
+        "IC" => Some("interjection"),
+        _ => None,
+    }
+}
+
+// Analyses one word (eojeol). Nouns win over verb forms with the same spelling, as in 나는 (I + topic particle),
+// except for nouns that only match once a particle is taken off.
+pub fn analyze_word(word: &str) -> Option<WordAnalysis> {
+    let word = word.trim();
+    if word.is_empty() || !word.chars().all(is_syllable) {
+        return None;
+    }
+    let lexicon = lexicon();
+    let entries = |surface: &str| lexicon.forms.get(surface).map(Vec::as_slice).unwrap_or_default();
+    let is_nominal = |analysis: &&WordAnalysis| analysis.conjugation.is_none() && !analysis.lemma.ends_with('다');
+
+    if let Some(analysis) = entries(word).iter().find(is_nominal) {
+        return Some(analysis.clone());
+    }
+
+    let with_particle = particle_stems(word).into_iter().find_map(|stem| {
+        entries(stem).iter().find(is_nominal).map(|analysis| WordAnalysis {
+            conjugation: Some(format!("with particle -{}", &word[stem.len()..])),
+            ..analysis.clone()
+        })
+    });
+    if let Some(analysis) = with_particle.as_ref().filter(|analysis| analysis.part_of_speech == "pronoun") {
+        return Some(analysis.clone());
+    }
+
+    if let Some(analysis) = entries(word).first() {
+        return Some(analysis.clone());
+    }
+    if with_particle.is_some() {
+        return with_particle;
+    }
+
+    let with_copula = COPULA_ENDINGS.iter().find_map(|ending| {
+        let noun = word.strip_suffix(ending)?;
+        entries(noun).iter().find(is_nominal).map(|analysis| WordAnalysis {
+            conjugation: Some(format!("with copula -{}", ending)),
+            ..analysis.clone()
+        })
+    });
+    if with_copula.is_some() {
+        return with_copula;
+    }
+
+    // 공부했어요 is 공부하다 even without 공부하 in the lexicon
+    word.char_indices().skip(1).find_map(|(idx, _)| {
+        let conjugation = lexicon.ha_forms.get(&word[idx..])?;
+        Some(WordAnalysis {
+            lemma: format!("{}하다", &word[..idx]),
+            part_of_speech: "verb",
+            conjugation: Some(conjugation.clone()).filter(|conjugation| !conjugation.is_empty()),
+        })
+    })
+}
+
+fn syllables(text: &str) -> Vec<Jamo> {
+    text.chars().filter_map(Jamo::from_syllable).collect()
+}
+
+fn to_text(syllables: &[Jamo]) -> String {
+    syllables.iter().map(|syllable| syllable.to_syllable().unwrap_or('\u{fffd}')).collect()
+}
+
+// How the stem changes before endings starting with a vowel
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum StemClass {
+    Regular,
+    // 하다, 하 + 여 is 해
+    Ha,
+    // 살다: ㄹ drops before ㄴ, ㅂ and ㅅ
+    Rieul,
+    // 쓰다: ㅡ drops before 아 / 어
+    Eu,
+    // 모르다 -> 몰라
+    Reu,
+    // 듣다 -> 들어
+    Digeut,
+    // 춥다 -> 추워, 돕다 -> 도와
+    Bieup,
+    // 낫다 -> 나아
+    Siot,
+    // 그렇다 -> 그래
+    Hieuh,
+}
+
+fn stem_class(stem: &str, stem_syllables: &[Jamo], is_adjective: bool) -> StemClass {
+    let Some(last) = stem_syllables.last() else {
+        return StemClass::Regular;
+    };
+    match last.tail {
+        None if *last == Jamo::new('ㅎ', 'ㅏ', None) => StemClass::Ha,
+        None if *last == Jamo::new('ㄹ', 'ㅡ', None) && stem_syllables.len() > 1 => {
+            match REU_REGULAR.contains(&stem) {
+                true => StemClass::Eu,
+                false => StemClass::Reu,
+            }
+        }
+        None if last.vowel == 'ㅡ' => StemClass::Eu,
+        Some('ㄹ') => StemClass::Rieul,
+        Some('ㄷ') if DIGEUT_IRREGULAR.contains(&stem) => StemClass::Digeut,
+        Some('ㅅ') if SIOT_IRREGULAR.contains(&stem) => StemClass::Siot,
+        Some('ㅂ') if is_adjective && !BIEUP_REGULAR_ADJECTIVES.contains(&stem) => StemClass::Bieup,
+        Some('ㅂ') if BIEUP_IRREGULAR_VERBS.contains(&stem) => StemClass::Bieup,
+        Some('ㅎ') if is_adjective && stem != "좋" => StemClass::Hieuh,
+        _ => StemClass::Regular,
+    }
+}
+
+// Every form of a verb or adjective stem with a description of its ending, the dictionary form has none
+fn conjugations(stem: &str, is_adjective: bool) -> Vec<(String, Option<String>)> {
+    let stem_syllables = syllables(stem);
+    if stem_syllables.is_empty() {
+        return Vec::new();
+    }
+    let class = stem_class(stem, &stem_syllables, is_adjective);
+    let applies = |applies: Applies| match applies {
+        Applies::Both => true,
+        Applies::Verb => !is_adjective,
+        Applies::Adjective => is_adjective,
+    };
+    let describe = |description: &str, ending: &str| Some(format!("{} (-{})", description, ending));
+
+    let mut forms = vec![(format!("{}다", stem), None)];
+
+    for (ending, description, _) in CONSONANT_ENDINGS.iter().filter(|(_, _, applies_to)| applies(*applies_to)) {
+        let form = attach_consonant(&stem_syllables, class, &syllables(ending));
+        forms.push((to_text(&form), describe(description, ending)));
+    }
+    for (ending, description, _) in EU_ENDINGS.iter().filter(|(_, _, applies_to)| applies(*applies_to)) {
+        let form = attach_eu(&stem_syllables, class, &syllables(ending));
+        forms.push((to_text(&form), describe(description, &eu_ending_display(ending))));
+    }
+
+    // -는다 / -ㄴ다 for verbs, -습니다 / -ㅂ니다 for both
+    let batchim_endings = [
+        (!is_adjective, Some('ㄴ'), "는다", "다", "plain present", "(느)ㄴ다"),
+        (true, Some('ㅂ'), "습니다", "니다", "formal", "(스)ㅂ니다"),
+        (true, Some('ㅂ'), "습니까", "니까", "formal question", "(스)ㅂ니까"),
+    ];
+    for (applies, tail, after_consonant, after_vowel, description, ending) in batchim_endings {
+        if applies {
+            let form = attach_batchim_ending(&stem_syllables, class, tail, after_consonant, after_vowel);
+            forms.push((to_text(&form), describe(description, ending)));
+        }
+    }
+
+    let a_vowel = a_vowel(&stem_syllables, class);
+    for (past, rest, description) in A_ENDINGS {
+        let tail = if past { Some('ㅆ') } else { None };
+        let rest = syllables(rest);
+        let mut form = attach_a(&stem_syllables, class, a_vowel, tail);
+        form.extend_from_slice(&rest);
+        let ending = format!("{}{}", to_text(&[Jamo::new('ㅇ', a_vowel, tail)]), to_text(&rest));
+        forms.push((to_text(&form), describe(description, &ending)));
+    }
+
+    forms
+}
+
+// 으면 as (으)면 and 을게 as (으)ㄹ게, the way textbooks write them
+fn eu_ending_display(ending: &str) -> String {
+    let mut chars = ending.chars();
+    let tail = match chars.next() {
+        Some('은') => "ㄴ",
+        Some('을') => "ㄹ",
+        Some('읍') => "ㅂ",
+        _ => "",
+    };
+    format!("(으){}{}", tail, chars.as_str())
+}
+
+// 아 after ㅏ and ㅗ, 여 after 하, 어 otherwise. Stems that lose their ㅡ look at the syllable before it.
+fn a_vowel(stem: &[Jamo], class: StemClass) -> char {
+    let deciding = match class {
+        StemClass::Ha => return 'ㅕ',
+        StemClass::Eu | StemClass::Reu if stem.len() > 1 => stem[stem.len() - 2],
+        StemClass::Eu | StemClass::Reu => return 'ㅓ',
+        // 도와 and 고와, but 고마워 and 추워
+        StemClass::Bieup if stem.len() == 1 && stem[0].vowel == 'ㅗ' => return 'ㅏ',
+        StemClass::Bieup => return 'ㅓ',
+        _ => stem[stem.len() - 1],
+    };
+    match deciding.vowel {
+        'ㅏ' | 'ㅗ' | 'ㅑ' => 'ㅏ',
+        _ => 'ㅓ',
+    }
+}
+
+fn attach_consonant(stem: &[Jamo], class: StemClass, ending: &[Jamo]) -> Vec<Jamo> {
+    let mut form = stem.to_vec();
+    let drops_rieul = ending.first().map(|first| ['ㄴ', 'ㅂ', 'ㅅ'].contains(&first.lead)).unwrap_or(false);
+    if class == StemClass::Rieul && drops_rieul {
+        set_last_tail(&mut form, None);
+    }
+    form.extend_from_slice(ending);
+    form
+}
+
+// The ending's first syllable is 으, 은, 을 or 읍
+fn attach_eu(stem: &[Jamo], class: StemClass, ending: &[Jamo]) -> Vec<Jamo> {
+    let Some((eu, rest)) = ending.split_first() else {
+        return stem.to_vec();
+    };
+    let mut form = stem.to_vec();
+    let last_tail = stem.last().map(|last| last.tail).unwrap_or(None);
+
+    // Attached like after a vowel: 으 drops and its final consonant moves onto the stem
+    let after_vowel = |mut form: Vec<Jamo>| {
+        if eu.tail.is_some() {
+            set_last_tail(&mut form, eu.tail);
+        }
+        form.extend_from_slice(rest);
+        form
+    };
+
+    match class {
+        StemClass::Digeut => {
+            set_last_tail(&mut form, Some('ㄹ'));
+            form.extend_from_slice(ending);
+            form
+        }
+        StemClass::Bieup => {
+            set_last_tail(&mut form, None);
+            form.push(Jamo { vowel: 'ㅜ', ..*eu });
+            form.extend_from_slice(rest);
+            form
+        }
+        StemClass::Siot => {
+            set_last_tail(&mut form, None);
+            form.extend_from_slice(ending);
+            form
+        }
+        StemClass::Hieuh => {
+            set_last_tail(&mut form, None);
+            after_vowel(form)
+        }
+        StemClass::Rieul => match eu.tail {
+            // 살 + 을 -> 살
+            Some('ㄹ') => {
+                form.extend_from_slice(rest);
+                form
+            }
+            None => attach_consonant(&form, class, rest),
+            _ => {
+                set_last_tail(&mut form, None);
+                after_vowel(form)
+            }
+        },
+        _ if last_tail.is_none() => after_vowel(form),
+        _ => {
+            form.extend_from_slice(ending);
+            form
+        }
+    }
+}
+
+// -는다 / -습니다 after consonants, the final consonant ㄴ / ㅂ on the stem after vowels
+fn attach_batchim_ending(
+    stem: &[Jamo],
+    class: StemClass,
+    tail: Option<char>,
+    after_consonant: &str,
+    after_vowel: &str,
+) -> Vec<Jamo> {
+    let mut form = stem.to_vec();
+    let last_tail = stem.last().map(|last| last.tail).unwrap_or(None);
+    if last_tail.is_none() || class == StemClass::Rieul {
+        set_last_tail(&mut form, tail);
+        form.extend(syllables(after_vowel));
+    } else {
+        form.extend(syllables(after_consonant));
+    }
+    form
+}
+
+// The stem with 아 / 어 (and the past tense final consonant) attached, contracted the way it's written
+fn attach_a(stem: &[Jamo], class: StemClass, a_vowel: char, tail: Option<char>) -> Vec<Jamo> {
+    let mut form = stem.to_vec();
+    let separate = Jamo::new('ㅇ', a_vowel, tail);
+    let last = form[form.len() - 1];
+    let last_idx = form.len() - 1;
+
+    match class {
+        StemClass::Ha => form[last_idx] = Jamo::new('ㅎ', 'ㅐ', tail),
+        StemClass::Digeut => {
+            form[last_idx] = Jamo { tail: Some('ㄹ'), ..last };
+            form.push(separate);
+        }
+        StemClass::Bieup => {
+            form[last_idx] = Jamo { tail: None, ..last };
+            let vowel = if a_vowel == 'ㅏ' { 'ㅘ' } else { 'ㅝ' };
+            form.push(Jamo::new('ㅇ', vowel, tail));
+        }
+        StemClass::Siot => {
+            form[last_idx] = Jamo { tail: None, ..last };
+            form.push(separate);
+        }
+        StemClass::Hieuh => {
+            let vowel = if last.vowel == 'ㅑ' { 'ㅒ' } else { 'ㅐ' };
+            form[last_idx] = Jamo::new(last.lead, vowel, tail);
+        }
+        StemClass::Reu => {
+            form[last_idx - 1] = Jamo { tail: Some('ㄹ'), ..form[last_idx - 1] };
+            form[last_idx] = Jamo::new('ㄹ', a_vowel, tail);
+        }
+        StemClass::Eu => form[last_idx] = Jamo::new(last.lead, a_vowel, tail),
+        StemClass::Regular | StemClass::Rieul if last.tail.is_some() => form.push(separate),
+        StemClass::Regular | StemClass::Rieul => {
+            let contracted = match last.vowel {
+                'ㅏ' | 'ㅓ' | 'ㅐ' | 'ㅔ' | 'ㅕ' => Some(last.vowel),
+                'ㅗ' => Some('ㅘ'),
+                'ㅜ' => Some('ㅝ'),
+                'ㅣ' => Some('ㅕ'),
+                'ㅚ' => Some('ㅙ'),
+                _ => None,
+            };
+            match contracted {
+                Some(vowel) => form[last_idx] = Jamo::new(last.lead, vowel, tail),
+                None => form.push(separate),
+            }
+        }
+    }
+    form
+}
+
+fn set_last_tail(form: &mut [Jamo], tail: Option<char>) {
+    if let Some(last) = form.last_mut() {
+        *last = Jamo { tail, ..*last };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn analysis(lemma: &str, part_of_speech: &'static str, conjugation: &str) -> Option<WordAnalysis> {
+        Some(WordAnalysis {
+            lemma: lemma.to_string(),
+            part_of_speech,
+            conjugation: Some(conjugation.to_string()),
+        })
+    }
+
+    #[test]
+    fn stems_are_classified_by_their_shape() {
+        let cases = [
+            ("하", false, StemClass::Ha),
+            ("살", false, StemClass::Rieul),
+            ("쓰", false, StemClass::Eu),
+            ("따르", false, StemClass::Eu),
+            ("모르", false, StemClass::Reu),
+            ("듣", false, StemClass::Digeut),
+            ("받", false, StemClass::Regular),
+            ("춥", true, StemClass::Bieup),
+            ("돕", false, StemClass::Bieup),
+            ("입", false, StemClass::Regular),
+            ("낫", false, StemClass::Siot),
+            ("웃", false, StemClass::Regular),
+            ("그렇", true, StemClass::Hieuh),
+            ("좋", true, StemClass::Regular),
+        ];
+        for (stem, is_adjective, class) in cases {
+            assert_eq!(stem_class(stem, &syllables(stem), is_adjective), class, "{}", stem);
+        }
+    }
+
+    #[test]
+    fn irregular_stems_contract_with_a() {
+        let cases = [
+            ("들어", "듣다", "verb", "informal (-어)"),
+            ("추워", "춥다", "adjective", "informal (-어)"),
+            ("도와", "돕다", "verb", "informal (-아)"),
+            ("몰라", "모르다", "verb", "informal (-아)"),
+            ("그래요", "그렇다", "adjective", "polite (-어요)"),
+            ("나아", "낫다", "verb", "informal (-아)"),
+            ("살아", "살다", "verb", "informal (-아)"),
+        ];
+        for (word, lemma, part_of_speech, conjugation) in cases {
+            assert_eq!(analyze_word(word), analysis(lemma, part_of_speech, conjugation), "{}", word);
+        }
+        // On its own 그래 is the interjection, which wins over the verb form
+        assert_eq!(analyze_word("그래").map(|analysis| analysis.part_of_speech), Some("interjection"));
+        assert!(conjugations("그렇", true).contains(&("그래".to_string(), Some("informal (-어)".to_string()))));
+    }
+
+    #[test]
+    fn rieul_drops_before_n() {
+        assert_eq!(analyze_word("사는"), analysis("살다", "verb", "present modifier (-는)"));
+    }
+
+    #[test]
+    fn particles_and_the_copula_are_taken_off_nouns() {
+        assert_eq!(analyze_word("나는"), analysis("나", "pronoun", "with particle -는"));
+        assert_eq!(analyze_word("사전이에요"), analysis("사전", "noun", "with copula -이에요"));
+        assert_eq!(analyze_word("공부했어요"), analysis("공부하다", "verb", "past, polite (-였어요)"));
+    }
+
+    #[test]
+    fn words_that_arent_hangul_are_not_analyzed() {
+        assert_eq!(analyze_word("hello"), None);
+        assert_eq!(analyze_word("  "), None);
+    }
+}
@@ -0,0 +1,146 @@
+use crate::languages::Language;
+
+use crate::romanization::{is_syllable, Jamo};
+
+use super::korean::analyze_word;
+
+// Particles are only attached after a syllable with a final consonant, without one, or either
+const PARTICLES_AFTER_CONSONANT: [&str; 7] = ["이랑", "으로", "이나", "은", "는", "이", "을"];
+const PARTICLES_AFTER_VOWEL: [&str; 7] = ["랑", "로", "나", "는", "가", "를", "와"];
+const PARTICLES_AFTER_ANY: [&str; 17] = [
+    "에서는", "에게서", "한테서", "에서", "에게", "한테", "까지", "부터", "처럼", "보다", "하고", "께서", "에는",
+    "에", "의", "도", "만",
+];
+
+// Pronouns, interjections and the like, which every learner knows and every episode is full of
+const KOREAN_FUNCTION_WORDS: [&str; 36] = [
+    "나", "너", "저", "제", "내", "네", "니", "그", "이", "저기", "여기", "거기", "이거", "그거", "저거", "것", "거",
+    "수", "뭐", "왜", "예", "아", "어", "응", "음", "야", "우리", "누가", "내가", "제가", "니가", "네가", "그래",
+    "아니", "좀", "안",
+];
+const JAPANESE_FUNCTION_WORDS: [&str; 6] = ["私", "僕", "俺", "君", "何", "今"];
+const MANDARIN_FUNCTION_WORDS: [&str; 24] = [
+    "的", "了", "是", "不", "我", "你", "他", "她", "它", "们", "們", "这", "這", "那", "在", "有", "吗", "嗎", "吧",
+    "呢", "啊", "就", "也", "都",
+];
+const SPANISH_FUNCTION_WORDS: [&str; 46] = [
+    "el", "la", "los", "las", "un", "una", "unos", "unas", "de", "del", "al", "que", "y", "o", "a", "en", "es", "no",
+    "sí", "si", "lo", "le", "les", "me", "te", "se", "nos", "por", "para", "con", "mi", "tu", "su", "yo", "tú", "él",
+    "ella", "qué", "pero", "como", "más", "ya", "eso", "esto", "muy", "bien",
+];
+
+// The words of a line, in a form that is counted together: Korean in its dictionary form when the analyzer
+// knows the word, Spanish lowercased.
+// Japanese keeps kanji and katakana runs, which carry most of the meaning, and Chinese, which
+// isn't segmented, is counted character by character.
+pub fn tokenize(text: &str, language: Language) -> Vec<String> {
+    match language {
+        Language::Korean => words(text)
+            .filter(|word| word.chars().any(is_syllable))
+            // 누가 isn't 누 + 가
+            .map(|word| match KOREAN_FUNCTION_WORDS.contains(&word) {
+                true => word.to_string(),
+                false => analyze_word(word).map(|analysis| analysis.lemma).unwrap_or_else(|| word.to_string()),
+            })
+            .collect(),
+        Language::Japanese => script_runs(text, |c| is_kanji(c) || is_katakana(c))
+            .into_iter()
+            .filter(|run| run.chars().count() > 1 || run.chars().all(is_kanji))
+            .collect(),
+        Language::Mandarin => text.chars().filter(|c| is_kanji(*c)).map(String::from).collect(),
+        Language::Spanish | Language::English => words(text)
+            .map(str::to_lowercase)
+            .filter(|word| word.chars().any(char::is_alphabetic))
+            .collect(),
+    }
+}
+
+pub fn is_function_word(word: &str, language: Language) -> bool {
+    match language {
+        Language::Korean => KOREAN_FUNCTION_WORDS.contains(&word),
+        Language::Japanese => JAPANESE_FUNCTION_WORDS.contains(&word),
+        Language::Mandarin => MANDARIN_FUNCTION_WORDS.contains(&word),
+        Language::Spanish => SPANISH_FUNCTION_WORDS.contains(&word),
+        Language::English => false,
+    }
+}
+
+fn words(text: &str) -> impl Iterator<Item = &str> {
+    text.split(|c: char| !c.is_alphanumeric()).filter(|word| !word.is_empty())
+}
+
+// What's left of the word after each particle that can follow it, shortest first. Only a guess, 포도 isn't 포 + 도,
+// so callers check the stem against the lexicon.
+pub(super) fn particle_stems(word: &str) -> Vec<&str> {
+    let mut stems: Vec<&str> = PARTICLES_AFTER_ANY
+        .iter()
+        .map(|particle| (particle, None))
+        .chain(PARTICLES_AFTER_CONSONANT.iter().map(|particle| (particle, Some(true))))
+        .chain(PARTICLES_AFTER_VOWEL.iter().map(|particle| (particle, Some(false))))
+        .filter_map(|(particle, after_consonant)| {
+            let stem = word.strip_suffix(*particle)?;
+            let last = stem.chars().last().filter(|c| is_syllable(*c))?;
+            match after_consonant {
+                // 로 also follows ㄹ
+                Some(false) if *particle == "로" && final_consonant(last) == Some('ㄹ') => Some(stem),
+                Some(after_consonant) if final_consonant(last).is_some() != after_consonant => None,
+                _ => Some(stem),
+            }
+        })
+        .collect();
+    stems.sort_by_key(|stem| stem.len());
+    stems
+}
+
+// The syllable's final consonant, None for syllables ending in a vowel and for other characters
+fn final_consonant(c: char) -> Option<char> {
+    Jamo::from_syllable(c)?.tail
+}
+
+// Maximal runs of characters matching the predicate
+fn script_runs(text: &str, in_run: impl Fn(char) -> bool) -> Vec<String> {
+    let mut runs = Vec::new();
+    let mut run = String::new();
+    for c in text.chars() {
+        if in_run(c) {
+            run.push(c);
+        } else if !run.is_empty() {
+            runs.push(std::mem::take(&mut run));
+        }
+    }
+    if !run.is_empty() {
+        runs.push(run);
+    }
+    runs
+}
+
+fn is_kanji(c: char) -> bool {
+    matches!(c, '\u{4e00}'..='\u{9fff}' | '\u{3400}'..='\u{4dbf}')
+}
+
+fn is_katakana(c: char) -> bool {
+    matches!(c, '\u{30a0}'..='\u{30ff}')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn particles_are_only_taken_off_known_words() {
+        assert_eq!(tokenize("친구를 사전이 학교에서", Language::Korean), vec!["친구", "사전", "학교"]);
+        // These end like particles but aren't in the lexicon, so they are kept whole
+        assert_eq!(
+            tokenize("포도 바나나 하나 고양이", Language::Korean),
+            vec!["포도", "바나나", "하나", "고양이"]
+        );
+    }
+
+    #[test]
+    fn particle_stems_match_the_final_consonant() {
+        assert_eq!(particle_stems("사전이"), vec!["사전"]);
+        assert_eq!(particle_stems("친구이"), Vec::<&str>::new());
+        assert_eq!(particle_stems("집으로").first(), Some(&"집"));
+        assert_eq!(particle_stems("서울로"), vec!["서울"]);
+    }
+}